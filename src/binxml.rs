@@ -0,0 +1,795 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::errors::WinEvtError;
+use crate::evtx::{self, Chunk, EvtxRecord};
+use crate::filetime;
+
+const TOKEN_EOF: u8 = 0x00;
+const TOKEN_OPEN_START_ELEMENT: u8 = 0x01;
+const TOKEN_CLOSE_START_ELEMENT: u8 = 0x02;
+const TOKEN_CLOSE_EMPTY_ELEMENT: u8 = 0x03;
+const TOKEN_END_ELEMENT: u8 = 0x04;
+const TOKEN_VALUE: u8 = 0x05;
+const TOKEN_ATTRIBUTE: u8 = 0x06;
+const TOKEN_CDATA_SECTION: u8 = 0x07;
+const TOKEN_CHAR_REF: u8 = 0x08;
+const TOKEN_ENTITY_REF: u8 = 0x09;
+const TOKEN_PI_TARGET: u8 = 0x0a;
+const TOKEN_PI_DATA: u8 = 0x0b;
+const TOKEN_TEMPLATE_INSTANCE: u8 = 0x0c;
+const TOKEN_NORMAL_SUBSTITUTION: u8 = 0x0d;
+const TOKEN_OPTIONAL_SUBSTITUTION: u8 = 0x0e;
+const TOKEN_FRAGMENT_HEADER: u8 = 0x0f;
+
+/// Set on a token when more of the same kind follow, or an element has attributes
+const TOKEN_MORE_FLAG: u8 = 0x40;
+
+const TYPE_NULL: u8 = 0x00;
+const TYPE_STRING: u8 = 0x01;
+const TYPE_ANSI_STRING: u8 = 0x02;
+const TYPE_INT8: u8 = 0x03;
+const TYPE_UINT8: u8 = 0x04;
+const TYPE_INT16: u8 = 0x05;
+const TYPE_UINT16: u8 = 0x06;
+const TYPE_INT32: u8 = 0x07;
+const TYPE_UINT32: u8 = 0x08;
+const TYPE_INT64: u8 = 0x09;
+const TYPE_UINT64: u8 = 0x0a;
+const TYPE_REAL32: u8 = 0x0b;
+const TYPE_REAL64: u8 = 0x0c;
+const TYPE_BOOL: u8 = 0x0d;
+const TYPE_BINARY: u8 = 0x0e;
+const TYPE_GUID: u8 = 0x0f;
+const TYPE_SIZE_T: u8 = 0x10;
+const TYPE_FILETIME: u8 = 0x11;
+const TYPE_SYSTEMTIME: u8 = 0x12;
+const TYPE_SID: u8 = 0x13;
+const TYPE_HEX_INT32: u8 = 0x14;
+const TYPE_HEX_INT64: u8 = 0x15;
+const TYPE_BINXML: u8 = 0x21;
+const TYPE_ARRAY_FLAG: u8 = 0x80;
+
+/// Templates can nest through BinXML substitutions; anything deeper than this
+/// is a corrupt (or hostile) record pointing back at itself
+const MAX_DEPTH: usize = 32;
+
+/// Elements nested deeper than any real event; each level is a few stack
+/// frames, and carved data can claim any nesting at all
+const MAX_ELEMENT_DEPTH: usize = 256;
+
+/// A decoded BinXML node
+#[derive(Debug, Clone)]
+pub enum Node {
+    Element {
+        name: String,
+        attrs: Vec<Attribute>,
+        children: Vec<Node>,
+        empty: bool,
+    },
+    Text(String),
+    CData(String),
+    CharRef(u16),
+    EntityRef(String),
+    Pi {
+        target: String,
+        data: String,
+    },
+    Substitution {
+        index: u16,
+        optional: bool,
+    },
+    Template {
+        def: Arc<Vec<Node>>,
+        values: Vec<Value>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Vec<Node>,
+}
+
+/// A typed substitution value from a template instance
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    String(String),
+    AnsiString(String),
+    Int8(i8),
+    UInt8(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Real32(f32),
+    Real64(f64),
+    Bool(bool),
+    Binary(Vec<u8>),
+    Guid([u8; 16]),
+    SizeT(u64),
+    FileTime(u64),
+    SystemTime([u16; 8]),
+    Sid(String),
+    HexInt32(u32),
+    HexInt64(u64),
+    BinXml(Vec<Node>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn is_null(&self) -> bool {
+        match self {
+            Value::Null => true,
+            Value::Array(vals) => vals.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn write_xml(&self, out: &mut String, attr: bool) {
+        match self {
+            Value::Null => {}
+            Value::String(s) | Value::AnsiString(s) => escape(out, s, attr),
+            Value::Int8(v) => write!(out, "{}", v).unwrap(),
+            Value::UInt8(v) => write!(out, "{}", v).unwrap(),
+            Value::Int16(v) => write!(out, "{}", v).unwrap(),
+            Value::UInt16(v) => write!(out, "{}", v).unwrap(),
+            Value::Int32(v) => write!(out, "{}", v).unwrap(),
+            Value::UInt32(v) => write!(out, "{}", v).unwrap(),
+            Value::Int64(v) => write!(out, "{}", v).unwrap(),
+            Value::UInt64(v) => write!(out, "{}", v).unwrap(),
+            Value::Real32(v) => write!(out, "{}", v).unwrap(),
+            Value::Real64(v) => write!(out, "{}", v).unwrap(),
+            Value::Bool(v) => out.push_str(if *v { "true" } else { "false" }),
+            Value::Binary(b) => b.iter().for_each(|b| write!(out, "{:02X}", b).unwrap()),
+            Value::Guid(g) => out.push_str(&format_guid(g)),
+            Value::SizeT(v) | Value::HexInt64(v) => write!(out, "0x{:x}", v).unwrap(),
+            Value::HexInt32(v) => write!(out, "0x{:x}", v).unwrap(),
+            Value::FileTime(ft) => out.push_str(&filetime::to_system_time(*ft)),
+            Value::SystemTime(st) => write!(
+                out,
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}0000Z",
+                st[0], st[1], st[3], st[4], st[5], st[6], st[7]
+            )
+            .unwrap(),
+            Value::Sid(s) => out.push_str(s),
+            Value::BinXml(nodes) => write_nodes(out, nodes, &[]),
+            Value::Array(vals) => {
+                for (i, v) in vals.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    v.write_xml(out, attr);
+                }
+            }
+        }
+    }
+}
+
+/// Formats a little-endian GUID the way the event log does, `{XXXXXXXX-...}`
+pub fn format_guid(g: &[u8; 16]) -> String {
+    format!(
+        "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+        evtx::read_u32(g, 0),
+        evtx::read_u16(g, 4),
+        evtx::read_u16(g, 6),
+        g[8],
+        g[9],
+        g[10],
+        g[11],
+        g[12],
+        g[13],
+        g[14],
+        g[15]
+    )
+}
+
+fn format_sid(b: &[u8]) -> Option<String> {
+    if b.len() < 8 {
+        return None;
+    }
+
+    let count = b[1] as usize;
+    if b.len() < 8 + count * 4 {
+        return None;
+    }
+
+    let auth = b[2..8]
+        .iter()
+        .fold(0u64, |acc, &x| (acc << 8) | u64::from(x));
+    let mut sid = format!("S-{}-{}", b[0], auth);
+    for i in 0..count {
+        write!(sid, "-{}", evtx::read_u32(b, 8 + i * 4)).unwrap();
+    }
+
+    Some(sid)
+}
+
+/// Escapes `s` for XML, dropping the characters XML 1.0 can't hold at all,
+/// even as character references
+pub(crate) fn escape(out: &mut String, s: &str, attr: bool) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '\'' if attr => out.push_str("&apos;"),
+            '"' if attr => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {}
+            c => out.push(c),
+        }
+    }
+}
+
+/// Renders decoded nodes as XML, filling substitutions from `values`
+pub fn write_nodes(out: &mut String, nodes: &[Node], values: &[Value]) {
+    for node in nodes {
+        write_node(out, node, values);
+    }
+}
+
+fn write_node(out: &mut String, node: &Node, values: &[Value]) {
+    match node {
+        Node::Element {
+            name,
+            attrs,
+            children,
+            empty,
+        } => {
+            out.push('<');
+            out.push_str(name);
+
+            for attr in attrs {
+                if is_omitted(&attr.value, values) {
+                    continue;
+                }
+
+                write!(out, " {}='", attr.name).unwrap();
+                write_attr_value(out, &attr.value, values);
+                out.push('\'');
+            }
+
+            if *empty {
+                out.push_str("/>");
+            } else {
+                out.push('>');
+                write_nodes(out, children, values);
+                write!(out, "</{}>", name).unwrap();
+            }
+        }
+        Node::Text(s) => escape(out, s, false),
+        Node::CData(s) => write!(out, "<![CDATA[{}]]>", s).unwrap(),
+        Node::CharRef(c) => write!(out, "&#{};", c).unwrap(),
+        Node::EntityRef(e) => write!(out, "&{};", e).unwrap(),
+        Node::Pi { target, data } => write!(out, "<?{} {}?>", target, data).unwrap(),
+        Node::Substitution { index, .. } => {
+            if let Some(v) = values.get(*index as usize) {
+                v.write_xml(out, false);
+            }
+        }
+        Node::Template { def, values } => write_nodes(out, def, values),
+    }
+}
+
+fn write_attr_value(out: &mut String, nodes: &[Node], values: &[Value]) {
+    for node in nodes {
+        match node {
+            Node::Text(s) => escape(out, s, true),
+            Node::Substitution { index, .. } => {
+                if let Some(v) = values.get(*index as usize) {
+                    v.write_xml(out, true);
+                }
+            }
+            other => write_node(out, other, values),
+        }
+    }
+}
+
+/// Attributes made up only of empty optional substitutions aren't rendered
+fn is_omitted(nodes: &[Node], values: &[Value]) -> bool {
+    !nodes.is_empty()
+        && nodes.iter().all(|n| match n {
+            Node::Substitution {
+                index,
+                optional: true,
+            } => values.get(*index as usize).map_or(true, Value::is_null),
+            _ => false,
+        })
+}
+
+/// Names and template definitions already decoded from the current chunk
+struct ChunkCache {
+    chunk: Arc<Chunk>,
+    names: HashMap<u32, String>,
+    templates: HashMap<u32, Arc<Vec<Node>>>,
+}
+
+/// Decodes the BinXML payload of evtx records.
+///
+/// Template definitions and element names live in the chunk and are shared by
+/// every record in it, so they're cached until a record from another chunk is
+/// decoded.
+#[derive(Default)]
+pub struct BinXmlDecoder {
+    cache: Option<ChunkCache>,
+}
+
+impl BinXmlDecoder {
+    pub fn new() -> Self {
+        BinXmlDecoder { cache: None }
+    }
+
+    pub fn decode(&mut self, rec: &EvtxRecord) -> Result<Vec<Node>, WinEvtError> {
+        let chunk = rec.chunk();
+
+        let reuse = match &self.cache {
+            Some(cache) => Arc::ptr_eq(&cache.chunk, chunk),
+            None => false,
+        };
+        if !reuse {
+            self.cache = Some(ChunkCache {
+                chunk: Arc::clone(chunk),
+                names: HashMap::new(),
+                templates: HashMap::new(),
+            });
+        }
+
+        let start = rec.offset() + 24;
+        let end = start + rec.data().len();
+        let cache = self.cache.as_mut().unwrap();
+
+        let mut parser = Parser {
+            data: chunk.data(),
+            pos: start,
+            end,
+            names: &mut cache.names,
+            templates: &mut cache.templates,
+            depth: 0,
+            elements: 0,
+        };
+        parser.fragment()
+    }
+
+    /// Renders a record as XML, without decoding it again if that's been
+    /// done already
+    pub fn render(&mut self, rec: &EvtxRecord) -> Result<String, WinEvtError> {
+        let mut out = String::with_capacity(rec.data().len() * 2);
+        match rec.nodes() {
+            Some(nodes) => write_nodes(&mut out, nodes, &[]),
+            None => write_nodes(&mut out, &self.decode(rec)?, &[]),
+        }
+        Ok(out)
+    }
+}
+
+/// Walks BinXML within a chunk; every offset is relative to the chunk start
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    end: usize,
+    names: &'a mut HashMap<u32, String>,
+    templates: &'a mut HashMap<u32, Arc<Vec<Node>>>,
+    /// Templates being expanded
+    depth: usize,
+    /// Elements open, counting those of the templates being expanded
+    elements: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn err<T>(&self, what: &str) -> Result<T, WinEvtError> {
+        Err(evtx::invalid(format!(
+            "binxml: {} at chunk offset {}",
+            what, self.pos
+        )))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], WinEvtError> {
+        if self.pos + n > self.end {
+            return self.err("unexpected end of data");
+        }
+
+        let data: &'a [u8] = self.data;
+        let b = &data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(b)
+    }
+
+    fn peek(&self) -> Result<u8, WinEvtError> {
+        if self.pos >= self.end {
+            return self.err("unexpected end of data");
+        }
+        Ok(self.data[self.pos])
+    }
+
+    fn u8(&mut self) -> Result<u8, WinEvtError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, WinEvtError> {
+        Ok(evtx::read_u16(self.take(2)?, 0))
+    }
+
+    fn u32(&mut self) -> Result<u32, WinEvtError> {
+        Ok(evtx::read_u32(self.take(4)?, 0))
+    }
+
+    fn utf16(&mut self, chars: usize) -> Result<String, WinEvtError> {
+        Ok(utf16_string(self.take(chars * 2)?))
+    }
+
+    /// A length prefixed UTF-16 string as used by value and CDATA tokens
+    fn prefixed_string(&mut self) -> Result<String, WinEvtError> {
+        let len = self.u16()? as usize;
+        self.utf16(len)
+    }
+
+    /// Names are stored once per chunk and referenced by offset; the first use
+    /// stores the name inline, right where the offset points
+    fn name(&mut self) -> Result<String, WinEvtError> {
+        let off = self.u32()?;
+
+        if off as usize == self.pos {
+            let name = self.name_at(off)?;
+            let len = evtx::read_u16(self.take(8)?, 6) as usize;
+            self.take(len * 2 + 2)?;
+            return Ok(name);
+        }
+
+        self.name_at(off)
+    }
+
+    fn name_at(&mut self, off: u32) -> Result<String, WinEvtError> {
+        if let Some(name) = self.names.get(&off) {
+            return Ok(name.clone());
+        }
+
+        let off = off as usize;
+        if off + 8 > self.data.len() {
+            return self.err("name offset out of range");
+        }
+
+        let len = evtx::read_u16(self.data, off + 6) as usize;
+        if off + 8 + len * 2 > self.data.len() {
+            return self.err("name out of range");
+        }
+
+        let name = utf16_string(&self.data[off + 8..off + 8 + len * 2]);
+        self.names.insert(off as u32, name.clone());
+        Ok(name)
+    }
+
+    /// A fragment up to the end of the stream, which is all of a record
+    fn fragment(&mut self) -> Result<Vec<Node>, WinEvtError> {
+        let mut nodes = Vec::new();
+
+        while self.pos < self.end {
+            match self.peek()? {
+                TOKEN_EOF => {
+                    self.pos += 1;
+                    break;
+                }
+                TOKEN_FRAGMENT_HEADER => {
+                    self.take(4)?;
+                }
+                TOKEN_TEMPLATE_INSTANCE => nodes.push(self.template_instance()?),
+                t if t & !TOKEN_MORE_FLAG == TOKEN_OPEN_START_ELEMENT => {
+                    nodes.push(self.element()?)
+                }
+                t => return self.err(&format!("unexpected token {:#x}", t)),
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    fn element(&mut self) -> Result<Node, WinEvtError> {
+        if self.elements >= MAX_ELEMENT_DEPTH {
+            return self.err("elements nested too deeply");
+        }
+        self.elements += 1;
+
+        let has_attrs = self.u8()? & TOKEN_MORE_FLAG != 0;
+        let _dependency_id = self.u16()?;
+        let _size = self.u32()?;
+        let name = self.name()?;
+
+        let mut attrs = Vec::new();
+        if has_attrs {
+            let _attrs_size = self.u32()?;
+
+            while self.peek()? & !TOKEN_MORE_FLAG == TOKEN_ATTRIBUTE {
+                self.pos += 1;
+                attrs.push(Attribute {
+                    name: self.name()?,
+                    value: self.content(true)?,
+                });
+            }
+        }
+
+        let empty = match self.u8()? {
+            TOKEN_CLOSE_EMPTY_ELEMENT => true,
+            TOKEN_CLOSE_START_ELEMENT => false,
+            t => return self.err(&format!("unexpected token {:#x} in element", t)),
+        };
+
+        let children = if empty {
+            Vec::new()
+        } else {
+            self.content(false)?
+        };
+        self.elements -= 1;
+
+        Ok(Node::Element {
+            name,
+            attrs,
+            children,
+            empty,
+        })
+    }
+
+    /// The content of an element, up to and including its end token, or the
+    /// value of an attribute, which ends at the first token that isn't a value
+    fn content(&mut self, attr: bool) -> Result<Vec<Node>, WinEvtError> {
+        let mut nodes = Vec::new();
+
+        loop {
+            let token = self.peek()?;
+
+            match token & !TOKEN_MORE_FLAG {
+                TOKEN_END_ELEMENT if !attr => {
+                    self.pos += 1;
+                    break;
+                }
+                TOKEN_OPEN_START_ELEMENT if !attr => nodes.push(self.element()?),
+                TOKEN_TEMPLATE_INSTANCE if !attr => nodes.push(self.template_instance()?),
+                TOKEN_CDATA_SECTION if !attr => {
+                    self.pos += 1;
+                    nodes.push(Node::CData(self.prefixed_string()?));
+                }
+                TOKEN_PI_TARGET if !attr => {
+                    self.pos += 1;
+                    let target = self.name()?;
+                    let data = if self.peek()? == TOKEN_PI_DATA {
+                        self.pos += 1;
+                        self.prefixed_string()?
+                    } else {
+                        String::new()
+                    };
+                    nodes.push(Node::Pi { target, data });
+                }
+                TOKEN_VALUE => {
+                    self.pos += 1;
+                    let _type = self.u8()?;
+                    nodes.push(Node::Text(self.prefixed_string()?));
+                }
+                TOKEN_CHAR_REF => {
+                    self.pos += 1;
+                    nodes.push(Node::CharRef(self.u16()?));
+                }
+                TOKEN_ENTITY_REF => {
+                    self.pos += 1;
+                    nodes.push(Node::EntityRef(self.name()?));
+                }
+                TOKEN_NORMAL_SUBSTITUTION | TOKEN_OPTIONAL_SUBSTITUTION => {
+                    self.pos += 1;
+                    let index = self.u16()?;
+                    let _type = self.u8()?;
+                    nodes.push(Node::Substitution {
+                        index,
+                        optional: token == TOKEN_OPTIONAL_SUBSTITUTION,
+                    });
+                }
+                _ if attr => break,
+                _ => return self.err(&format!("unexpected token {:#x} in content", token)),
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    fn template_instance(&mut self) -> Result<Node, WinEvtError> {
+        if self.depth >= MAX_DEPTH {
+            return self.err("templates nested too deeply");
+        }
+
+        self.take(2)?;
+        let _template_id = self.u32()?;
+        let def_off = self.u32()?;
+
+        let def = self.template_def(def_off)?;
+        if def_off as usize == self.pos {
+            // Skip the next offset, GUID and size header plus the definition itself
+            let size = evtx::read_u32(self.take(24)?, 20) as usize;
+            self.take(size)?;
+        }
+
+        let count = self.u32()? as usize;
+        let mut descriptors = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let size = self.u16()? as usize;
+            let ty = self.u8()?;
+            self.u8()?;
+            descriptors.push((size, ty));
+        }
+
+        let mut values = Vec::with_capacity(descriptors.len());
+        for (size, ty) in descriptors {
+            let start = self.pos;
+            self.take(size)?;
+            values.push(self.value(ty, start, size)?);
+        }
+
+        Ok(Node::Template { def, values })
+    }
+
+    fn template_def(&mut self, off: u32) -> Result<Arc<Vec<Node>>, WinEvtError> {
+        if let Some(def) = self.templates.get(&off) {
+            return Ok(Arc::clone(def));
+        }
+
+        let start = off as usize + 24;
+        if start > self.data.len() {
+            return self.err("template offset out of range");
+        }
+
+        let size = evtx::read_u32(self.data, off as usize + 20) as usize;
+        let def = Arc::new(self.sub_parser(start, size)?.fragment()?);
+        self.templates.insert(off, Arc::clone(&def));
+        Ok(def)
+    }
+
+    /// A parser for a nested run of BinXML sharing this one's caches
+    fn sub_parser(&mut self, start: usize, size: usize) -> Result<Parser<'_>, WinEvtError> {
+        if start + size > self.data.len() {
+            return self.err("nested binxml out of range");
+        }
+
+        Ok(Parser {
+            data: self.data,
+            pos: start,
+            end: start + size,
+            names: &mut *self.names,
+            templates: &mut *self.templates,
+            depth: self.depth + 1,
+            elements: self.elements,
+        })
+    }
+
+    fn value(&mut self, ty: u8, start: usize, size: usize) -> Result<Value, WinEvtError> {
+        let data: &'a [u8] = self.data;
+        let b = &data[start..start + size];
+
+        if size == 0 || ty == TYPE_NULL {
+            return Ok(Value::Null);
+        }
+
+        if ty & TYPE_ARRAY_FLAG != 0 {
+            return self.array(ty & !TYPE_ARRAY_FLAG, b);
+        }
+
+        if ty == TYPE_BINXML {
+            return Ok(Value::BinXml(self.sub_parser(start, size)?.fragment()?));
+        }
+
+        match scalar(ty, b) {
+            Some(v) => Ok(v),
+            None => self.err(&format!("bad value of type {:#x} and size {}", ty, size)),
+        }
+    }
+
+    fn array(&mut self, ty: u8, b: &[u8]) -> Result<Value, WinEvtError> {
+        let items = match ty {
+            TYPE_STRING => utf16_string(b)
+                .split('\0')
+                .map(|s| Value::String(s.to_string()))
+                .collect(),
+            TYPE_ANSI_STRING => b
+                .split(|&c| c == 0)
+                .map(|s| Value::AnsiString(String::from_utf8_lossy(s).into_owned()))
+                .collect(),
+            TYPE_SID => {
+                let mut items = Vec::new();
+                let mut rest = b;
+                while rest.len() >= 8 {
+                    let len = 8 + rest[1] as usize * 4;
+                    match format_sid(rest) {
+                        Some(sid) => items.push(Value::Sid(sid)),
+                        None => break,
+                    }
+                    rest = &rest[len..];
+                }
+                items
+            }
+            _ => match fixed_size(ty, b.len()) {
+                Some(width) => b
+                    .chunks(width)
+                    .filter_map(|item| scalar(ty, item))
+                    .collect(),
+                None => return self.err(&format!("bad array of type {:#x}", ty)),
+            },
+        };
+
+        Ok(Value::Array(items))
+    }
+}
+
+/// Width of one element of a fixed size type, given a value of `len` bytes
+fn fixed_size(ty: u8, len: usize) -> Option<usize> {
+    Some(match ty {
+        TYPE_INT8 | TYPE_UINT8 => 1,
+        TYPE_INT16 | TYPE_UINT16 => 2,
+        TYPE_INT32 | TYPE_UINT32 | TYPE_REAL32 | TYPE_BOOL | TYPE_HEX_INT32 => 4,
+        TYPE_INT64 | TYPE_UINT64 | TYPE_REAL64 | TYPE_FILETIME | TYPE_HEX_INT64 => 8,
+        TYPE_GUID | TYPE_SYSTEMTIME => 16,
+        TYPE_SIZE_T if len % 8 == 0 => 8,
+        TYPE_SIZE_T => 4,
+        _ => return None,
+    })
+}
+
+fn scalar(ty: u8, b: &[u8]) -> Option<Value> {
+    let exact = |n: usize| if b.len() == n { Some(()) } else { None };
+
+    Some(match ty {
+        TYPE_STRING => Value::String(utf16_string(b)),
+        TYPE_ANSI_STRING => Value::AnsiString(
+            String::from_utf8_lossy(b)
+                .trim_end_matches('\0')
+                .to_string(),
+        ),
+        TYPE_INT8 => exact(1).map(|_| Value::Int8(b[0] as i8))?,
+        TYPE_UINT8 => exact(1).map(|_| Value::UInt8(b[0]))?,
+        TYPE_INT16 => exact(2).map(|_| Value::Int16(evtx::read_u16(b, 0) as i16))?,
+        TYPE_UINT16 => exact(2).map(|_| Value::UInt16(evtx::read_u16(b, 0)))?,
+        TYPE_INT32 => exact(4).map(|_| Value::Int32(evtx::read_u32(b, 0) as i32))?,
+        TYPE_UINT32 => exact(4).map(|_| Value::UInt32(evtx::read_u32(b, 0)))?,
+        TYPE_INT64 => exact(8).map(|_| Value::Int64(evtx::read_u64(b, 0) as i64))?,
+        TYPE_UINT64 => exact(8).map(|_| Value::UInt64(evtx::read_u64(b, 0)))?,
+        TYPE_REAL32 => exact(4).map(|_| Value::Real32(f32::from_bits(evtx::read_u32(b, 0))))?,
+        TYPE_REAL64 => exact(8).map(|_| Value::Real64(f64::from_bits(evtx::read_u64(b, 0))))?,
+        TYPE_BOOL => exact(4).map(|_| Value::Bool(evtx::read_u32(b, 0) != 0))?,
+        TYPE_BINARY => Value::Binary(b.to_vec()),
+        TYPE_GUID => {
+            exact(16)?;
+            let mut g = [0; 16];
+            g.copy_from_slice(b);
+            Value::Guid(g)
+        }
+        TYPE_SIZE_T => match b.len() {
+            4 => Value::SizeT(u64::from(evtx::read_u32(b, 0))),
+            8 => Value::SizeT(evtx::read_u64(b, 0)),
+            _ => return None,
+        },
+        TYPE_FILETIME => exact(8).map(|_| Value::FileTime(evtx::read_u64(b, 0)))?,
+        TYPE_SYSTEMTIME => {
+            exact(16)?;
+            let mut st = [0; 8];
+            for (i, v) in st.iter_mut().enumerate() {
+                *v = evtx::read_u16(b, i * 2);
+            }
+            Value::SystemTime(st)
+        }
+        TYPE_SID => Value::Sid(format_sid(b)?),
+        TYPE_HEX_INT32 => exact(4).map(|_| Value::HexInt32(evtx::read_u32(b, 0)))?,
+        TYPE_HEX_INT64 => exact(8).map(|_| Value::HexInt64(evtx::read_u64(b, 0)))?,
+        // Anything else (handles, embedded xml strings) is kept as raw bytes
+        _ => Value::Binary(b.to_vec()),
+    })
+}
+
+fn utf16_string(b: &[u8]) -> String {
+    let wide: Vec<u16> = b
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+
+    let mut s = String::from_utf16_lossy(&wide);
+    let trimmed = s.trim_end_matches('\0').len();
+    s.truncate(trimmed);
+    s
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::errors::{WinEvtError, ERROR_INVALID_DATA};
+use crate::event::Event;
+
+const VERSION: u64 = 1;
+
+/// Where each channel's last dump got up to, so the next can resume after it.
+///
+/// Bookmarks are kept in a JSON state file mapping each channel to the
+/// `EventRecordID` of the last event dumped from it:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "channels": {
+///     "Security": 1234567,
+///     "Microsoft-Windows-Sysmon/Operational": 42
+///   }
+/// }
+/// ```
+///
+/// The file can be edited by hand on any platform. Removing a channel's
+/// entry, or lowering its ID, makes the next dump start earlier; this is
+/// needed after a log has been cleared and its record IDs start over.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bookmarks {
+    channels: BTreeMap<String, u64>,
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Bookmarks::default()
+    }
+
+    /// Reads a state file, which is treated as empty if it doesn't exist yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        match fs::read_to_string(path) {
+            Ok(json) => Bookmarks::from_json(&json),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Bookmarks::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the state file, replacing it in one go so an interrupted save
+    /// leaves the old one in place
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WinEvtError> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, self.to_json())?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, WinEvtError> {
+        let invalid = |msg: &str| WinEvtError::new(ERROR_INVALID_DATA, msg);
+
+        let root: Value =
+            serde_json::from_str(json).map_err(|e| WinEvtError::parse(ERROR_INVALID_DATA, e))?;
+
+        match root.get("version").and_then(Value::as_u64) {
+            Some(VERSION) => {}
+            Some(v) => {
+                return Err(WinEvtError::new(
+                    ERROR_INVALID_DATA,
+                    format!("unknown bookmark version {}", v),
+                ))
+            }
+            None => return Err(invalid("bookmarks have no version")),
+        }
+
+        let mut bookmarks = Bookmarks::new();
+        let channels = match root.get("channels") {
+            None => return Ok(bookmarks),
+            Some(Value::Object(channels)) => channels,
+            Some(_) => return Err(invalid("bookmark channels aren't an object")),
+        };
+
+        for (name, id) in channels {
+            match id.as_u64() {
+                Some(id) => bookmarks.set(name.clone(), id),
+                None => {
+                    return Err(WinEvtError::new(
+                        ERROR_INVALID_DATA,
+                        format!("bookmark for {} isn't a record ID", name),
+                    ))
+                }
+            }
+        }
+
+        Ok(bookmarks)
+    }
+
+    pub fn to_json(&self) -> String {
+        let channels = self
+            .channels
+            .iter()
+            .map(|(name, &id)| (name.clone(), Value::from(id)))
+            .collect::<Map<_, _>>();
+
+        let mut root = Map::new();
+        root.insert("version".to_string(), Value::from(VERSION));
+        root.insert("channels".to_string(), Value::Object(channels));
+
+        let mut json = serde_json::to_string_pretty(&Value::Object(root))
+            .expect("bookmarks are always serializable");
+        json.push('\n');
+        json
+    }
+
+    /// The record ID of the last event dumped from `channel`
+    pub fn get(&self, channel: &str) -> Option<u64> {
+        self.channels.get(channel).copied()
+    }
+
+    pub fn set<S: Into<String>>(&mut self, channel: S, record_id: u64) {
+        self.channels.insert(channel.into(), record_id);
+    }
+
+    /// Moves the bookmark forward to `record_id`, never back
+    pub fn advance(&mut self, channel: &str, record_id: u64) {
+        match self.channels.get_mut(channel) {
+            Some(last) => *last = (*last).max(record_id),
+            None => self.set(channel, record_id),
+        }
+    }
+
+    pub fn remove(&mut self, channel: &str) -> Option<u64> {
+        self.channels.remove(channel)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.channels.iter().map(|(name, &id)| (name.as_str(), id))
+    }
+
+    /// An XPath selecting only the events after the bookmark
+    pub fn query_after(record_id: u64) -> String {
+        format!("*[System[EventRecordID>{}]]", record_id)
+    }
+}
+
+/// The `EventRecordID` of rendered event XML, if it has one and parses
+pub fn record_id(xml: &str) -> Option<u64> {
+    Event::from_xml(xml).ok()?.system.event_record_id
+}
@@ -0,0 +1,365 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::vec;
+
+use crate::binxml::BinXmlDecoder;
+use crate::errors::{WinEvtError, ERROR_EVT_CHANNEL_NOT_FOUND};
+use crate::evtx::{
+    self, Chunk, EvtxRecord, EvtxRenderer, CHUNK_HEADER_SIZE, CHUNK_SIGNATURE, CHUNK_SIZE,
+    RECORD_MIN_SIZE, RECORD_SIGNATURE,
+};
+use crate::source::{ChannelSource, EventSource};
+use crate::xpath::{FilteredEvents, FilteredRenderer};
+
+const READ_SIZE: usize = 1024 * 1024;
+
+/// A record's BinXML starts with a fragment header then a template instance
+/// whose definition offset, when the definition follows inline, points just
+/// past itself; that is 38 bytes into the record
+const INLINE_TEMPLATE_OFFSET: usize = 38;
+
+/// An inline definition's own BinXML starts after its 24 byte header
+const INLINE_TEMPLATE_DATA: usize = INLINE_TEMPLATE_OFFSET + 24;
+
+const FRAGMENT_HEADER: [u8; 4] = [0x0f, 0x01, 0x01, 0x00];
+
+/// Counts of what has been recovered so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CarveStats {
+    /// Chunks found with valid header and records checksums
+    pub chunks: usize,
+    /// Records read out of those chunks
+    pub records: usize,
+    /// Records found on their own, outside of any valid chunk
+    pub orphans: usize,
+    /// Orphaned records that looked right but couldn't be decoded
+    pub undecodable: usize,
+}
+
+impl CarveStats {
+    fn add(&mut self, other: &CarveStats) {
+        self.chunks += other.chunks;
+        self.records += other.records;
+        self.orphans += other.orphans;
+        self.undecodable += other.undecodable;
+    }
+}
+
+/// Recovers event records from an arbitrary stream of bytes such as a disk
+/// image, memory dump or unallocated space.
+///
+/// Whole chunks are found by their `ElfChnk` signature and kept when both
+/// their checksums match. Anywhere else, including in chunks whose records
+/// have been damaged, records are found by their `**\0\0` signature and kept
+/// when their sizes agree and their BinXML can be decoded using whatever
+/// surrounds them as the chunk.
+pub struct CarvedRecords<R: Read> {
+    reader: R,
+    buf: Vec<u8>,
+    /// Position in `buf` the scan has reached
+    pos: usize,
+    /// Stream offset of the start of `buf`
+    buf_start: u64,
+    /// Stream offset of the chunk the last decoded orphan belonged to
+    orphan_chunk: Option<u64>,
+    eof: bool,
+    pending: VecDeque<EvtxRecord>,
+    decoder: BinXmlDecoder,
+    stats: CarveStats,
+    /// Totals the stats are added to once carving stops
+    totals: Option<Arc<Mutex<CarveStats>>>,
+}
+
+impl CarvedRecords<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        Ok(CarvedRecords::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> CarvedRecords<R> {
+    pub fn new(reader: R) -> Self {
+        CarvedRecords {
+            reader,
+            buf: Vec::with_capacity(READ_SIZE + 2 * CHUNK_SIZE),
+            pos: 0,
+            buf_start: 0,
+            orphan_chunk: None,
+            eof: false,
+            pending: VecDeque::new(),
+            decoder: BinXmlDecoder::new(),
+            stats: CarveStats::default(),
+            totals: None,
+        }
+    }
+
+    /// Adds the stats to `totals` once carving stops
+    fn totals(mut self, totals: Arc<Mutex<CarveStats>>) -> Self {
+        self.totals = Some(totals);
+        self
+    }
+
+    pub fn stats(&self) -> &CarveStats {
+        &self.stats
+    }
+
+    /// Makes sure a whole chunk is buffered past the scan position, keeping a
+    /// chunk's worth of history behind it for orphaned records to refer back to
+    fn fill(&mut self) -> Result<(), WinEvtError> {
+        if self.pos > 2 * CHUNK_SIZE {
+            let drop = self.pos - CHUNK_SIZE;
+            self.buf.drain(..drop);
+            self.pos -= drop;
+            self.buf_start += drop as u64;
+        }
+
+        while !self.eof && self.buf.len() - self.pos < CHUNK_SIZE {
+            let len = self.buf.len();
+            self.buf.resize(len + READ_SIZE, 0);
+
+            match self.reader.read(&mut self.buf[len..]) {
+                Ok(0) => {
+                    self.buf.truncate(len);
+                    self.eof = true;
+                }
+                Ok(n) => self.buf.truncate(len + n),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => self.buf.truncate(len),
+                Err(e) => {
+                    self.buf.truncate(len);
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queues up the records of the chunk at the scan position if it's valid
+    fn try_chunk(&mut self) -> bool {
+        if self.buf.len() - self.pos < CHUNK_SIZE {
+            return false;
+        }
+
+        let chunk = match Chunk::parse(self.buf[self.pos..self.pos + CHUNK_SIZE].to_vec()) {
+            Ok(chunk) => chunk,
+            Err(_) => return false,
+        };
+        if chunk.compute_header_checksum() != chunk.header.header_checksum
+            || chunk.compute_records_checksum() != chunk.header.records_checksum
+        {
+            return false;
+        }
+
+        let chunk = Arc::new(chunk);
+        let mut off = CHUNK_HEADER_SIZE;
+        while off + RECORD_MIN_SIZE <= chunk.records_end() {
+            match chunk.record_at(off) {
+                Ok(rec) => {
+                    off += evtx::read_u32(chunk.data(), off + 4) as usize;
+                    self.pending.push_back(rec);
+                }
+                Err(_) => break,
+            }
+        }
+
+        self.stats.chunks += 1;
+        self.stats.records += self.pending.len();
+        true
+    }
+
+    /// Checks the record at the scan position and, if it decodes, queues it.
+    /// Returns the size of the record found.
+    fn try_record(&mut self) -> Option<usize> {
+        let buf = &self.buf[self.pos..];
+        if buf.len() < INLINE_TEMPLATE_OFFSET {
+            return None;
+        }
+
+        // Too small for a template instance, though records whose template
+        // was defined by an earlier one can be smaller than an inline one
+        let size = evtx::read_u32(buf, 4) as usize;
+        if !(INLINE_TEMPLATE_OFFSET..=CHUNK_SIZE - CHUNK_HEADER_SIZE).contains(&size)
+            || size > buf.len()
+            || evtx::read_u32(buf, size - 4) as usize != size
+            || buf[24..28] != FRAGMENT_HEADER
+        {
+            return None;
+        }
+
+        // Work out where in its chunk the record sat, either from an inline
+        // template definition, the chunk of the previous orphan or by assuming
+        // it was the chunk's first record
+        let abs = self.buf_start + self.pos as u64;
+        let inline = size >= INLINE_TEMPLATE_DATA + 4
+            && buf[28] == 0x0c
+            && buf[INLINE_TEMPLATE_DATA..INLINE_TEMPLATE_DATA + 4] == FRAGMENT_HEADER;
+        let def_off = evtx::read_u32(buf, 34) as usize;
+        let off = match def_off.checked_sub(INLINE_TEMPLATE_OFFSET) {
+            Some(off) if inline && off >= CHUNK_HEADER_SIZE && off as u64 <= abs => off,
+            _ => match self.orphan_chunk {
+                Some(start) if abs - start < CHUNK_SIZE as u64 => (abs - start) as usize,
+                _ => CHUNK_HEADER_SIZE,
+            },
+        };
+        if off + size > CHUNK_SIZE {
+            return None;
+        }
+
+        // Use the real surroundings when they're still buffered so names and
+        // templates defined by earlier records can be found
+        let mut data = vec![0; CHUNK_SIZE];
+        match self.pos.checked_sub(off) {
+            Some(start) if start + CHUNK_SIZE <= self.buf.len() => {
+                data.copy_from_slice(&self.buf[start..start + CHUNK_SIZE])
+            }
+            _ => data[off..off + size].copy_from_slice(&self.buf[self.pos..self.pos + size]),
+        }
+
+        self.stats.orphans += 1;
+        let rec = match Arc::new(Chunk::recovered(data)).record_at(off) {
+            Ok(rec) => rec,
+            Err(_) => {
+                self.stats.undecodable += 1;
+                return Some(size);
+            }
+        };
+
+        match self.decoder.decode(&rec) {
+            Ok(nodes) => {
+                self.orphan_chunk = Some(abs - off as u64);
+                self.pending.push_back(rec.with_nodes(nodes));
+            }
+            Err(_) => self.stats.undecodable += 1,
+        }
+
+        Some(size)
+    }
+}
+
+impl<R: Read> Iterator for CarvedRecords<R> {
+    type Item = Result<EvtxRecord, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(rec) = self.pending.pop_front() {
+                return Some(Ok(rec));
+            }
+
+            if let Err(e) = self.fill() {
+                self.eof = true;
+                self.pos = self.buf.len();
+                return Some(Err(e));
+            }
+
+            let found = self.buf[self.pos..]
+                .windows(CHUNK_SIGNATURE.len())
+                .position(|w| w == CHUNK_SIGNATURE || w[..4] == RECORD_SIGNATURE[..]);
+
+            match found {
+                Some(at) => self.pos += at,
+                None if self.eof => return None,
+                None => {
+                    self.pos = self.buf.len().saturating_sub(CHUNK_SIGNATURE.len() - 1);
+                    continue;
+                }
+            }
+
+            // Make sure the whole candidate is buffered before looking at it
+            if let Err(e) = self.fill() {
+                return Some(Err(e));
+            }
+
+            if self.buf[self.pos..].starts_with(CHUNK_SIGNATURE) {
+                if self.try_chunk() {
+                    self.pos += CHUNK_SIZE;
+                } else {
+                    self.pos += 1;
+                }
+            } else {
+                self.pos += self.try_record().unwrap_or(1);
+            }
+        }
+    }
+}
+
+impl<R: Read> EventSource for CarvedRecords<R> {
+    type Event = EvtxRecord;
+}
+
+impl<R: Read> Drop for CarvedRecords<R> {
+    fn drop(&mut self) {
+        if let Some(totals) = &self.totals {
+            totals
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .add(&self.stats);
+        }
+    }
+}
+
+/// A [`ChannelSource`] carving records out of raw images, one channel per
+/// image named after its file.
+///
+/// The records carved come from whichever channels happened to be in the
+/// image; the `Channel` element of each rendered event says which.
+#[derive(Default)]
+pub struct CarveSource {
+    images: Vec<(String, PathBuf)>,
+    stats: Arc<Mutex<CarveStats>>,
+}
+
+impl CarveSource {
+    pub fn new() -> Self {
+        CarveSource::default()
+    }
+
+    /// What's been recovered from every image carved so far, counting each
+    /// time one is read
+    pub fn stats(&self) -> CarveStats {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn add_image<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        self.images
+            .push((path.display().to_string(), path.to_path_buf()));
+    }
+}
+
+impl ChannelSource for CarveSource {
+    type Channels = vec::IntoIter<Result<String, WinEvtError>>;
+    type Events = FilteredEvents<CarvedRecords<BufReader<File>>, EvtxRenderer>;
+    type Renderer = FilteredRenderer<EvtxRenderer>;
+
+    fn channels(&self) -> Result<Self::Channels, WinEvtError> {
+        Ok(self
+            .images
+            .iter()
+            .map(|(name, _)| Ok(name.clone()))
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    fn events_for(&self, name: &str, query: Option<&str>) -> Result<Self::Events, WinEvtError> {
+        match self.images.iter().find(|(n, _)| n == name) {
+            Some((_, path)) => FilteredEvents::new(
+                CarvedRecords::open(path)?.totals(Arc::clone(&self.stats)),
+                query,
+                EvtxRenderer::default(),
+            ),
+            None => Err(WinEvtError::new(
+                ERROR_EVT_CHANNEL_NOT_FOUND,
+                "channel not found",
+            )),
+        }
+    }
+
+    fn renderer(&self) -> Self::Renderer {
+        FilteredRenderer(EvtxRenderer::default())
+    }
+}
@@ -5,6 +5,9 @@ use winapi::um::winevt::{EvtClose, EvtNextChannelPath, EvtOpenChannelEnum, EVT_H
 
 use crate::errors::WinError;
 use crate::errors::WinEvtError;
+use crate::event_iter::WinEventsIter;
+use crate::renderer::Renderer;
+use crate::source::ChannelSource;
 use crate::utils;
 
 pub struct ChannelIter {
@@ -63,3 +66,25 @@ impl Drop for ChannelIter {
             .expect("Couldn't close the channel enum handle")
     }
 }
+
+/// The channels and events of the local machine's event log.
+#[derive(Default)]
+pub struct LocalChannels;
+
+impl ChannelSource for LocalChannels {
+    type Channels = ChannelIter;
+    type Events = WinEventsIter;
+    type Renderer = Renderer;
+
+    fn channels(&self) -> Result<ChannelIter, WinEvtError> {
+        ChannelIter::new()
+    }
+
+    fn events_for(&self, name: &str, query: Option<&str>) -> Result<WinEventsIter, WinEvtError> {
+        WinEventsIter::get_logs_for(name, query)
+    }
+
+    fn renderer(&self) -> Renderer {
+        Renderer::new()
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use xz2::bufread::XzDecoder;
+use xz2::write::XzEncoder;
+
+use crate::errors::{WinEvtError, ERROR_INVALID_PARAMETER};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// How output files are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    None,
+    #[default]
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Codec {
+    /// File extension added after the format's, if any
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Codec::None => None,
+            Codec::Gzip => Some("gz"),
+            Codec::Zstd => Some("zst"),
+            Codec::Xz => Some("xz"),
+        }
+    }
+
+    pub fn levels(self) -> RangeInclusive<u32> {
+        match self {
+            Codec::None => 0..=0,
+            Codec::Gzip => 0..=9,
+            Codec::Zstd => 1..=22,
+            Codec::Xz => 0..=9,
+        }
+    }
+
+    /// A level that trades a little size for a lot of speed
+    pub fn default_level(self) -> u32 {
+        match self {
+            Codec::None => 0,
+            Codec::Gzip => 3,
+            Codec::Zstd => 3,
+            Codec::Xz => 6,
+        }
+    }
+
+    /// Starts compressing into `w`; the level must be in [`levels`]
+    ///
+    /// [`levels`]: Codec::levels
+    pub fn encoder<W: Write>(self, w: W, level: u32) -> Result<Encoder<W>, WinEvtError> {
+        if !self.levels().contains(&level) {
+            return Err(WinEvtError::new(
+                ERROR_INVALID_PARAMETER,
+                format!(
+                    "{} levels go from {} to {}, not {}",
+                    self,
+                    self.levels().start(),
+                    self.levels().end(),
+                    level
+                ),
+            ));
+        }
+
+        Ok(match self {
+            Codec::None => Encoder::None(w),
+            Codec::Gzip => Encoder::Gzip(GzEncoder::new(w, Compression::new(level))),
+            Codec::Zstd => Encoder::Zstd(zstd::Encoder::new(w, level as i32)?),
+            Codec::Xz => Encoder::Xz(XzEncoder::new(w, level)),
+        })
+    }
+
+    /// Works out the codec from the first few bytes of a file
+    pub fn detect(magic: &[u8]) -> Codec {
+        if magic.starts_with(GZIP_MAGIC) {
+            Codec::Gzip
+        } else if magic.starts_with(ZSTD_MAGIC) {
+            Codec::Zstd
+        } else if magic.starts_with(XZ_MAGIC) {
+            Codec::Xz
+        } else {
+            Codec::None
+        }
+    }
+
+    /// Decompresses `r` with whichever codec it turns out to use
+    pub fn decoder<'a, R: BufRead + 'a>(mut r: R) -> Result<Box<dyn Read + 'a>, WinEvtError> {
+        Ok(match Codec::detect(r.fill_buf()?) {
+            Codec::None => Box::new(r),
+            Codec::Gzip => Box::new(MultiGzDecoder::new(r)),
+            Codec::Zstd => Box::new(zstd::Decoder::with_buffer(r)?),
+            Codec::Xz => Box::new(XzDecoder::new_multi_decoder(r)),
+        })
+    }
+
+    /// Opens a file written with any codec, or none
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Box<dyn Read>, WinEvtError> {
+        Codec::decoder(BufReader::new(File::open(path)?))
+    }
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Codec::None => "none",
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+        })
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Codec::None),
+            "gzip" | "gz" => Ok(Codec::Gzip),
+            "zstd" | "zst" => Ok(Codec::Zstd),
+            "xz" => Ok(Codec::Xz),
+            other => Err(format!(
+                "unknown codec {}, expected gzip, zstd, xz or none",
+                other
+            )),
+        }
+    }
+}
+
+/// A compressing writer for one of the [`Codec`]s
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Xz(XzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes out anything buffered along with the codec's trailer
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::None(mut w) => {
+                w.flush()?;
+                Ok(w)
+            }
+            Encoder::Gzip(e) => e.finish(),
+            Encoder::Zstd(e) => e.finish(),
+            Encoder::Xz(e) => e.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(w) => w.write(buf),
+            Encoder::Gzip(e) => e.write(buf),
+            Encoder::Zstd(e) => e.write(buf),
+            Encoder::Xz(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(w) => w.flush(),
+            Encoder::Gzip(e) => e.flush(),
+            Encoder::Zstd(e) => e.flush(),
+            Encoder::Xz(e) => e.flush(),
+        }
+    }
+}
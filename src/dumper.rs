@@ -0,0 +1,232 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+
+use crate::bookmark::{self, Bookmarks};
+use crate::errors::{WinEvtError, ERROR_CANCELLED};
+use crate::output::EventWriter;
+use crate::source::{ChannelSource, EventRenderer};
+
+/// Events a worker renders before handing them over to be written
+const BATCH_SIZE: usize = 256;
+/// Batches waiting to be written before workers have to wait too
+const QUEUE_DEPTH: usize = 16;
+
+/// How dumping one channel went
+#[derive(Debug)]
+pub struct ChannelReport {
+    pub channel: String,
+    /// Events written, including any before an error
+    pub events: u64,
+    /// Events the output couldn't take, which were left out
+    pub skipped: u64,
+    /// What stopped the channel being read, or else why the first event
+    /// skipped couldn't be written
+    pub error: Option<WinEvtError>,
+}
+
+enum Message {
+    /// Rendered events of the channel at this index, with their record IDs
+    Events(usize, Vec<(String, Option<u64>)>),
+    /// The channel at this index has been read, or failed to be
+    Done(usize, Result<(), WinEvtError>),
+}
+
+/// Dumps the channels of a [`ChannelSource`] on a pool of worker threads.
+///
+/// Each worker takes the next channel not yet started, reads it with its own
+/// renderer and hands the rendered events over in batches. Only the calling
+/// thread writes, so output and bookmarks need not be shared; events of one
+/// channel stay in order, but batches from different channels interleave.
+pub struct ChannelDumper<'a, S> {
+    source: &'a S,
+    jobs: usize,
+    query: Option<&'a str>,
+    state: Option<&'a Path>,
+}
+
+impl<'a, S: ChannelSource + Sync> ChannelDumper<'a, S> {
+    /// A dumper with one worker per available CPU
+    pub fn new(source: &'a S) -> Self {
+        ChannelDumper {
+            source,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+            query: None,
+            state: None,
+        }
+    }
+
+    /// How many channels are read at once; at least one
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// XPath query to filter each channel's events with
+    pub fn query(mut self, query: Option<&'a str>) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// State file the bookmarks are saved to as each channel finishes, so an
+    /// interrupted dump resumes from the last channel it got through
+    pub fn state(mut self, state: Option<&'a Path>) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Writes the events of every channel to `out`, skipping and advancing
+    /// past those in `bookmarks`.
+    ///
+    /// With a [`state`] file, `out` is flushed and the bookmarks saved each
+    /// time a channel finishes.
+    ///
+    /// A channel failing doesn't stop the others; the error is kept in its
+    /// report, as are events `out` fails to parse, which are skipped. The
+    /// output itself failing, or saving the bookmarks, stops everything and
+    /// is returned instead.
+    ///
+    /// [`state`]: ChannelDumper::state
+    pub fn dump(
+        &self,
+        channels: &[String],
+        out: &mut dyn EventWriter,
+        mut bookmarks: Option<&mut Bookmarks>,
+    ) -> Result<Vec<ChannelReport>, WinEvtError> {
+        let mut reports: Vec<_> = channels
+            .iter()
+            .map(|c| ChannelReport {
+                channel: c.clone(),
+                events: 0,
+                skipped: 0,
+                error: None,
+            })
+            .collect();
+        let after: Vec<_> = channels
+            .iter()
+            .map(|c| bookmarks.as_ref().and_then(|b| b.get(c)))
+            .collect();
+        let next = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            let (tx, rx) = mpsc::sync_channel(QUEUE_DEPTH);
+            for _ in 0..self.jobs.min(channels.len()) {
+                let tx = tx.clone();
+                let (after, next) = (&after, &next);
+                scope.spawn(move || self.work(channels, after, next, tx));
+            }
+            drop(tx);
+
+            for msg in rx {
+                match msg {
+                    Message::Events(i, events) => {
+                        for (xml, id) in events {
+                            let report = &mut reports[i];
+                            match out.write_event(&xml) {
+                                Ok(()) => report.events += 1,
+                                Err(e @ WinEvtError::Parse { .. }) => {
+                                    report.skipped += 1;
+                                    report.error.get_or_insert(e);
+                                }
+                                Err(e) => return Err(e),
+                            }
+                            if let (Some(bookmarks), Some(id)) = (bookmarks.as_deref_mut(), id) {
+                                bookmarks.advance(&channels[i], id);
+                            }
+                        }
+                    }
+                    Message::Done(i, result) => {
+                        if let Err(e) = result {
+                            reports[i].error = Some(e);
+                        }
+                        if let (Some(path), Some(bookmarks)) = (self.state, bookmarks.as_deref()) {
+                            out.flush()?;
+                            bookmarks.save(path)?;
+                        }
+                    }
+                }
+            }
+
+            Ok(reports)
+        })
+    }
+
+    fn work(
+        &self,
+        channels: &[String],
+        after: &[Option<u64>],
+        next: &AtomicUsize,
+        tx: SyncSender<Message>,
+    ) {
+        let mut rend = self.source.renderer();
+
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let chan = match channels.get(i) {
+                Some(chan) => chan,
+                None => return,
+            };
+
+            let result = self.dump_chan(i, chan, after[i], &mut rend, &tx);
+            // Nothing is being written any more
+            if tx.send(Message::Done(i, result)).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn dump_chan(
+        &self,
+        i: usize,
+        chan: &str,
+        after: Option<u64>,
+        rend: &mut S::Renderer,
+        tx: &SyncSender<Message>,
+    ) -> Result<(), WinEvtError> {
+        eprintln!("Processing {}", chan);
+
+        // Have the source skip what's been dumped already when there's no query
+        // to combine that with; otherwise it's skipped here
+        let resume = after.map(Bookmarks::query_after);
+        let iter = self
+            .source
+            .events_for(chan, self.query.or(resume.as_deref()))?;
+
+        let send = |batch| {
+            tx.send(Message::Events(i, batch))
+                .map_err(|_| WinEvtError::new(ERROR_CANCELLED, "output closed"))
+        };
+
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for e in iter {
+            // Write what was rendered before the error, as a sequential dump would
+            let xml = match e.and_then(|e| rend.render(e)) {
+                Ok(xml) => xml,
+                Err(e) => {
+                    if !batch.is_empty() {
+                        send(batch)?;
+                    }
+                    return Err(e);
+                }
+            };
+            let id = bookmark::record_id(&xml);
+            if id.is_some() && id <= after {
+                continue;
+            }
+
+            batch.push((xml, id));
+            if batch.len() == BATCH_SIZE {
+                send(std::mem::replace(
+                    &mut batch,
+                    Vec::with_capacity(BATCH_SIZE),
+                ))?;
+            }
+        }
+
+        if !batch.is_empty() {
+            send(batch)?;
+        }
+        Ok(())
+    }
+}
@@ -1,41 +1,320 @@
-use std::fmt::{Display, Error, Formatter};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
 
+#[cfg(windows)]
 use widestring::U16String;
+#[cfg(windows)]
 use winapi::shared::winerror;
-use winapi::shared::winerror::*;
+#[cfg(windows)]
 use winapi::um::errhandlingapi::GetLastError;
+#[cfg(windows)]
 use winapi::um::winevt::EvtGetExtendedStatus;
+#[cfg(windows)]
 use windows_error::WindowsError;
 
-pub enum WinError {
+pub const ERROR_ACCESS_DENIED: u32 = 5;
+pub const ERROR_INVALID_DATA: u32 = 13;
+pub const ERROR_INVALID_PARAMETER: u32 = 87;
+pub const ERROR_BAD_EXE_FORMAT: u32 = 193;
+pub const ERROR_CANCELLED: u32 = 1223;
+pub const ERROR_RESOURCE_TYPE_NOT_FOUND: u32 = 1813;
+
+pub const ERROR_EVT_INVALID_CHANNEL_PATH: u32 = 15000;
+pub const ERROR_EVT_INVALID_QUERY: u32 = 15001;
+pub const ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND: u32 = 15002;
+pub const ERROR_EVT_EVENT_TEMPLATE_NOT_FOUND: u32 = 15003;
+pub const ERROR_EVT_INVALID_PUBLISHER_NAME: u32 = 15004;
+pub const ERROR_EVT_INVALID_EVENT_DATA: u32 = 15005;
+pub const ERROR_EVT_CHANNEL_NOT_FOUND: u32 = 15007;
+pub const ERROR_EVT_MALFORMED_XML_TEXT: u32 = 15008;
+pub const ERROR_EVT_SUBSCRIPTION_TO_DIRECT_CHANNEL: u32 = 15009;
+pub const ERROR_EVT_CONFIGURATION_ERROR: u32 = 15010;
+pub const ERROR_EVT_QUERY_RESULT_STALE: u32 = 15011;
+pub const ERROR_EVT_QUERY_RESULT_INVALID_POSITION: u32 = 15012;
+pub const ERROR_EVT_NON_VALIDATING_MSXML: u32 = 15013;
+pub const ERROR_EVT_FILTER_ALREADYSCOPED: u32 = 15014;
+pub const ERROR_EVT_FILTER_NOTELTSET: u32 = 15015;
+pub const ERROR_EVT_FILTER_INVARG: u32 = 15016;
+pub const ERROR_EVT_FILTER_INVTEST: u32 = 15017;
+pub const ERROR_EVT_FILTER_INVTYPE: u32 = 15018;
+pub const ERROR_EVT_FILTER_PARSEERR: u32 = 15019;
+pub const ERROR_EVT_FILTER_UNSUPPORTEDOP: u32 = 15020;
+pub const ERROR_EVT_FILTER_UNEXPECTEDTOKEN: u32 = 15021;
+pub const ERROR_EVT_INVALID_OPERATION_OVER_ENABLED_DIRECT_CHANNEL: u32 = 15022;
+pub const ERROR_EVT_INVALID_CHANNEL_PROPERTY_VALUE: u32 = 15023;
+pub const ERROR_EVT_INVALID_PUBLISHER_PROPERTY_VALUE: u32 = 15024;
+pub const ERROR_EVT_CHANNEL_CANNOT_ACTIVATE: u32 = 15025;
+pub const ERROR_EVT_FILTER_TOO_COMPLEX: u32 = 15026;
+pub const ERROR_EVT_MESSAGE_NOT_FOUND: u32 = 15027;
+pub const ERROR_EVT_MESSAGE_ID_NOT_FOUND: u32 = 15028;
+pub const ERROR_EVT_UNRESOLVED_VALUE_INSERT: u32 = 15029;
+pub const ERROR_EVT_UNRESOLVED_PARAMETER_INSERT: u32 = 15030;
+pub const ERROR_EVT_MAX_INSERTS_REACHED: u32 = 15031;
+pub const ERROR_EVT_EVENT_DEFINITION_NOT_FOUND: u32 = 15032;
+pub const ERROR_EVT_MESSAGE_LOCALE_NOT_FOUND: u32 = 15033;
+pub const ERROR_EVT_VERSION_TOO_OLD: u32 = 15034;
+pub const ERROR_EVT_VERSION_TOO_NEW: u32 = 15035;
+pub const ERROR_EVT_CANNOT_OPEN_CHANNEL_OF_QUERY: u32 = 15036;
+pub const ERROR_EVT_PUBLISHER_DISABLED: u32 = 15037;
+pub const ERROR_EVT_FILTER_OUT_OF_RANGE: u32 = 15038;
+
+/// The text for each of the event log's own error codes
+pub const EVT_ERRORS: &[(u32, &str)] = &[
+    (ERROR_EVT_INVALID_CHANNEL_PATH, "invalid channel path"),
+    (ERROR_EVT_INVALID_QUERY, "invalid query"),
+    (
+        ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND,
+        "publisher metadata not found",
+    ),
+    (
+        ERROR_EVT_EVENT_TEMPLATE_NOT_FOUND,
+        "event template not found",
+    ),
+    (ERROR_EVT_INVALID_PUBLISHER_NAME, "invalid publisher name"),
+    (ERROR_EVT_INVALID_EVENT_DATA, "invalid event data"),
+    (ERROR_EVT_CHANNEL_NOT_FOUND, "channel not found"),
+    (ERROR_EVT_MALFORMED_XML_TEXT, "malformed xml text"),
+    (
+        ERROR_EVT_SUBSCRIPTION_TO_DIRECT_CHANNEL,
+        "subscription to direct channel",
+    ),
+    (ERROR_EVT_CONFIGURATION_ERROR, "configuration error"),
+    (ERROR_EVT_QUERY_RESULT_STALE, "query result stale"),
+    (
+        ERROR_EVT_QUERY_RESULT_INVALID_POSITION,
+        "query result invalid position",
+    ),
+    (ERROR_EVT_NON_VALIDATING_MSXML, "non validating msxml"),
+    (ERROR_EVT_FILTER_ALREADYSCOPED, "filter alreadyscoped"),
+    (ERROR_EVT_FILTER_NOTELTSET, "filter noteltset"),
+    (ERROR_EVT_FILTER_INVARG, "filter invarg"),
+    (ERROR_EVT_FILTER_INVTEST, "filter invtest"),
+    (ERROR_EVT_FILTER_INVTYPE, "filter invtype"),
+    (ERROR_EVT_FILTER_PARSEERR, "filter parseerr"),
+    (ERROR_EVT_FILTER_UNSUPPORTEDOP, "filter unsupportedop"),
+    (ERROR_EVT_FILTER_UNEXPECTEDTOKEN, "filter unexpectedtoken"),
+    (
+        ERROR_EVT_INVALID_OPERATION_OVER_ENABLED_DIRECT_CHANNEL,
+        "invalid operation over enabled direct channel",
+    ),
+    (
+        ERROR_EVT_INVALID_CHANNEL_PROPERTY_VALUE,
+        "invalid channel property value",
+    ),
+    (
+        ERROR_EVT_INVALID_PUBLISHER_PROPERTY_VALUE,
+        "invalid publisher property value",
+    ),
+    (ERROR_EVT_CHANNEL_CANNOT_ACTIVATE, "channel cannot activate"),
+    (ERROR_EVT_FILTER_TOO_COMPLEX, "filter too complex"),
+    (ERROR_EVT_MESSAGE_NOT_FOUND, "message not found"),
+    (ERROR_EVT_MESSAGE_ID_NOT_FOUND, "message id not found"),
+    (ERROR_EVT_UNRESOLVED_VALUE_INSERT, "unresolved value insert"),
+    (
+        ERROR_EVT_UNRESOLVED_PARAMETER_INSERT,
+        "unresolved parameter insert",
+    ),
+    (ERROR_EVT_MAX_INSERTS_REACHED, "max inserts reached"),
+    (
+        ERROR_EVT_EVENT_DEFINITION_NOT_FOUND,
+        "event definition not found",
+    ),
+    (
+        ERROR_EVT_MESSAGE_LOCALE_NOT_FOUND,
+        "message locale not found",
+    ),
+    (ERROR_EVT_VERSION_TOO_OLD, "version too old"),
+    (ERROR_EVT_VERSION_TOO_NEW, "version too new"),
+    (
+        ERROR_EVT_CANNOT_OPEN_CHANNEL_OF_QUERY,
+        "cannot open channel of query",
+    ),
+    (ERROR_EVT_PUBLISHER_DISABLED, "publisher disabled"),
+    (ERROR_EVT_FILTER_OUT_OF_RANGE, "filter out of range"),
+];
+
+/// The text for an `ERROR_EVT_*` code
+pub fn describe(code: u32) -> Option<&'static str> {
+    EVT_ERRORS
+        .iter()
+        .find(|&&(c, _)| c == code)
+        .map(|&(_, text)| text)
+}
+
+/// Results of the `Evt*` calls the iterators handle themselves
+#[cfg(windows)]
+pub(crate) enum WinError {
     NoMoreItems,
     InsufficientBuffer,
     Err(WinEvtError),
 }
 
+#[cfg(windows)]
 impl WinError {
     #[inline]
-    pub fn into_err(self) -> WinEvtError {
+    pub(crate) fn into_err(self) -> WinEvtError {
         match self {
-            WinError::NoMoreItems => WinEvtError::from_dword(ERROR_NO_MORE_ITEMS),
-            WinError::InsufficientBuffer => WinEvtError::from_dword(ERROR_INSUFFICIENT_BUFFER),
+            WinError::NoMoreItems => WinEvtError::from_dword(winerror::ERROR_NO_MORE_ITEMS),
+            WinError::InsufficientBuffer => {
+                WinEvtError::from_dword(winerror::ERROR_INSUFFICIENT_BUFFER)
+            }
             WinError::Err(e) => e,
         }
     }
 }
 
+/// Everything that can go wrong reading, rendering or writing events. Each
+/// keeps the system error code it stands for, see [`code`].
+///
+/// [`code`]: WinEvtError::code
 #[derive(Debug)]
-pub struct WinEvtError {
-    pub errno: u32,
-    pub msg: String,
+pub enum WinEvtError {
+    /// A query, XPath filter or query list that can't be parsed or run
+    QuerySyntax {
+        code: u32,
+        msg: String,
+    },
+    /// A channel, or the log file standing in for one, that doesn't exist
+    ChannelNotFound {
+        code: u32,
+        msg: String,
+    },
+    AccessDenied {
+        code: u32,
+        msg: String,
+    },
+    /// A publisher, or its metadata, messages or templates, that can't be found
+    MetadataMissing {
+        code: u32,
+        msg: String,
+    },
+    /// Reading input failed
+    Io(io::Error),
+    /// Data that isn't what it should be: event XML, log files, caches,
+    /// manifests or images
+    Parse {
+        code: u32,
+        msg: String,
+        source: Option<Box<dyn Error + Send + Sync>>,
+    },
+    /// Writing events failed, or the output was closed
+    Output {
+        code: u32,
+        msg: String,
+        source: Option<io::Error>,
+    },
+    /// Any other failure of the event log or the system
+    Api {
+        code: u32,
+        msg: String,
+    },
+}
+
+impl WinEvtError {
+    /// An error with a system error code, of the kind the code belongs to
+    pub fn new<S: Into<String>>(code: u32, msg: S) -> Self {
+        let msg = msg.into();
+        match code {
+            ERROR_EVT_INVALID_QUERY
+            | ERROR_EVT_FILTER_ALREADYSCOPED..=ERROR_EVT_FILTER_UNEXPECTEDTOKEN
+            | ERROR_EVT_FILTER_TOO_COMPLEX
+            | ERROR_EVT_FILTER_OUT_OF_RANGE => WinEvtError::QuerySyntax { code, msg },
+            ERROR_EVT_INVALID_CHANNEL_PATH
+            | ERROR_EVT_CHANNEL_NOT_FOUND
+            | ERROR_EVT_CANNOT_OPEN_CHANNEL_OF_QUERY => WinEvtError::ChannelNotFound { code, msg },
+            ERROR_ACCESS_DENIED => WinEvtError::AccessDenied { code, msg },
+            ERROR_RESOURCE_TYPE_NOT_FOUND
+            | ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND
+            | ERROR_EVT_EVENT_TEMPLATE_NOT_FOUND
+            | ERROR_EVT_INVALID_PUBLISHER_NAME
+            | ERROR_EVT_MESSAGE_NOT_FOUND
+            | ERROR_EVT_MESSAGE_ID_NOT_FOUND
+            | ERROR_EVT_EVENT_DEFINITION_NOT_FOUND
+            | ERROR_EVT_MESSAGE_LOCALE_NOT_FOUND => WinEvtError::MetadataMissing { code, msg },
+            ERROR_INVALID_DATA
+            | ERROR_BAD_EXE_FORMAT
+            | ERROR_EVT_INVALID_EVENT_DATA
+            | ERROR_EVT_MALFORMED_XML_TEXT => WinEvtError::Parse {
+                code,
+                msg,
+                source: None,
+            },
+            ERROR_CANCELLED => WinEvtError::Output {
+                code,
+                msg,
+                source: None,
+            },
+            _ => WinEvtError::Api { code, msg },
+        }
+    }
+
+    /// Data that failed to parse, keeping what the parser said about it
+    pub fn parse<E: Error + Send + Sync + 'static>(code: u32, source: E) -> Self {
+        WinEvtError::Parse {
+            code,
+            msg: source.to_string(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// An error writing events
+    pub fn output(e: io::Error) -> Self {
+        WinEvtError::Output {
+            code: e.raw_os_error().unwrap_or(0) as u32,
+            msg: e.to_string(),
+            source: Some(e),
+        }
+    }
+
+    /// The system error code, or zero for I/O errors that didn't come with one
+    pub fn code(&self) -> u32 {
+        match self {
+            WinEvtError::Io(e) => e.raw_os_error().unwrap_or(0) as u32,
+            WinEvtError::QuerySyntax { code, .. }
+            | WinEvtError::ChannelNotFound { code, .. }
+            | WinEvtError::AccessDenied { code, .. }
+            | WinEvtError::MetadataMissing { code, .. }
+            | WinEvtError::Parse { code, .. }
+            | WinEvtError::Output { code, .. }
+            | WinEvtError::Api { code, .. } => *code,
+        }
+    }
 }
 
 impl Display for WinEvtError {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        f.write_str(self.msg.as_str())
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WinEvtError::Io(e) => e.fmt(f),
+            WinEvtError::QuerySyntax { msg, .. }
+            | WinEvtError::ChannelNotFound { msg, .. }
+            | WinEvtError::AccessDenied { msg, .. }
+            | WinEvtError::MetadataMissing { msg, .. }
+            | WinEvtError::Parse { msg, .. }
+            | WinEvtError::Output { msg, .. }
+            | WinEvtError::Api { msg, .. } => f.write_str(msg),
+        }
+    }
+}
+
+impl Error for WinEvtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WinEvtError::Io(e) => Some(e),
+            WinEvtError::Parse { source, .. } => source.as_deref().map(|e| e as _),
+            WinEvtError::Output { source, .. } => source.as_ref().map(|e| e as _),
+            _ => None,
+        }
     }
 }
 
+impl From<io::Error> for WinEvtError {
+    fn from(e: io::Error) -> Self {
+        WinEvtError::Io(e)
+    }
+}
+
+#[cfg(windows)]
 fn try_detailed_error() -> Option<String> {
     let mut buf = Vec::with_capacity(1024 * 32);
     let mut used = 0;
@@ -65,63 +344,17 @@ fn try_detailed_error() -> Option<String> {
     }
 }
 
+#[cfg(windows)]
 impl WinEvtError {
     pub fn from_last_error() -> Self {
         Self::from_dword(unsafe { GetLastError() })
     }
 
-    pub fn from_dword(errno: u32) -> Self {
-        let msg: String = match errno {
-            ERROR_EVT_CANNOT_OPEN_CHANNEL_OF_QUERY => "cannot open channel of query".to_string(),
-            ERROR_EVT_CHANNEL_CANNOT_ACTIVATE => "channel cannot activate".to_string(),
-            ERROR_EVT_CHANNEL_NOT_FOUND => "channel not found".to_string(),
-            ERROR_EVT_CONFIGURATION_ERROR => "configuration error".to_string(),
-            ERROR_EVT_EVENT_DEFINITION_NOT_FOUND => "event definition not found".to_string(),
-            ERROR_EVT_EVENT_TEMPLATE_NOT_FOUND => "event template not found".to_string(),
-            ERROR_EVT_FILTER_ALREADYSCOPED => "filter alreadyscoped".to_string(),
-            ERROR_EVT_FILTER_INVARG => "filter invarg".to_string(),
-            ERROR_EVT_FILTER_INVTEST => "filter invtest".to_string(),
-            ERROR_EVT_FILTER_INVTYPE => "filter invtype".to_string(),
-            ERROR_EVT_FILTER_NOTELTSET => "filter noteltset".to_string(),
-            ERROR_EVT_FILTER_OUT_OF_RANGE => "filter out of range".to_string(),
-            ERROR_EVT_FILTER_PARSEERR => "filter parseerr".to_string(),
-            ERROR_EVT_FILTER_TOO_COMPLEX => "filter too complex".to_string(),
-            ERROR_EVT_FILTER_UNEXPECTEDTOKEN => "filter unexpectedtoken".to_string(),
-            ERROR_EVT_FILTER_UNSUPPORTEDOP => "filter unsupportedop".to_string(),
-            ERROR_EVT_INVALID_CHANNEL_PATH => "invalid channel path".to_string(),
-            ERROR_EVT_INVALID_CHANNEL_PROPERTY_VALUE => {
-                "invalid channel property value".to_string()
-            }
-            ERROR_EVT_INVALID_EVENT_DATA => "invalid event data".to_string(),
-            ERROR_EVT_INVALID_OPERATION_OVER_ENABLED_DIRECT_CHANNEL => {
-                "invalid operation over enabled direct channel".to_string()
-            }
-            ERROR_EVT_INVALID_PUBLISHER_NAME => "invalid publisher name".to_string(),
-            ERROR_EVT_INVALID_PUBLISHER_PROPERTY_VALUE => {
-                "invalid publisher property value".to_string()
-            }
-            ERROR_EVT_INVALID_QUERY => "invalid query".to_string(),
-            ERROR_EVT_MALFORMED_XML_TEXT => "malformed xml text".to_string(),
-            ERROR_EVT_MAX_INSERTS_REACHED => "max inserts reached".to_string(),
-            ERROR_EVT_MESSAGE_ID_NOT_FOUND => "message id not found".to_string(),
-            ERROR_EVT_MESSAGE_LOCALE_NOT_FOUND => "message locale not found".to_string(),
-            ERROR_EVT_MESSAGE_NOT_FOUND => "message not found".to_string(),
-            ERROR_EVT_NON_VALIDATING_MSXML => "non validating msxml".to_string(),
-            ERROR_EVT_PUBLISHER_DISABLED => "publisher disabled".to_string(),
-            ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND => "publisher metadata not found".to_string(),
-            ERROR_EVT_QUERY_RESULT_INVALID_POSITION => "query result invalid position".to_string(),
-            ERROR_EVT_QUERY_RESULT_STALE => "query result stale".to_string(),
-            ERROR_EVT_SUBSCRIPTION_TO_DIRECT_CHANNEL => {
-                "subscription to direct channel".to_string()
-            }
-            ERROR_EVT_UNRESOLVED_PARAMETER_INSERT => "unresolved parameter insert".to_string(),
-            ERROR_EVT_UNRESOLVED_VALUE_INSERT => "unresolved value insert".to_string(),
-            ERROR_EVT_VERSION_TOO_NEW => "version too new".to_string(),
-            ERROR_EVT_VERSION_TOO_OLD => "version too old".to_string(),
-
-            other => try_detailed_error().unwrap_or_else(|| WindowsError::new(other).to_string()),
+    pub fn from_dword(code: u32) -> Self {
+        let msg = match describe(code) {
+            Some(text) => text.to_string(),
+            None => try_detailed_error().unwrap_or_else(|| WindowsError::new(code).to_string()),
         };
-
-        WinEvtError { errno, msg }
+        WinEvtError::new(code, msg)
     }
 }
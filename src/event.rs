@@ -0,0 +1,270 @@
+use std::str::FromStr;
+
+use roxmltree::{Document, Node};
+
+use crate::errors::{WinEvtError, ERROR_EVT_MALFORMED_XML_TEXT};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Provider {
+    pub name: Option<String>,
+    pub guid: Option<String>,
+    pub event_source_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Correlation {
+    pub activity_id: Option<String>,
+    pub related_activity_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Execution {
+    pub process_id: Option<u32>,
+    pub thread_id: Option<u32>,
+}
+
+/// The `<System>` block every event carries
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct System {
+    pub provider: Provider,
+    pub event_id: Option<u32>,
+    pub qualifiers: Option<u16>,
+    pub version: Option<u8>,
+    pub level: Option<u8>,
+    pub task: Option<u16>,
+    pub opcode: Option<u8>,
+    pub keywords: Option<u64>,
+    /// As rendered, e.g. `2019-05-14T18:16:09.2851463Z`
+    pub time_created: Option<String>,
+    pub event_record_id: Option<u64>,
+    pub correlation: Correlation,
+    pub execution: Execution,
+    pub channel: Option<String>,
+    pub computer: Option<String>,
+    pub user_id: Option<String>,
+}
+
+/// A single `<Data>` entry from `<EventData>`; older providers leave them unnamed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Data {
+    pub name: Option<String>,
+    pub value: String,
+}
+
+/// A generic element, used for `<UserData>` whose layout is up to the provider
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    fn from_node(node: Node) -> Self {
+        Element {
+            name: node.tag_name().name().to_string(),
+            attrs: node
+                .attributes()
+                .map(|a| (a.name().to_string(), a.value().to_string()))
+                .collect(),
+            text: node
+                .children()
+                .filter(Node::is_text)
+                .filter_map(|n| n.text())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            children: node
+                .children()
+                .filter(Node::is_element)
+                .map(Element::from_node)
+                .collect(),
+        }
+    }
+}
+
+/// The display text the event log adds for an event when formatting its
+/// message, or [`EventNames`] adds for its numeric `System` fields
+///
+/// [`EventNames`]: crate::names::EventNames
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderingInfo {
+    pub culture: Option<String>,
+    pub message: Option<String>,
+    pub level: Option<String>,
+    pub task: Option<String>,
+    pub opcode: Option<String>,
+    pub channel: Option<String>,
+    pub provider: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+/// An event parsed from the XML an [`EventRenderer`] produces.
+///
+/// [`EventRenderer`]: crate::source::EventRenderer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Event {
+    pub system: System,
+    pub event_data: Vec<Data>,
+    /// Hex encoded `<Binary>` data from `<EventData>`
+    pub binary: Option<String>,
+    /// The elements under `<UserData>`, usually just the one
+    pub user_data: Vec<Element>,
+    pub rendering_info: Option<RenderingInfo>,
+}
+
+impl Event {
+    pub fn from_xml(xml: &str) -> Result<Self, WinEvtError> {
+        let doc = Document::parse(xml)
+            .map_err(|e| WinEvtError::parse(ERROR_EVT_MALFORMED_XML_TEXT, e))?;
+        Event::from_doc(&doc)
+    }
+
+    /// An event from XML that's already been parsed
+    pub fn from_doc(doc: &Document) -> Result<Self, WinEvtError> {
+        let root = doc.root_element();
+        if root.tag_name().name() != "Event" {
+            return Err(WinEvtError::new(
+                ERROR_EVT_MALFORMED_XML_TEXT,
+                format!("expected an Event element, not {}", root.tag_name().name()),
+            ));
+        }
+
+        let mut event = Event::default();
+
+        for node in root.children().filter(Node::is_element) {
+            match node.tag_name().name() {
+                "System" => event.system = parse_system(node),
+                "EventData" => {
+                    for data in node.children().filter(Node::is_element) {
+                        match data.tag_name().name() {
+                            "Data" => event.event_data.push(Data {
+                                name: data.attribute("Name").map(str::to_string),
+                                value: text(data).unwrap_or_default(),
+                            }),
+                            "Binary" => event.binary = text(data),
+                            _ => {}
+                        }
+                    }
+                }
+                "UserData" => {
+                    event.user_data = node
+                        .children()
+                        .filter(Node::is_element)
+                        .map(Element::from_node)
+                        .collect()
+                }
+                "RenderingInfo" => event.rendering_info = Some(parse_rendering_info(node)),
+                _ => {}
+            }
+        }
+
+        Ok(event)
+    }
+
+    /// Looks up a named `<Data>` value
+    pub fn data(&self, name: &str) -> Option<&str> {
+        self.event_data
+            .iter()
+            .find(|d| d.name.as_deref() == Some(name))
+            .map(|d| d.value.as_str())
+    }
+}
+
+impl FromStr for Event {
+    type Err = WinEvtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Event::from_xml(s)
+    }
+}
+
+fn text(node: Node) -> Option<String> {
+    node.text().map(str::to_string)
+}
+
+fn number<T: FromStr>(s: Option<&str>) -> Option<T> {
+    s.and_then(|s| s.trim().parse().ok())
+}
+
+fn hex(s: Option<&str>) -> Option<u64> {
+    let s = s?.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(h) => u64::from_str_radix(h, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_rendering_info(info: Node) -> RenderingInfo {
+    let mut ri = RenderingInfo {
+        culture: info.attribute("Culture").map(str::to_string),
+        ..RenderingInfo::default()
+    };
+
+    for node in info.children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "Message" => ri.message = text(node),
+            "Level" => ri.level = text(node),
+            "Task" => ri.task = text(node),
+            "Opcode" => ri.opcode = text(node),
+            "Channel" => ri.channel = text(node),
+            "Provider" => ri.provider = text(node),
+            "Keywords" => {
+                ri.keywords = node
+                    .children()
+                    .filter(|n| n.has_tag_name("Keyword"))
+                    .filter_map(text)
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+
+    ri
+}
+
+fn parse_system(system: Node) -> System {
+    let mut sys = System::default();
+
+    for node in system.children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "Provider" => {
+                sys.provider = Provider {
+                    name: node.attribute("Name").map(str::to_string),
+                    guid: node.attribute("Guid").map(str::to_string),
+                    event_source_name: node.attribute("EventSourceName").map(str::to_string),
+                }
+            }
+            "EventID" => {
+                sys.event_id = number(node.text());
+                sys.qualifiers = number(node.attribute("Qualifiers"));
+            }
+            "Version" => sys.version = number(node.text()),
+            "Level" => sys.level = number(node.text()),
+            "Task" => sys.task = number(node.text()),
+            "Opcode" => sys.opcode = number(node.text()),
+            "Keywords" => sys.keywords = hex(node.text()),
+            "TimeCreated" => sys.time_created = node.attribute("SystemTime").map(str::to_string),
+            "EventRecordID" => sys.event_record_id = number(node.text()),
+            "Correlation" => {
+                sys.correlation = Correlation {
+                    activity_id: node.attribute("ActivityID").map(str::to_string),
+                    related_activity_id: node.attribute("RelatedActivityID").map(str::to_string),
+                }
+            }
+            "Execution" => {
+                sys.execution = Execution {
+                    process_id: number(node.attribute("ProcessID")),
+                    thread_id: number(node.attribute("ThreadID")),
+                }
+            }
+            "Channel" => sys.channel = text(node),
+            "Computer" => sys.computer = text(node),
+            "Security" => sys.user_id = node.attribute("UserID").map(str::to_string),
+            _ => {}
+        }
+    }
+
+    sys
+}
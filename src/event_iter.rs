@@ -7,6 +7,7 @@ use winapi::um::winbase::INFINITE;
 use winapi::um::winevt::{self, EvtClose, EvtNext, EvtQuery, EVT_HANDLE};
 
 use crate::errors::{WinError, WinEvtError};
+use crate::source::EventSource;
 use crate::utils;
 use crate::win_event::WinEvent;
 
@@ -60,6 +61,10 @@ impl Iterator for WinEventsIter {
     }
 }
 
+impl EventSource for WinEventsIter {
+    type Event = WinEvent;
+}
+
 impl Drop for WinEventsIter {
     fn drop(&mut self) {
         crate::utils::check_okay(unsafe { EvtClose(self.handle) })
@@ -71,18 +76,13 @@ impl WinEventsIter {
     pub fn get_logs_for(name: &str, query: Option<&str>) -> Result<WinEventsIter, WinEvtError> {
         let path = widestring::U16CString::from_str(name).expect("Invalid channel");
 
-        let query = match query {
-            None => ptr::null(),
-            Some(q) => widestring::U16CString::from_str(q)
-                .expect("Invalid query")
-                .as_ptr(),
-        };
+        let query = query.map(|q| widestring::U16CString::from_str(q).expect("Invalid query"));
 
         let handle = utils::not_null(unsafe {
             EvtQuery(
                 ptr::null_mut(),
                 path.as_ptr(),
-                query,
+                query.as_ref().map_or(ptr::null(), |q| q.as_ptr()),
                 winevt::EvtQueryChannelPath | winevt::EvtQueryForwardDirection,
             )
         })?;
@@ -0,0 +1,723 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::vec;
+
+use crate::binxml::{BinXmlDecoder, Node};
+use crate::errors::{WinEvtError, ERROR_EVT_CHANNEL_NOT_FOUND, ERROR_INVALID_DATA};
+use crate::source::{ChannelSource, EventRenderer, EventSource};
+use crate::xpath::{FilteredEvents, FilteredRenderer};
+
+pub const FILE_SIGNATURE: &[u8; 8] = b"ElfFile\0";
+pub const CHUNK_SIGNATURE: &[u8; 8] = b"ElfChnk\0";
+pub const RECORD_SIGNATURE: &[u8; 4] = b"**\0\0";
+
+/// The header block files are normally written with; the header itself says
+/// how big its block is
+pub const FILE_HEADER_SIZE: usize = 4096;
+/// The header fields proper, the rest of the block is padding
+pub const FILE_HEADER_MIN_SIZE: usize = 128;
+pub const CHUNK_SIZE: usize = 64 * 1024;
+pub const CHUNK_HEADER_SIZE: usize = 512;
+
+/// Records are at least their 24 byte header plus the trailing size copy
+pub(crate) const RECORD_MIN_SIZE: usize = 28;
+
+#[inline]
+pub(crate) fn read_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+#[inline]
+pub(crate) fn read_u32(buf: &[u8], off: usize) -> u32 {
+    let mut b = [0; 4];
+    b.copy_from_slice(&buf[off..off + 4]);
+    u32::from_le_bytes(b)
+}
+
+#[inline]
+pub(crate) fn read_u64(buf: &[u8], off: usize) -> u64 {
+    let mut b = [0; 8];
+    b.copy_from_slice(&buf[off..off + 8]);
+    u64::from_le_bytes(b)
+}
+
+pub(crate) fn invalid<S: Into<String>>(msg: S) -> WinEvtError {
+    WinEvtError::new(ERROR_INVALID_DATA, msg)
+}
+
+/// The `ElfFile` header found at the very start of an evtx file
+#[derive(Debug, Clone)]
+pub struct FileHeader {
+    pub first_chunk: u64,
+    pub last_chunk: u64,
+    pub next_record_id: u64,
+    pub header_size: u32,
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub header_block_size: u16,
+    pub chunk_count: u16,
+    pub flags: u32,
+    pub checksum: u32,
+}
+
+impl FileHeader {
+    pub fn parse(buf: &[u8]) -> Result<Self, WinEvtError> {
+        if buf.len() < FILE_HEADER_MIN_SIZE || &buf[..8] != FILE_SIGNATURE {
+            return Err(invalid("not an evtx file"));
+        }
+
+        Ok(FileHeader {
+            first_chunk: read_u64(buf, 8),
+            last_chunk: read_u64(buf, 16),
+            next_record_id: read_u64(buf, 24),
+            header_size: read_u32(buf, 32),
+            minor_version: read_u16(buf, 36),
+            major_version: read_u16(buf, 38),
+            header_block_size: read_u16(buf, 40),
+            chunk_count: read_u16(buf, 42),
+            flags: read_u32(buf, 120),
+            checksum: read_u32(buf, 124),
+        })
+    }
+
+    /// The file wasn't closed cleanly so the header may be out of date
+    pub fn is_dirty(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.flags & 0x2 != 0
+    }
+
+    /// CRC32 of the header bytes the stored checksum covers
+    pub fn compute_checksum(buf: &[u8]) -> u32 {
+        crc32fast::hash(&buf[..120])
+    }
+}
+
+/// The `ElfChnk` header at the start of every 64KiB chunk
+#[derive(Debug, Clone)]
+pub struct ChunkHeader {
+    pub first_record_number: u64,
+    pub last_record_number: u64,
+    pub first_record_id: u64,
+    pub last_record_id: u64,
+    pub header_size: u32,
+    pub last_record_offset: u32,
+    pub free_space_offset: u32,
+    pub records_checksum: u32,
+    pub flags: u32,
+    pub header_checksum: u32,
+}
+
+impl ChunkHeader {
+    pub fn parse(buf: &[u8]) -> Result<Self, WinEvtError> {
+        if buf.len() < CHUNK_HEADER_SIZE || &buf[..8] != CHUNK_SIGNATURE {
+            return Err(invalid("missing chunk signature"));
+        }
+
+        Ok(ChunkHeader {
+            first_record_number: read_u64(buf, 8),
+            last_record_number: read_u64(buf, 16),
+            first_record_id: read_u64(buf, 24),
+            last_record_id: read_u64(buf, 32),
+            header_size: read_u32(buf, 40),
+            last_record_offset: read_u32(buf, 44),
+            free_space_offset: read_u32(buf, 48),
+            records_checksum: read_u32(buf, 52),
+            flags: read_u32(buf, 120),
+            header_checksum: read_u32(buf, 124),
+        })
+    }
+}
+
+/// A single chunk along with its raw bytes, which records keep hold of as
+/// their BinXML refers back into the chunk's string and template tables
+pub struct Chunk {
+    pub header: ChunkHeader,
+    data: Vec<u8>,
+}
+
+impl Chunk {
+    pub fn parse(data: Vec<u8>) -> Result<Self, WinEvtError> {
+        if data.len() != CHUNK_SIZE {
+            return Err(invalid(format!("chunk is {} bytes", data.len())));
+        }
+
+        Ok(Chunk {
+            header: ChunkHeader::parse(&data)?,
+            data,
+        })
+    }
+
+    /// Wraps bytes recovered from somewhere other than a well formed chunk.
+    ///
+    /// There may be no usable header so every byte past the header is treated
+    /// as possibly holding records.
+    pub(crate) fn recovered(data: Vec<u8>) -> Self {
+        assert_eq!(data.len(), CHUNK_SIZE);
+
+        Chunk {
+            header: ChunkHeader {
+                first_record_number: 0,
+                last_record_number: 0,
+                first_record_id: 0,
+                last_record_id: 0,
+                header_size: 0,
+                last_record_offset: 0,
+                free_space_offset: CHUNK_SIZE as u32,
+                records_checksum: 0,
+                flags: 0,
+                header_checksum: 0,
+            },
+            data,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// CRC32 of the chunk header, which skips over the checksum fields
+    pub fn compute_header_checksum(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&self.data[..120]);
+        hasher.update(&self.data[128..CHUNK_HEADER_SIZE]);
+        hasher.finalize()
+    }
+
+    /// CRC32 of the records, from the end of the header up to the free space
+    pub fn compute_records_checksum(&self) -> u32 {
+        crc32fast::hash(&self.data[CHUNK_HEADER_SIZE..self.records_end()])
+    }
+
+    /// Where the records stop, clamped to the chunk in case the header lies
+    pub(crate) fn records_end(&self) -> usize {
+        (self.header.free_space_offset as usize).clamp(CHUNK_HEADER_SIZE, CHUNK_SIZE)
+    }
+
+    /// Parses the record starting at `off`
+    pub(crate) fn record_at(self: &Arc<Self>, off: usize) -> Result<EvtxRecord, WinEvtError> {
+        let buf = &self.data[..self.records_end()];
+
+        if off + RECORD_MIN_SIZE > buf.len() || &buf[off..off + 4] != RECORD_SIGNATURE {
+            return Err(invalid(format!("no record at chunk offset {}", off)));
+        }
+
+        let size = read_u32(buf, off + 4) as usize;
+        if size < RECORD_MIN_SIZE || off + size > buf.len() {
+            return Err(invalid(format!(
+                "bad record size {} at offset {}",
+                size, off
+            )));
+        }
+        if read_u32(buf, off + size - 4) as usize != size {
+            return Err(invalid(format!("record size mismatch at offset {}", off)));
+        }
+
+        Ok(EvtxRecord {
+            record_id: read_u64(buf, off + 8),
+            written: read_u64(buf, off + 16),
+            chunk: Arc::clone(self),
+            offset: off,
+            size,
+            nodes: None,
+        })
+    }
+}
+
+/// What to do with a chunk whose checksums don't match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorruptChunks {
+    /// Don't compute checksums at all
+    #[default]
+    Ignore,
+    /// Leave out the records of corrupt chunks
+    Skip,
+    /// Keep the records but print a warning for each corrupt chunk
+    Warn,
+    /// Stop reading at the first corrupt chunk
+    Abort,
+}
+
+impl FromStr for CorruptChunks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ignore" => Ok(CorruptChunks::Ignore),
+            "skip" => Ok(CorruptChunks::Skip),
+            "warn" => Ok(CorruptChunks::Warn),
+            "abort" => Ok(CorruptChunks::Abort),
+            other => Err(format!(
+                "unknown corrupt chunk handling {}, expected ignore, skip, warn or abort",
+                other
+            )),
+        }
+    }
+}
+
+/// The checksum results for a single chunk
+#[derive(Debug, Clone)]
+pub struct ChunkReport {
+    /// Index of the chunk within the file
+    pub index: usize,
+    /// Byte offset of the chunk within the file
+    pub offset: u64,
+    pub first_record_id: u64,
+    pub last_record_id: u64,
+    pub header_ok: bool,
+    pub records_ok: bool,
+    /// Why the chunk couldn't be read at all, in which case neither checksum
+    /// could be checked
+    pub error: Option<String>,
+}
+
+impl ChunkReport {
+    fn new(index: usize, offset: u64, chunk: &Chunk) -> Self {
+        ChunkReport {
+            index,
+            offset,
+            first_record_id: chunk.header.first_record_id,
+            last_record_id: chunk.header.last_record_id,
+            header_ok: chunk.compute_header_checksum() == chunk.header.header_checksum,
+            records_ok: chunk.compute_records_checksum() == chunk.header.records_checksum,
+            error: None,
+        }
+    }
+
+    fn unreadable(index: usize, offset: u64, error: &WinEvtError) -> Self {
+        ChunkReport {
+            index,
+            offset,
+            first_record_id: 0,
+            last_record_id: 0,
+            header_ok: false,
+            records_ok: false,
+            error: Some(error.to_string()),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.header_ok && self.records_ok && self.error.is_none()
+    }
+}
+
+impl Display for ChunkReport {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        if let Some(error) = &self.error {
+            return write!(
+                f,
+                "chunk {} at offset {}: {}",
+                self.index, self.offset, error
+            );
+        }
+
+        write!(
+            f,
+            "chunk {} at offset {} (records {}-{}): header checksum {}, records checksum {}",
+            self.index,
+            self.offset,
+            self.first_record_id,
+            self.last_record_id,
+            if self.header_ok { "ok" } else { "BAD" },
+            if self.records_ok { "ok" } else { "BAD" },
+        )
+    }
+}
+
+/// Checksum results for a whole file, filled in as its chunks are read
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub file_header_ok: bool,
+    pub chunks: Vec<ChunkReport>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.file_header_ok && self.chunks.iter().all(ChunkReport::is_ok)
+    }
+
+    pub fn corrupt_chunks(&self) -> impl Iterator<Item = &ChunkReport> {
+        self.chunks.iter().filter(|c| !c.is_ok())
+    }
+}
+
+/// An event record read straight out of an evtx chunk
+#[derive(Clone)]
+pub struct EvtxRecord {
+    pub record_id: u64,
+    /// FILETIME the record was written
+    pub written: u64,
+    chunk: Arc<Chunk>,
+    offset: usize,
+    size: usize,
+    /// The BinXML, when it had to be decoded already to tell whether this is
+    /// a record at all
+    nodes: Option<Arc<Vec<Node>>>,
+}
+
+impl EvtxRecord {
+    pub fn chunk(&self) -> &Arc<Chunk> {
+        &self.chunk
+    }
+
+    /// Offset of the record from the start of its chunk
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The record's BinXML payload
+    pub fn data(&self) -> &[u8] {
+        &self.chunk.data[self.offset + 24..self.offset + self.size - 4]
+    }
+
+    pub(crate) fn with_nodes(mut self, nodes: Vec<Node>) -> Self {
+        self.nodes = Some(Arc::new(nodes));
+        self
+    }
+
+    pub(crate) fn nodes(&self) -> Option<&[Node]> {
+        self.nodes.as_deref().map(Vec::as_slice)
+    }
+}
+
+/// Reads the records of an evtx file in order, a chunk at a time.
+///
+/// A chunk that can't be parsed is reported once as an error and skipped,
+/// or when validating is handled like one whose checksums don't match; only
+/// failing to read the underlying stream ends the iteration early.
+/// Reading stops after the number of chunks the file header gives, unless the
+/// file wasn't closed cleanly and that count may be stale.
+/// Checksums are only checked when asked for with [`with_validation`], in
+/// which case the results build up in [`report`] as chunks are read.
+///
+/// [`with_validation`]: EvtxRecords::with_validation
+/// [`report`]: EvtxRecords::report
+pub struct EvtxRecords<R: Read> {
+    reader: R,
+    pub header: FileHeader,
+    chunk: Option<Arc<Chunk>>,
+    chunks_read: usize,
+    chunk_limit: Option<usize>,
+    offset: usize,
+    validation: CorruptChunks,
+    report: ValidationReport,
+    done: bool,
+}
+
+impl EvtxRecords<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        EvtxRecords::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> EvtxRecords<R> {
+    pub fn new(mut reader: R) -> Result<Self, WinEvtError> {
+        let mut buf = vec![0; FILE_HEADER_MIN_SIZE];
+        reader.read_exact(&mut buf)?;
+
+        let header = FileHeader::parse(&buf)?;
+        let block = header.header_block_size as usize;
+        if block < FILE_HEADER_MIN_SIZE {
+            return Err(invalid(format!("file header block of {} bytes", block)));
+        }
+        io::copy(
+            &mut (&mut reader).take((block - FILE_HEADER_MIN_SIZE) as u64),
+            &mut io::sink(),
+        )?;
+
+        let report = ValidationReport {
+            file_header_ok: FileHeader::compute_checksum(&buf) == header.checksum,
+            chunks: Vec::new(),
+        };
+
+        Ok(EvtxRecords {
+            chunk_limit: Some(header.chunk_count as usize).filter(|_| !header.is_dirty()),
+            header,
+            reader,
+            chunk: None,
+            chunks_read: 0,
+            offset: 0,
+            validation: CorruptChunks::Ignore,
+            report,
+            done: false,
+        })
+    }
+
+    /// Checks the checksums of the file header and every chunk as it is read.
+    ///
+    /// A bad file header is only fatal when aborting on corruption; otherwise
+    /// it is just noted in the report.
+    pub fn with_validation(mut self, validation: CorruptChunks) -> Result<Self, WinEvtError> {
+        if !self.report.file_header_ok {
+            match validation {
+                CorruptChunks::Abort => return Err(invalid("file header checksum mismatch")),
+                CorruptChunks::Warn => eprintln!("Warning: file header checksum mismatch"),
+                _ => {}
+            }
+        }
+
+        self.validation = validation;
+        Ok(self)
+    }
+
+    pub fn report(&self) -> &ValidationReport {
+        &self.report
+    }
+
+    /// Reads every remaining chunk without decoding any records, returning the
+    /// finished report
+    pub fn validate(mut self) -> Result<ValidationReport, WinEvtError> {
+        self.chunk = None;
+
+        while let Some(chunk) = self.next_chunk() {
+            match chunk {
+                Ok(chunk) => {
+                    self.check_chunk(&chunk);
+                }
+                Err(ref e) if e.code() == ERROR_INVALID_DATA => self.unreadable_chunk(e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(self.report)
+    }
+
+    /// Index and file offset of the chunk last read
+    fn chunk_position(&self) -> (usize, u64) {
+        let index = self.chunks_read - 1;
+        let offset = self.header.header_block_size as usize + index * CHUNK_SIZE;
+        (index, offset as u64)
+    }
+
+    /// Adds the chunk to the report, returning whether it passed
+    fn check_chunk(&mut self, chunk: &Chunk) -> bool {
+        let (index, offset) = self.chunk_position();
+        let report = ChunkReport::new(index, offset, chunk);
+        let ok = report.is_ok();
+
+        self.report.chunks.push(report);
+        ok
+    }
+
+    /// Adds a chunk that couldn't be parsed to the report
+    fn unreadable_chunk(&mut self, error: &WinEvtError) {
+        let (index, offset) = self.chunk_position();
+        self.report
+            .chunks
+            .push(ChunkReport::unreadable(index, offset, error));
+    }
+
+    fn start_chunk(&mut self, chunk: Chunk) {
+        self.chunk = Some(Arc::new(chunk));
+        self.offset = CHUNK_HEADER_SIZE;
+    }
+
+    /// Reads the next full chunk, returning `None` at the end of the stream
+    fn next_chunk(&mut self) -> Option<Result<Chunk, WinEvtError>> {
+        let mut buf = vec![0; CHUNK_SIZE];
+
+        loop {
+            if self.chunk_limit.is_some_and(|n| self.chunks_read >= n) {
+                return None;
+            }
+            let mut filled = 0;
+            self.chunks_read += 1;
+
+            while filled < CHUNK_SIZE {
+                match self.reader.read(&mut buf[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Some(Err(e.into())),
+                }
+            }
+
+            return match filled {
+                0 => None,
+                // Preallocated chunks past the end of the log are left zeroed
+                CHUNK_SIZE if buf.iter().all(|&b| b == 0) => continue,
+                CHUNK_SIZE => Some(Chunk::parse(buf)),
+                n => Some(Err(invalid(format!("truncated chunk of {} bytes", n)))),
+            };
+        }
+    }
+}
+
+impl<R: Read> Iterator for EvtxRecords<R> {
+    type Item = Result<EvtxRecord, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some(chunk) = &self.chunk {
+                if self.offset + RECORD_MIN_SIZE <= chunk.records_end() {
+                    let rec = chunk.record_at(self.offset);
+                    match &rec {
+                        Ok(r) => self.offset += r.size,
+                        Err(_) => self.chunk = None,
+                    }
+                    return Some(rec);
+                }
+                self.chunk = None;
+            }
+
+            let corrupt = match self.next_chunk() {
+                None => {
+                    self.done = true;
+                    return None;
+                }
+                Some(Err(e)) if e.code() != ERROR_INVALID_DATA => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                Some(Err(e)) if self.validation == CorruptChunks::Ignore => return Some(Err(e)),
+                Some(Err(e)) => {
+                    self.unreadable_chunk(&e);
+                    None
+                }
+                Some(Ok(chunk))
+                    if self.validation == CorruptChunks::Ignore || self.check_chunk(&chunk) =>
+                {
+                    self.start_chunk(chunk);
+                    continue;
+                }
+                Some(Ok(chunk)) => Some(chunk),
+            };
+
+            // The chunk failed its checksums, or couldn't be parsed at all
+            let report = self.report.chunks.last().unwrap();
+            match self.validation {
+                CorruptChunks::Warn => {
+                    eprintln!("Warning: {}", report);
+                    if let Some(chunk) = corrupt {
+                        self.start_chunk(chunk);
+                    }
+                }
+                CorruptChunks::Abort => {
+                    let e = invalid(format!("corrupt {}", report));
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+impl<R: Read> EventSource for EvtxRecords<R> {
+    type Event = EvtxRecord;
+}
+
+/// A [`ChannelSource`] over one or more evtx files, one channel per file.
+///
+/// Channel names come from the file names the same way the event log names
+/// them under `winevt\Logs`, with `%4` standing in for `/`.
+#[derive(Default)]
+pub struct EvtxSource {
+    files: BTreeMap<String, PathBuf>,
+    validation: CorruptChunks,
+}
+
+impl EvtxSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        let path = path.as_ref();
+        let mut source = EvtxSource::default();
+
+        match channel_name(path) {
+            Some(name) => source.files.insert(name, path.to_path_buf()),
+            None => return Err(invalid(format!("bad evtx file name {}", path.display()))),
+        };
+
+        Ok(source)
+    }
+
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, WinEvtError> {
+        let mut source = EvtxSource::default();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(true, |e| !e.eq_ignore_ascii_case("evtx"))
+            {
+                continue;
+            }
+
+            if let Some(name) = channel_name(&path) {
+                source.files.insert(name, path);
+            }
+        }
+
+        Ok(source)
+    }
+
+    /// Validates the checksums of every file opened
+    pub fn with_validation(mut self, validation: CorruptChunks) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+fn channel_name(path: &Path) -> Option<String> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.replace("%4", "/"))
+}
+
+impl ChannelSource for EvtxSource {
+    type Channels = vec::IntoIter<Result<String, WinEvtError>>;
+    type Events = FilteredEvents<EvtxRecords<BufReader<File>>, EvtxRenderer>;
+    type Renderer = FilteredRenderer<EvtxRenderer>;
+
+    fn channels(&self) -> Result<Self::Channels, WinEvtError> {
+        Ok(self
+            .files
+            .keys()
+            .cloned()
+            .map(Ok)
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    fn events_for(&self, name: &str, query: Option<&str>) -> Result<Self::Events, WinEvtError> {
+        match self.files.get(name) {
+            Some(path) => FilteredEvents::new(
+                EvtxRecords::open(path)?.with_validation(self.validation)?,
+                query,
+                EvtxRenderer::default(),
+            ),
+            None => Err(WinEvtError::new(
+                ERROR_EVT_CHANNEL_NOT_FOUND,
+                "channel not found",
+            )),
+        }
+    }
+
+    fn renderer(&self) -> Self::Renderer {
+        FilteredRenderer(EvtxRenderer::default())
+    }
+}
+
+/// Renders records by decoding their BinXML, reusing templates across records
+#[derive(Default)]
+pub struct EvtxRenderer {
+    decoder: BinXmlDecoder,
+}
+
+impl EventRenderer<EvtxRecord> for EvtxRenderer {
+    fn render(&mut self, rec: EvtxRecord) -> Result<String, WinEvtError> {
+        self.decoder.render(&rec)
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of 100ns ticks in a day
+const TICKS_PER_DAY: u64 = 864_000_000_000;
+const TICKS_PER_SECOND: u64 = 10_000_000;
+
+/// Days between the FILETIME epoch (1601-01-01) and the unix epoch
+const DAYS_TO_UNIX_EPOCH: i64 = 134_774;
+
+/// The last year a `SYSTEMTIME` can hold
+const MAX_YEAR: i64 = 30_827;
+
+/// Formats a FILETIME the way the event log renders `SystemTime` attributes,
+/// e.g. `2019-05-14T18:16:09.2851463Z`
+pub fn to_system_time(ft: u64) -> String {
+    let days = (ft / TICKS_PER_DAY) as i64 - DAYS_TO_UNIX_EPOCH;
+    let ticks = ft % TICKS_PER_DAY;
+
+    let (year, month, day) = civil_from_days(days);
+    let secs = ticks / TICKS_PER_SECOND;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:07}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        ticks % TICKS_PER_SECOND
+    )
+}
+
+/// Converts a [`SystemTime`] to a FILETIME, clamping anything before 1601
+pub fn from_system_time(t: SystemTime) -> u64 {
+    let unix_epoch = DAYS_TO_UNIX_EPOCH as u64 * TICKS_PER_DAY;
+
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => unix_epoch + d.as_nanos() as u64 / 100,
+        Err(e) => unix_epoch.saturating_sub(e.duration().as_nanos() as u64 / 100),
+    }
+}
+
+/// Parses a `SystemTime` attribute back into a FILETIME; any fraction of a
+/// second is optional, as is the trailing `Z`
+pub fn parse_system_time(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = s.split_once('T')?;
+
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, fraction),
+        None => (time, ""),
+    };
+    let mut time = time.splitn(3, ':').map(|p| p.parse::<u64>().ok());
+    let (hour, min, sec) = (time.next()??, time.next()??, time.next()??);
+
+    if !(1601..=MAX_YEAR).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || min > 59
+        || sec > 60
+    {
+        return None;
+    }
+    if fraction.len() > 7 && !fraction[7..].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut ticks = 0;
+    for (i, b) in fraction.bytes().take(7).enumerate() {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        ticks += u64::from(b - b'0') * 10u64.pow(6 - i as u32);
+    }
+
+    let days = days_from_civil(year, month as u32, day as u32) + DAYS_TO_UNIX_EPOCH;
+    if days < 0 {
+        return None;
+    }
+
+    (days as u64)
+        .checked_mul(TICKS_PER_DAY)?
+        .checked_add((hour * 3600 + min * 60 + sec) * TICKS_PER_SECOND + ticks)
+}
+
+/// Converts a (year, month, day) triple into days since the unix epoch
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+/// Converts days since the unix epoch into a (year, month, day) triple
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
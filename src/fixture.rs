@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::vec;
+
+use crate::codec::Codec;
+use crate::errors::{WinEvtError, ERROR_EVT_CHANNEL_NOT_FOUND};
+use crate::source::{ChannelSource, EventRenderer, EventSource};
+use crate::xpath::{FilteredEvents, FilteredRenderer};
+
+const EVENT_OPEN: &str = "<Event";
+const EVENT_CLOSE: &str = "</Event>";
+
+/// An in-memory [`ChannelSource`] whose events are already rendered XML.
+///
+/// Fixtures are usually loaded from a directory with one `.xml` file per
+/// channel. As with the files under `winevt\Logs`, a `/` in the channel name is
+/// written as `%4` in the file name, so `Microsoft-Windows-Sysmon%4Operational.xml`
+/// holds the `Microsoft-Windows-Sysmon/Operational` channel. Each file may
+/// contain any number of `<Event>` elements, either one per line as the dumper
+/// writes them or wrapped in an `<Events>` element as `wevtutil` exports them,
+/// and may be compressed with any [`Codec`] as long as it keeps its extension,
+/// e.g. `Security.xml.zst`.
+///
+/// An XML dump written by the dumper can be loaded back with [`from_dump`].
+///
+/// [`from_dump`]: FixtureSource::from_dump
+#[derive(Default)]
+pub struct FixtureSource {
+    channels: BTreeMap<String, Vec<String>>,
+}
+
+impl FixtureSource {
+    pub fn new() -> Self {
+        FixtureSource {
+            channels: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, WinEvtError> {
+        let mut source = FixtureSource::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = match path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(channel_name)
+            {
+                Some(name) => name,
+                None => continue,
+            };
+
+            source.add_xml(name, &read_to_string(&path)?);
+        }
+
+        Ok(source)
+    }
+
+    /// Loads an XML dump of any number of channels, putting each event in
+    /// the channel named by its `<Channel>` element, or failing that one
+    /// named after the file
+    pub fn from_dump<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        let path = path.as_ref();
+        let fallback = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        let fallback = fallback.split('.').next().unwrap_or_default();
+
+        let mut source = FixtureSource::new();
+        for event in split_events(&read_to_string(path)?) {
+            source.add_event(event_channel(event).unwrap_or(fallback), event);
+        }
+
+        Ok(source)
+    }
+
+    /// Registers a channel with no events
+    pub fn add_channel<S: Into<String>>(&mut self, name: S) {
+        self.channels.entry(name.into()).or_default();
+    }
+
+    pub fn add_event<S: Into<String>, E: Into<String>>(&mut self, name: S, xml: E) {
+        self.channels
+            .entry(name.into())
+            .or_default()
+            .push(xml.into());
+    }
+
+    /// Adds every `<Event>` element found in `xml` to the channel
+    pub fn add_xml<S: Into<String>>(&mut self, name: S, xml: &str) {
+        let events = self.channels.entry(name.into()).or_default();
+        events.extend(split_events(xml).map(str::to_string));
+    }
+}
+
+/// The channel a fixture file holds, if it's a fixture file at all
+fn channel_name(file_name: &str) -> Option<String> {
+    let name = match file_name.rsplit_once('.') {
+        Some((name, ext)) if ext.parse::<Codec>().is_ok_and(|c| c != Codec::None) => name,
+        _ => file_name,
+    };
+    name.strip_suffix(".xml").map(|n| n.replace("%4", "/"))
+}
+
+fn read_to_string(path: &Path) -> Result<String, WinEvtError> {
+    let mut xml = String::new();
+    Codec::open(path)?.read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+fn event_channel(xml: &str) -> Option<&str> {
+    let start = xml.find("<Channel>")? + "<Channel>".len();
+    let len = xml[start..].find('<')?;
+    Some(xml[start..start + len].trim())
+}
+
+fn split_events(xml: &str) -> impl Iterator<Item = &str> {
+    let mut rest = xml;
+
+    std::iter::from_fn(move || loop {
+        let start = rest.find(EVENT_OPEN)?;
+        let after = &rest[start + EVENT_OPEN.len()..];
+
+        // Skip over elements that merely start with `Event`, such as `<EventData>`
+        match after.chars().next() {
+            Some(c) if c == '>' || c == '/' || c.is_whitespace() => {}
+            _ => {
+                rest = after;
+                continue;
+            }
+        }
+
+        let end = match start_tag_len(&rest[start..]) {
+            // A self-closing `<Event/>` is the whole event
+            Some(len) if rest[start..start + len].ends_with("/>") => Some(start + len),
+            Some(len) => rest[start + len..]
+                .find(EVENT_CLOSE)
+                .map(|end| start + len + end + EVENT_CLOSE.len()),
+            None => None,
+        };
+        let end = match end {
+            Some(end) => end,
+            None => {
+                rest = "";
+                return None;
+            }
+        };
+
+        let event = &rest[start..end];
+        rest = &rest[end..];
+        return Some(event);
+    })
+}
+
+/// The length of the start tag `xml` begins with, up to and including its
+/// `>`, skipping over any `>` in quoted attribute values
+fn start_tag_len(xml: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in xml.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl ChannelSource for FixtureSource {
+    type Channels = vec::IntoIter<Result<String, WinEvtError>>;
+    type Events = FilteredEvents<FixtureEvents, FixtureRenderer>;
+    type Renderer = FilteredRenderer<FixtureRenderer>;
+
+    fn channels(&self) -> Result<Self::Channels, WinEvtError> {
+        Ok(self
+            .channels
+            .keys()
+            .cloned()
+            .map(Ok)
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    fn events_for(&self, name: &str, query: Option<&str>) -> Result<Self::Events, WinEvtError> {
+        match self.channels.get(name) {
+            Some(events) => FilteredEvents::new(
+                FixtureEvents {
+                    events: events.clone().into_iter(),
+                },
+                query,
+                FixtureRenderer,
+            ),
+            None => Err(WinEvtError::new(
+                ERROR_EVT_CHANNEL_NOT_FOUND,
+                "channel not found",
+            )),
+        }
+    }
+
+    fn renderer(&self) -> Self::Renderer {
+        FilteredRenderer(FixtureRenderer)
+    }
+}
+
+pub struct FixtureEvents {
+    events: vec::IntoIter<String>,
+}
+
+impl Iterator for FixtureEvents {
+    type Item = Result<String, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.next().map(Ok)
+    }
+}
+
+impl EventSource for FixtureEvents {
+    type Event = String;
+}
+
+/// Fixture events are stored as XML already so rendering just hands them back
+#[derive(Default)]
+pub struct FixtureRenderer;
+
+impl EventRenderer<String> for FixtureRenderer {
+    fn render(&mut self, event: String) -> Result<String, WinEvtError> {
+        Ok(event)
+    }
+}
@@ -1,10 +1,44 @@
+pub mod binxml;
+pub mod bookmark;
+pub mod carver;
+#[cfg(windows)]
 pub mod channel_iter;
+pub mod codec;
+pub mod dumper;
 pub mod errors;
+pub mod event;
+#[cfg(windows)]
 pub mod event_iter;
+pub mod evtx;
+pub mod filetime;
+pub mod fixture;
+pub mod manifest;
+pub mod message;
+pub mod metadata_cache;
+pub mod mock_publisher;
+pub mod names;
+pub mod output;
+pub mod pe;
 pub mod pub_metadata;
+#[cfg(windows)]
 pub mod pub_metadata_fetcher;
+#[cfg(windows)]
 pub mod pub_metadata_fields;
+pub mod query;
+pub mod query_list;
+#[cfg(windows)]
 pub mod renderer;
+pub mod rotate;
+pub mod source;
+pub mod stream;
+#[cfg(windows)]
+pub mod subscription;
+#[cfg(windows)]
 pub mod utils;
+#[cfg(windows)]
 pub mod vwrapper;
-pub mod win_event;
\ No newline at end of file
+pub mod wevt;
+#[cfg(windows)]
+pub mod win_event;
+pub mod winmeta;
+pub mod xpath;
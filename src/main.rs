@@ -1,98 +1,809 @@
-use std::fs::File;
-use std::io::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use widestring::U16CStr;
+use clap::{
+    builder::RangedU64ValueParser, error::ErrorKind, Args, CommandFactory, Parser, Subcommand,
+};
 
-use win_events;
-use win_events::channel_iter::ChannelIter;
-use win_events::errors::WinEvtError;
-use win_events::event_iter::WinEventsIter;
+use win_events::bookmark::{self, Bookmarks};
+use win_events::carver::CarveSource;
+#[cfg(windows)]
+use win_events::channel_iter::LocalChannels;
+use win_events::codec::Codec;
+use win_events::dumper::ChannelDumper;
+use win_events::errors::{
+    WinEvtError, ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND, ERROR_INVALID_PARAMETER,
+};
+use win_events::evtx::{CorruptChunks, EvtxSource};
+use win_events::fixture::FixtureSource;
+use win_events::manifest::Manifest;
+use win_events::metadata_cache::{MetadataCache, ProviderKey, DEFAULT_LOCALE};
+use win_events::names::NamingWriter;
+use win_events::output::{EventWriter, Format};
+use win_events::pub_metadata::PubMetadata;
+#[cfg(windows)]
 use win_events::pub_metadata_fetcher::PubMetadataFetcher;
-use win_events::pub_metadata_fields as meta_fields;
-use win_events::renderer::Renderer;
-use win_events::vwrapper::WevWrapper;
-use winapi::um::winevt::{self, EVT_VARIANT_TYPE_ARRAY};
+use win_events::query_list::QueryList;
+use win_events::rotate::{RotatingWriter, Rotation};
+use win_events::source::{ChannelSource, EventRenderer, LiveSource, StartAt};
+#[cfg(windows)]
+use win_events::subscription::LocalSubscriber;
+use win_events::wevt;
 
-const DUMP: bool = false;
-const LEVELS: bool = true;
+/// How often `tail` saves its bookmarks
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(1);
 
-const TEST_PROVIDER: &str = "PowerShell";
+/// Dumps Windows event logs, either live from the local machine or offline
+/// from evtx files, rendered XML fixtures or raw images
+#[derive(Parser)]
+#[command(name = "wevent_dumper", version)]
+struct Cli {
+    #[command(flatten)]
+    source: SourceArgs,
 
-fn dump_chan(chan: &str, rend: &mut Renderer, fh: &mut GzEncoder<File>) -> Result<(), WinEvtError> {
-    println!("Processing {}", chan);
-    let iter = WinEventsIter::get_logs_for(chan, None)?;
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Where events are read from; the local event log when none are given
+#[derive(Args)]
+struct SourceArgs {
+    /// Read an evtx file, or a directory of them, instead of the local event log
+    #[arg(long, global = true, value_name = "PATH")]
+    evtx: Option<PathBuf>,
+
+    /// Read rendered XML fixtures from a directory, one file per channel, or
+    /// from a previous XML dump
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "evtx")]
+    fixtures: Option<PathBuf>,
 
-    for e in iter {
-        match e {
-            Err(err) => return Err(err),
-            Ok(we) => writeln!(fh, "{}", rend.render(we)?).expect("Couldn't write entry"),
+    /// Carve records out of a raw disk image or memory dump; may be given more than once
+    #[arg(long, global = true, value_name = "IMAGE", conflicts_with_all = ["evtx", "fixtures"])]
+    carve: Vec<PathBuf>,
+
+    /// What to do with evtx chunks whose checksums don't match: ignore, skip,
+    /// warn or abort; defaults to warn
+    #[arg(long, global = true)]
+    corrupt: Option<CorruptChunks>,
+}
+
+impl SourceArgs {
+    /// The first of the flags picking a source that was given, if any
+    fn given(&self) -> Option<&'static str> {
+        if self.evtx.is_some() {
+            Some("--evtx")
+        } else if self.fixtures.is_some() {
+            Some("--fixtures")
+        } else if !self.carve.is_empty() {
+            Some("--carve")
+        } else if self.corrupt.is_some() {
+            Some("--corrupt")
+        } else {
+            None
         }
     }
+}
 
-    Ok(())
+#[derive(Args)]
+struct OutputArgs {
+    /// File to write to; defaults to `events.<format>.<codec>`
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format: xml, ndjson or json
+    #[arg(short, long, default_value = "xml")]
+    format: Format,
+
+    /// Compression: gzip, zstd, xz or none
+    #[arg(short = 'z', long, default_value = "gzip")]
+    codec: Codec,
+
+    /// Compression level; 0-9 for gzip and xz, 1-22 for zstd. Defaults to 3,
+    /// or 6 for xz
+    #[arg(short = 'l', long)]
+    level: Option<u32>,
+
+    /// Start a new numbered file once this much has been written to it, after
+    /// compression, e.g. 500M or 2G
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Start a new numbered file after this long, e.g. 90s, 15m or 1h
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    max_age: Option<Duration>,
+
+    #[command(flatten)]
+    names: NameArgs,
+}
+
+#[derive(Args)]
+struct NameArgs {
+    /// Add the names of each event's level, task, opcode and keywords, from
+    /// its publisher's metadata in a cache file if one is given, and the
+    /// standard Windows names
+    #[arg(long, value_name = "CACHE", num_args = 0..=1)]
+    names: Option<Option<PathBuf>>,
+}
+
+impl NameArgs {
+    fn wrap<'a, W: EventWriter + 'a>(
+        &self,
+        out: W,
+    ) -> Result<Box<dyn EventWriter + 'a>, WinEvtError> {
+        let cache = match &self.names {
+            Some(Some(path)) => MetadataCache::load(path)?,
+            Some(None) => MetadataCache::new(),
+            None => return Ok(Box::new(out)),
+        };
+        Ok(Box::new(NamingWriter::new(out, cache)))
+    }
+}
+
+/// A byte count with an optional K, M or G suffix, in powers of 1024
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (num, shift) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 30),
+        _ => (s, 0),
+    };
+    num.trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("expected a size like 500M, not {}", s))
+}
+
+/// A number of seconds with an optional s, m, h or d suffix
+fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (num, unit) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 60 * 60),
+        Some((i, 'd')) => (&s[..i], 24 * 60 * 60),
+        _ => (s, 1),
+    };
+    num.trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .filter(|&n| n > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("expected an age like 15m, not {}", s))
+}
+
+/// A format that can be written as events arrive, which a JSON array can't
+fn parse_stream_format(s: &str) -> Result<Format, String> {
+    match s.parse()? {
+        Format::Json => Err("json can't be streamed, use ndjson".to_string()),
+        format => Ok(format),
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the available channels
+    Channels,
+
+    /// Dump the events of every channel, or just those given, to a file
+    Dump {
+        /// Channel to dump; may be given more than once
+        #[arg(short, long = "channel")]
+        channels: Vec<String>,
+
+        /// XPath query to filter each channel's events with
+        #[arg(short, long)]
+        query: Option<String>,
+
+        /// `<QueryList>` file selecting events across channels, dumped as one stream
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["channels", "query"])]
+        query_list: Option<PathBuf>,
+
+        /// Bookmark file; only events after those last dumped are dumped, and
+        /// it's updated as each channel's output has been written
+        #[arg(long, value_name = "FILE")]
+        state: Option<PathBuf>,
+
+        /// Channels to dump at once; defaults to the number of CPUs
+        #[arg(short, long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        jobs: Option<usize>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Print the events of a single channel, optionally filtered by a query
+    Query {
+        channel: String,
+
+        /// XPath query to filter the channel's events with
+        query: Option<String>,
+
+        /// Output format: xml, ndjson or json
+        #[arg(short, long, default_value = "xml")]
+        format: Format,
+
+        #[command(flatten)]
+        names: NameArgs,
+    },
+
+    /// Follow a channel of the local event log, printing events as they're published
+    Tail {
+        channel: String,
+
+        /// XPath query to filter the channel's events with
+        query: Option<String>,
+
+        /// Where to start: now, oldest or the record ID to start after
+        #[arg(long, default_value = "now")]
+        start: StartAt,
+
+        /// Bookmark file to resume from, kept up to date as events are printed
+        #[arg(long, value_name = "FILE")]
+        state: Option<PathBuf>,
+
+        /// Output format: xml or ndjson
+        #[arg(short, long, default_value = "xml", value_parser = parse_stream_format)]
+        format: Format,
+
+        #[command(flatten)]
+        names: NameArgs,
+    },
+
+    /// Show the metadata of an event publisher
+    Publisher {
+        name: String,
+
+        /// Metadata cache file; on Windows the publisher's metadata is added
+        /// to it, elsewhere it's read from it
+        #[arg(long, value_name = "FILE")]
+        cache: Option<PathBuf>,
+
+        /// Read the metadata from the WEVT_TEMPLATE resource of the
+        /// publisher's binary instead, adding it to any --cache; NAME picks
+        /// the publisher by GUID when the binary defines more than one
+        #[arg(long, value_name = "FILE")]
+        binary: Option<PathBuf>,
+
+        /// Read the metadata from an instrumentation manifest (.man) instead,
+        /// adding it to any --cache; NAME is the publisher's name or GUID
+        #[arg(long, value_name = "FILE", conflicts_with = "binary")]
+        manifest: Option<PathBuf>,
+    },
+}
+
+fn open_output(args: &OutputArgs) -> Result<Box<dyn EventWriter>, WinEvtError> {
+    let level = args.level.unwrap_or(args.codec.default_level());
+    if !args.codec.levels().contains(&level) {
+        Cli::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "{} levels go from {} to {}",
+                    args.codec,
+                    args.codec.levels().start(),
+                    args.codec.levels().end()
+                ),
+            )
+            .exit()
+    }
+
+    let path = match &args.output {
+        Some(path) => path.clone(),
+        None => match args.codec.extension() {
+            Some(ext) => PathBuf::from(format!("events.{}.{}", args.format.extension(), ext)),
+            None => PathBuf::from(format!("events.{}", args.format.extension())),
+        },
+    };
+    let rotation = Rotation {
+        max_size: args.max_size,
+        max_age: args.max_age,
+    };
+    args.names.wrap(RotatingWriter::new(
+        path,
+        args.format,
+        args.codec,
+        level,
+        rotation,
+    ))
+}
+
+fn load_state(state: Option<&Path>) -> Result<Option<Bookmarks>, WinEvtError> {
+    state.map(Bookmarks::load).transpose()
 }
 
-fn print_channels() -> Result<(), WinEvtError> {
-    let fh = File::create("events.xml.gz").expect("Couldn't open out file");
-    //    let mut fh = BufWriter::with_capacity(1024 * 16, fh);
-    let mut fh = GzEncoder::new(fh, Compression::new(3));
+fn save_state(state: Option<&Path>, bookmarks: Option<&Bookmarks>) -> Result<(), WinEvtError> {
+    match (state, bookmarks) {
+        (Some(path), Some(bookmarks)) => bookmarks.save(path),
+        _ => Ok(()),
+    }
+}
+
+/// Writes an event, skipping it with a warning if the writer can't handle it
+fn write_or_skip(out: &mut dyn EventWriter, chan: &str, xml: &str) -> Result<(), WinEvtError> {
+    match out.write_event(xml) {
+        Err(e @ WinEvtError::Parse { .. }) => {
+            eprintln!("Skipping an event from {}: {}", chan, e);
+            Ok(())
+        }
+        result => result,
+    }
+}
 
-    let mut rend = Renderer::new();
+fn print_channels<S: ChannelSource + Sync>(
+    source: &S,
+    channels: &[String],
+    query: Option<&str>,
+    jobs: Option<usize>,
+    state: Option<&Path>,
+    args: &OutputArgs,
+) -> Result<(), WinEvtError> {
+    let mut bookmarks = load_state(state)?;
+    let mut out = open_output(args)?;
+
+    let names = if channels.is_empty() {
+        source.channels()?.collect::<Result<Vec<_>, _>>()?
+    } else {
+        channels.to_vec()
+    };
+
+    let mut dumper = ChannelDumper::new(source).query(query).state(state);
+    if let Some(jobs) = jobs {
+        dumper = dumper.jobs(jobs);
+    }
+    for report in dumper.dump(&names, &mut *out, bookmarks.as_mut())? {
+        if report.skipped > 0 {
+            eprintln!(
+                "Skipped {} events from {} that couldn't be written",
+                report.skipped, report.channel
+            )
+        }
+        if let Some(e) = report.error {
+            eprintln!("Error dumping {}: {}", report.channel, e)
+        }
+    }
+
+    out.finish()?;
+    save_state(state, bookmarks.as_ref())
+}
 
-    for c in ChannelIter::new().expect("Couldn't build channel iter") {
-        match c {
-            Err(err) => return Err(err),
-            Ok(n) => {
-                if let Err(e) = dump_chan(n.as_str(), &mut rend, &mut fh) {
-                    eprintln!("Error dumping {}: {}", n, e)
-                }
+fn print_query_list<S: ChannelSource>(
+    source: &S,
+    list: &QueryList,
+    state: Option<&Path>,
+    args: &OutputArgs,
+) -> Result<(), WinEvtError> {
+    let mut bookmarks = load_state(state)?;
+    let mut out = open_output(args)?;
+    let mut events = list.events(source)?;
+    let mut chan = None;
+
+    while let Some(e) = events.next() {
+        if events.channel() != chan.as_deref() {
+            if chan.is_some() && state.is_some() {
+                out.flush()?;
+                save_state(state, bookmarks.as_ref())?;
             }
+            chan = events.channel().map(str::to_string);
+            eprintln!("Processing {}", chan.as_deref().unwrap_or_default());
         }
+        let chan = chan.as_deref().unwrap_or_default();
+        let xml = match e {
+            Ok(xml) => xml,
+            Err(e) => {
+                eprintln!("Error dumping {}: {}", chan, e);
+                continue;
+            }
+        };
+
+        let id = bookmark::record_id(&xml);
+        if let Some(bookmarks) = bookmarks.as_mut() {
+            if id.is_some() && id <= bookmarks.get(chan) {
+                continue;
+            }
+            if let Some(id) = id {
+                bookmarks.advance(chan, id);
+            }
+        }
+        write_or_skip(&mut *out, chan, &xml)?;
+    }
+
+    out.finish()?;
+    save_state(state, bookmarks.as_ref())
+}
+
+fn list_channels<S: ChannelSource>(source: &S) -> Result<(), WinEvtError> {
+    for c in source.channels()? {
+        println!("{}", c?);
     }
     Ok(())
 }
 
-fn print_levels() -> Result<(), WinEvtError> {
-    let mut varw = WevWrapper::new().unwrap();
+fn query_chan<S: ChannelSource>(
+    source: &S,
+    chan: &str,
+    query: Option<&str>,
+    format: Format,
+    names: &NameArgs,
+) -> Result<(), WinEvtError> {
+    let stdout = io::stdout();
+    let mut out = names.wrap(format.writer(stdout.lock()))?;
+    let mut rend = source.renderer();
+
+    for e in source.events_for(chan, query)? {
+        write_or_skip(&mut *out, chan, &rend.render(e?)?)?;
+    }
+
+    out.finish()
+}
+
+fn run<S: ChannelSource + Sync>(source: &S, command: &Command) -> Result<(), WinEvtError> {
+    match command {
+        Command::Channels => list_channels(source),
+        Command::Dump {
+            query_list: Some(path),
+            state,
+            output,
+            ..
+        } => print_query_list(
+            source,
+            &QueryList::from_xml(&fs::read_to_string(path)?)?,
+            state.as_deref(),
+            output,
+        ),
+        Command::Dump {
+            channels,
+            query,
+            state,
+            jobs,
+            output,
+            ..
+        } => print_channels(
+            source,
+            channels,
+            query.as_deref(),
+            *jobs,
+            state.as_deref(),
+            output,
+        ),
+        Command::Query {
+            channel,
+            query,
+            format,
+            names,
+        } => query_chan(source, channel, query.as_deref(), *format, names),
+        Command::Tail { .. } | Command::Publisher { .. } => Err(WinEvtError::new(
+            ERROR_INVALID_PARAMETER,
+            "tail and publisher only read from the local event log",
+        )),
+    }
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn tail<L: LiveSource>(
+    live: &L,
+    chan: &str,
+    query: Option<&str>,
+    start: StartAt,
+    state: Option<&Path>,
+    format: Format,
+    names: &NameArgs,
+) -> Result<(), WinEvtError> {
+    let mut bookmarks = load_state(state)?;
+    let start = match bookmarks.as_ref().and_then(|b| b.get(chan)) {
+        Some(id) => StartAt::After(id),
+        None => start,
+    };
+
+    let stdout = io::stdout();
+    let mut out = names.wrap(format.writer(stdout.lock()))?;
+    let mut rend = live.renderer();
+    let mut saved = Instant::now();
 
-    println!("Getting meta");
-    let mut meta = PubMetadataFetcher::for_publisher(TEST_PROVIDER.to_string())?;
+    for e in live.subscribe(chan, query, start)? {
+        let xml = rend.render(e?)?;
+        write_or_skip(&mut *out, chan, &xml)?;
 
-    for field in meta_fields::PUB_META_FIELDS.iter() {
-        println!("Getting {}", field.name);
-        meta.get_prop(field, &mut varw);
-        println!("{} / {}", varw.Count, varw.Type);
-        if varw.Type == winevt::EvtVarTypeString {
-            println!(
-                "{}",
-                unsafe { U16CStr::from_ptr_str(*unsafe { varw.u.StringVal() }) }.to_string_lossy()
-            );
+        if let (Some(bookmarks), Some(id)) = (bookmarks.as_mut(), bookmark::record_id(&xml)) {
+            bookmarks.advance(chan, id);
+            if saved.elapsed() >= STATE_SAVE_INTERVAL {
+                save_state(state, Some(bookmarks))?;
+                saved = Instant::now();
+            }
         }
     }
-    //    println!("Getting prop");
-    //    meta.get_prop(meta_fields::PARAMETER_FILE_PATH, &mut varw)?;
-    //    println!("{} / {}", varw.Count, varw.Type);
 
-    //    let guid = unsafe { varw.u.StringVal() };
-    //    println!(
-    //        "{}",
-    //        unsafe { U16CStr::from_ptr_str(*guid) }.to_string_lossy()
-    //    );
-    //    println!("{}", unsafe{U16CString::from_ptr_str(*guid)}.to_string_lossy());
+    out.finish()?;
+    save_state(state, bookmarks.as_ref())
+}
+
+#[cfg(windows)]
+fn tail_local(
+    chan: &str,
+    query: Option<&str>,
+    start: StartAt,
+    state: Option<&Path>,
+    format: Format,
+    names: &NameArgs,
+) -> Result<(), WinEvtError> {
+    tail(&LocalSubscriber, chan, query, start, state, format, names)
+}
+
+#[cfg(not(windows))]
+fn tail_local(
+    _chan: &str,
+    _query: Option<&str>,
+    _start: StartAt,
+    _state: Option<&Path>,
+    _format: Format,
+    _names: &NameArgs,
+) -> Result<(), WinEvtError> {
+    Cli::command()
+        .error(
+            ErrorKind::InvalidSubcommand,
+            "channels can only be followed on the local event log on Windows",
+        )
+        .exit()
+}
+
+#[cfg(windows)]
+fn publisher_metadata(name: &str, cache: Option<&Path>) -> Result<PubMetadata, WinEvtError> {
+    let meta = PubMetadataFetcher::for_publisher(name.to_string())?.fetch_all()?;
+
+    if let Some(path) = cache {
+        add_to_cache(path, name, &meta)?;
+    }
+    Ok(meta)
+}
+
+#[cfg(not(windows))]
+fn publisher_metadata(name: &str, cache: Option<&Path>) -> Result<PubMetadata, WinEvtError> {
+    let path = match cache {
+        Some(path) => path,
+        None => Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "publisher metadata can only be read from the local event log on Windows, or from a --cache file",
+            )
+            .exit(),
+    };
+
+    match MetadataCache::load(path)?.find(name, None, DEFAULT_LOCALE) {
+        Some((_, meta)) => Ok(meta.clone()),
+        None => Err(WinEvtError::new(
+            ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND,
+            format!("no metadata for {} in {}", name, path.display()),
+        )),
+    }
+}
+
+fn binary_metadata(
+    name: &str,
+    binary: &Path,
+    cache: Option<&Path>,
+) -> Result<PubMetadata, WinEvtError> {
+    let mut providers = wevt::open(binary)?;
+    let guid = |g: &str| {
+        g.trim_matches(|c| c == '{' || c == '}')
+            .to_ascii_lowercase()
+    };
+
+    let found = providers
+        .iter()
+        .position(|p| p.guid.as_deref().map(guid) == Some(guid(name)));
+    let meta = match found {
+        Some(i) => providers.swap_remove(i),
+        None if providers.len() == 1 => providers.remove(0),
+        None => {
+            return Err(WinEvtError::new(
+                ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND,
+                format!(
+                    "{} defines {} publishers and none has the GUID {}",
+                    binary.display(),
+                    providers.len(),
+                    name
+                ),
+            ))
+        }
+    };
+
+    if let Some(path) = cache {
+        add_to_cache(path, name, &meta)?;
+    }
+    Ok(meta)
+}
+
+fn manifest_metadata(
+    name: &str,
+    manifest: &Path,
+    cache: Option<&Path>,
+) -> Result<PubMetadata, WinEvtError> {
+    let meta = match Manifest::open(manifest)?.provider(name) {
+        Some(provider) => provider.metadata.clone(),
+        None => {
+            return Err(WinEvtError::new(
+                ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND,
+                format!("{} doesn't define {}", manifest.display(), name),
+            ))
+        }
+    };
+
+    if let Some(path) = cache {
+        add_to_cache(path, name, &meta)?;
+    }
+    Ok(meta)
+}
+
+fn add_to_cache(path: &Path, name: &str, meta: &PubMetadata) -> Result<(), WinEvtError> {
+    let mut cached = MetadataCache::load(path)?;
+    cached.insert(ProviderKey::new(name, None, DEFAULT_LOCALE), meta.clone());
+    cached.save(path)
+}
+
+fn print_publisher(
+    name: &str,
+    binary: Option<&Path>,
+    manifest: Option<&Path>,
+    cache: Option<&Path>,
+) -> Result<(), WinEvtError> {
+    let meta = match (binary, manifest) {
+        (Some(binary), _) => binary_metadata(name, binary, cache)?,
+        (None, Some(manifest)) => manifest_metadata(name, manifest, cache)?,
+        (None, None) => publisher_metadata(name, cache)?,
+    };
+    let or_none = |s: &Option<String>| s.clone().unwrap_or_else(|| "-".to_string());
+
+    println!("Publisher Guid: {}", or_none(&meta.guid));
+    println!("Resource File Path: {}", or_none(&meta.resource_file_path));
+    println!(
+        "Parameter File Path: {}",
+        or_none(&meta.parameter_file_path)
+    );
+    println!("Message File Path: {}", or_none(&meta.message_file_path));
+    println!("Help Link: {}", or_none(&meta.help_link));
+
+    println!("Channels:");
+    for c in &meta.channels {
+        let imported = if c.imported { " (imported)" } else { "" };
+        println!(
+            "  {} {}{}",
+            c.id.unwrap_or_default(),
+            or_none(&c.name),
+            imported
+        );
+    }
+    println!("Levels:");
+    for l in &meta.levels {
+        println!("  {} {}", l.id.unwrap_or_default(), or_none(&l.name));
+    }
+    println!("Tasks:");
+    for t in &meta.tasks {
+        println!("  {} {}", t.value.unwrap_or_default(), or_none(&t.name));
+    }
+    println!("Opcodes:");
+    for o in &meta.opcodes {
+        println!(
+            "  {}/{} {}",
+            o.task_id.unwrap_or_default(),
+            o.opcode_value.unwrap_or_default(),
+            or_none(&o.name)
+        );
+    }
+    println!("Keywords:");
+    for k in &meta.keywords {
+        println!(
+            "  0x{:016x} {}",
+            k.mask.unwrap_or_default(),
+            or_none(&k.name)
+        );
+    }
+    println!("Events:");
+    for e in &meta.events {
+        println!(
+            "  {} v{} level {} task {} opcode {}",
+            e.id, e.version, e.level, e.task, e.opcode
+        );
+    }
 
     Ok(())
 }
 
 fn main() -> Result<(), WinEvtError> {
-    if DUMP {
-        print_channels()?;
+    let cli = Cli::parse();
+    let source = &cli.source;
+
+    if let (Command::Tail { .. } | Command::Publisher { .. }, Some(flag)) =
+        (&cli.command, source.given())
+    {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("{} can't be used with tail or publisher", flag),
+            )
+            .exit()
     }
 
-    if LEVELS {
-        print_levels()?;
+    match &cli.command {
+        Command::Publisher {
+            name,
+            cache,
+            binary,
+            manifest,
+        } => {
+            return print_publisher(
+                name,
+                binary.as_deref(),
+                manifest.as_deref(),
+                cache.as_deref(),
+            )
+        }
+        Command::Tail {
+            channel,
+            query,
+            start,
+            state,
+            format,
+            names,
+        } => {
+            return tail_local(
+                channel,
+                query.as_deref(),
+                *start,
+                state.as_deref(),
+                *format,
+                names,
+            )
+        }
+        _ => {}
     }
 
-    Ok(())
+    if let Some(path) = &source.evtx {
+        let evtx = if path.is_dir() {
+            EvtxSource::from_dir(path)?
+        } else {
+            EvtxSource::open(path)?
+        };
+        return run(
+            &evtx.with_validation(source.corrupt.unwrap_or(CorruptChunks::Warn)),
+            &cli.command,
+        );
+    }
+
+    if let Some(path) = &source.fixtures {
+        let fixtures = if path.is_dir() {
+            FixtureSource::from_dir(path)?
+        } else {
+            FixtureSource::from_dump(path)?
+        };
+        return run(&fixtures, &cli.command);
+    }
+
+    if !source.carve.is_empty() {
+        let mut carve = CarveSource::new();
+        source.carve.iter().for_each(|image| carve.add_image(image));
+        let result = run(&carve, &cli.command);
+
+        let stats = carve.stats();
+        eprintln!(
+            "Carved {} records from {} chunks and {} orphaned records, {} of which couldn't be decoded",
+            stats.records, stats.chunks, stats.orphans, stats.undecodable
+        );
+        return result;
+    }
+
+    #[cfg(windows)]
+    return run(&LocalChannels, &cli.command);
+
+    #[cfg(not(windows))]
+    Cli::command()
+        .error(
+            ErrorKind::MissingRequiredArgument,
+            "there is no local event log; give --evtx, --fixtures or --carve",
+        )
+        .exit()
 }
@@ -0,0 +1,458 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+use crate::binxml::escape;
+use crate::errors::{WinEvtError, ERROR_EVT_MALFORMED_XML_TEXT, ERROR_INVALID_DATA};
+use crate::event::Event;
+use crate::message::{event_data, format_message};
+use crate::metadata_cache::DEFAULT_LOCALE;
+use crate::pub_metadata::{Channel, EventMetadata, Keyword, Level, OpCode, PubMetadata, Task};
+use crate::winmeta;
+
+const TEMPLATE_XMLNS: &str = "http://schemas.microsoft.com/win/2004/08/events";
+
+/// The providers an instrumentation manifest (`.man` file) defines, checked
+/// the way the message compiler would check them: every channel, level,
+/// task, opcode, keyword, template and string an event or provider refers
+/// to has to be defined, or be one of the standard `win:` ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub providers: Vec<ManifestProvider>,
+    /// The string tables by culture, e.g. `en-US`, each by string ID
+    pub strings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestProvider {
+    pub name: String,
+    pub metadata: PubMetadata,
+    /// The string ID behind each message ID in the metadata. Manifests refer
+    /// to messages by string, so they're numbered from 1 as they come up.
+    pub messages: BTreeMap<u32, String>,
+}
+
+impl Manifest {
+    pub fn parse(xml: &str) -> Result<Self, WinEvtError> {
+        let doc = Document::parse(xml)
+            .map_err(|e| WinEvtError::parse(ERROR_EVT_MALFORMED_XML_TEXT, e))?;
+
+        let root = doc.root_element();
+        if !root.has_tag_name("instrumentationManifest") {
+            return Err(WinEvtError::new(
+                ERROR_EVT_MALFORMED_XML_TEXT,
+                format!(
+                    "expected an instrumentationManifest element, not {}",
+                    root.tag_name().name()
+                ),
+            ));
+        }
+
+        let mut manifest = Manifest::default();
+        for resources in descendants(root, "resources") {
+            let culture = resources.attribute("culture").unwrap_or(DEFAULT_LOCALE);
+            let table = manifest.strings.entry(culture.to_string()).or_default();
+            for string in descendants(resources, "string") {
+                table.insert(
+                    attr(string, "id")?.to_string(),
+                    attr(string, "value")?.to_string(),
+                );
+            }
+        }
+
+        for provider in descendants(root, "provider") {
+            let reader = ProviderReader {
+                strings: &manifest.strings,
+                messages: BTreeMap::new(),
+            };
+            let provider = reader.read(provider)?;
+            manifest.providers.push(provider);
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        Manifest::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The provider with a name or GUID, ignoring case and braces
+    pub fn provider(&self, name: &str) -> Option<&ManifestProvider> {
+        let bare = |s: &str| {
+            s.trim_matches(|c| c == '{' || c == '}')
+                .to_ascii_lowercase()
+        };
+        self.providers.iter().find(|p| {
+            p.name.eq_ignore_ascii_case(name)
+                || p.metadata.guid.as_deref().map(bare) == Some(bare(name))
+        })
+    }
+
+    /// A string in the culture asked for, falling back to en-US, then to
+    /// whichever culture has it
+    pub fn string(&self, id: &str, culture: &str) -> Option<&str> {
+        [culture, DEFAULT_LOCALE]
+            .iter()
+            .filter_map(|c| self.strings.get(*c))
+            .chain(self.strings.values())
+            .find_map(|table| table.get(id))
+            .map(String::as_str)
+    }
+
+    /// The text of one of a provider's message IDs
+    pub fn message(&self, provider: &ManifestProvider, id: u32, culture: &str) -> Option<&str> {
+        self.string(provider.messages.get(&id)?, culture)
+    }
+
+    /// Renders the message of an event from one of the providers, as Event
+    /// Viewer would show it once the manifest is installed; `None` if the
+    /// event isn't one of theirs or has no message
+    pub fn format_event(&self, xml: &str, culture: &str) -> Result<Option<String>, WinEvtError> {
+        let system = Event::from_xml(xml)?.system;
+        let provider = match system
+            .provider
+            .name
+            .as_deref()
+            .or(system.provider.guid.as_deref())
+            .and_then(|name| self.provider(name))
+        {
+            Some(provider) => provider,
+            None => return Ok(None),
+        };
+
+        let id = system.event_id.unwrap_or_default();
+        let version = system.version.unwrap_or_default();
+        let events = &provider.metadata.events;
+        let event = events
+            .iter()
+            .find(|e| e.id == id && e.version == version)
+            .or_else(|| events.iter().find(|e| e.id == id));
+
+        let text = match event
+            .and_then(|e| e.message_id)
+            .and_then(|m| self.message(provider, m, culture))
+        {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        Ok(Some(format_message(text, &event_data(xml)?)))
+    }
+}
+
+/// Reads one `<provider>`, numbering its messages as it goes
+struct ProviderReader<'a> {
+    strings: &'a BTreeMap<String, BTreeMap<String, String>>,
+    messages: BTreeMap<u32, String>,
+}
+
+impl ProviderReader<'_> {
+    fn read(mut self, node: Node) -> Result<ManifestProvider, WinEvtError> {
+        let name = attr(node, "name")?.to_string();
+        let mut meta = PubMetadata {
+            guid: Some(attr(node, "guid")?.to_string()),
+            resource_file_path: node.attribute("resourceFileName").map(str::to_string),
+            parameter_file_path: node.attribute("parameterFileName").map(str::to_string),
+            message_file_path: node.attribute("messageFileName").map(str::to_string),
+            message_id: self.message(node)?,
+            ..PubMetadata::default()
+        };
+
+        // Channels without a value are numbered after the event log's own
+        let mut chids = BTreeMap::new();
+        let mut next = Some(winmeta::FIRST_CHANNEL);
+        let channels = section(node, "channels")
+            .filter(|c| c.has_tag_name("importChannel") || c.has_tag_name("channel"));
+        for (index, c) in channels.enumerate() {
+            let imported = c.has_tag_name("importChannel");
+            let channel_name = attr(c, "name")?;
+            let value = match c.attribute("value") {
+                Some(v) => Some(number(c, "value", v)?),
+                None if imported => winmeta::lookup(winmeta::CHANNELS, channel_name),
+                None => None,
+            };
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    let value = next.ok_or_else(|| invalid(c, "too many channels to number"))?;
+                    next = value.checked_add(1);
+                    value
+                }
+            };
+
+            chids.insert(c.attribute("chid").unwrap_or(channel_name), value);
+            chids.insert(channel_name, value);
+            meta.channels.push(Channel {
+                name: Some(channel_name.to_string()),
+                index: Some(index as u32),
+                id: Some(value.into()),
+                imported,
+                message_id: self.message(c)?,
+            });
+        }
+
+        for l in section(node, "levels").filter(|n| n.has_tag_name("level")) {
+            meta.levels.push(Level {
+                name: Some(attr(l, "name")?.to_string()),
+                id: Some(required_number(l, "value")?),
+                message_id: self.message(l)?,
+            });
+        }
+
+        for t in section(node, "tasks").filter(|n| n.has_tag_name("task")) {
+            let value = required_number(t, "value")?;
+            meta.tasks.push(Task {
+                name: Some(attr(t, "name")?.to_string()),
+                guid: t.attribute("eventGUID").map(str::to_string),
+                value: Some(value),
+                message_id: self.message(t)?,
+            });
+            for o in section(t, "opcodes").filter(|n| n.has_tag_name("opcode")) {
+                meta.opcodes.push(self.opcode(o, value as u16)?);
+            }
+        }
+
+        for o in section(node, "opcodes").filter(|n| n.has_tag_name("opcode")) {
+            meta.opcodes.push(self.opcode(o, 0)?);
+        }
+
+        for k in section(node, "keywords").filter(|n| n.has_tag_name("keyword")) {
+            meta.keywords.push(Keyword {
+                name: Some(attr(k, "name")?.to_string()),
+                mask: Some(required_number(k, "mask")?),
+                message_id: self.message(k)?,
+            });
+        }
+
+        let mut templates = BTreeMap::new();
+        for t in section(node, "templates").filter(|n| n.has_tag_name("template")) {
+            templates.insert(attr(t, "tid")?, template(t));
+        }
+
+        for e in section(node, "events").filter(|n| n.has_tag_name("event")) {
+            let event = self.event(e, &meta, &chids, &templates)?;
+            meta.events.push(event);
+        }
+
+        Ok(ManifestProvider {
+            name,
+            metadata: meta,
+            messages: self.messages,
+        })
+    }
+
+    fn opcode(&mut self, node: Node, task: u16) -> Result<OpCode, WinEvtError> {
+        Ok(OpCode {
+            name: Some(attr(node, "name")?.to_string()),
+            opcode_value: Some(required_number(node, "value")?),
+            task_id: Some(task),
+            message_id: self.message(node)?,
+        })
+    }
+
+    fn event(
+        &mut self,
+        node: Node,
+        meta: &PubMetadata,
+        chids: &BTreeMap<&str, u8>,
+        templates: &BTreeMap<&str, String>,
+    ) -> Result<EventMetadata, WinEvtError> {
+        let named = |attr| node.attribute(attr).filter(|s| !s.is_empty());
+        let missing = |kind: &str, name: &str| {
+            invalid(
+                node,
+                format!(
+                    "event {} uses undefined {} {}",
+                    event_value(node),
+                    kind,
+                    name
+                ),
+            )
+        };
+
+        let channel = match named("channel") {
+            Some(c) => *chids.get(c).ok_or_else(|| missing("channel", c))?,
+            None => 0,
+        };
+
+        let level = match named("level") {
+            Some(l) => meta
+                .levels
+                .iter()
+                .find(|d| d.name.as_deref() == Some(l))
+                .and_then(|d| d.id)
+                .map(|id| id as u8)
+                .or_else(|| winmeta::lookup(winmeta::LEVELS, l))
+                .ok_or_else(|| missing("level", l))?,
+            None => 0,
+        };
+
+        let task = match named("task") {
+            Some(t) => meta
+                .tasks
+                .iter()
+                .find(|d| d.name.as_deref() == Some(t))
+                .and_then(|d| d.value)
+                .ok_or_else(|| missing("task", t))? as u16,
+            None => 0,
+        };
+
+        // An opcode may be one of the task's own, the provider's or standard
+        let opcode = match named("opcode") {
+            Some(o) => [task, 0]
+                .iter()
+                .find_map(|&task| {
+                    meta.opcodes
+                        .iter()
+                        .find(|d| d.task_id == Some(task) && d.name.as_deref() == Some(o))
+                        .and_then(|d| d.opcode_value)
+                })
+                .map(|v| v as u8)
+                .or_else(|| winmeta::lookup(winmeta::OPCODES, o))
+                .ok_or_else(|| missing("opcode", o))?,
+            None => 0,
+        };
+
+        let mut keywords = 0;
+        for k in named("keywords").unwrap_or_default().split_whitespace() {
+            keywords |= meta
+                .keywords
+                .iter()
+                .find(|d| d.name.as_deref() == Some(k))
+                .and_then(|d| d.mask)
+                .or_else(|| winmeta::lookup(winmeta::KEYWORDS, k))
+                .ok_or_else(|| missing("keyword", k))?;
+        }
+
+        let template = match named("template") {
+            Some(t) => Some(
+                templates
+                    .get(t)
+                    .ok_or_else(|| missing("template", t))?
+                    .clone(),
+            ),
+            None => None,
+        };
+
+        Ok(EventMetadata {
+            id: required_number(node, "value")?,
+            version: optional_number(node, "version")?.unwrap_or_default(),
+            channel,
+            level,
+            opcode,
+            task,
+            keywords,
+            message_id: self.message(node)?,
+            template,
+        })
+    }
+
+    /// Numbers the string a `message="$(string.Id)"` attribute refers to
+    fn message(&mut self, node: Node) -> Result<Option<u32>, WinEvtError> {
+        let reference = match node.attribute("message") {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        let id = reference
+            .strip_prefix("$(string.")
+            .and_then(|r| r.strip_suffix(')'))
+            .ok_or_else(|| invalid(node, format!("{} isn't a string reference", reference)))?;
+
+        if !self.strings.values().any(|table| table.contains_key(id)) {
+            return Err(invalid(
+                node,
+                format!("string {} isn't in any string table", id),
+            ));
+        }
+
+        if let Some((&n, _)) = self.messages.iter().find(|(_, s)| *s == id) {
+            return Ok(Some(n));
+        }
+        let n = self.messages.len() as u32 + 1;
+        self.messages.insert(n, id.to_string());
+        Ok(Some(n))
+    }
+}
+
+/// A `<template>` as the event log gives one, with the `<data>` items but
+/// not the `tid`
+fn template(node: Node) -> String {
+    let mut xml = format!("<template xmlns=\"{}\">", TEMPLATE_XMLNS);
+    for data in node.children().filter(|n| n.has_tag_name("data")) {
+        xml.push_str("<data");
+        for a in data.attributes() {
+            xml.push(' ');
+            xml.push_str(a.name());
+            xml.push_str("=\"");
+            escape(&mut xml, a.value(), true);
+            xml.push('"');
+        }
+        xml.push_str("/>");
+    }
+    xml.push_str("</template>");
+    xml
+}
+
+/// The element children of a provider's section, e.g. its `<levels>`
+fn section<'a, 'i>(node: Node<'a, 'i>, name: &'a str) -> impl Iterator<Item = Node<'a, 'i>> {
+    node.children()
+        .filter(move |n| n.has_tag_name(name))
+        .flat_map(|s| s.children())
+        .filter(Node::is_element)
+}
+
+fn descendants<'a, 'i>(node: Node<'a, 'i>, name: &'a str) -> impl Iterator<Item = Node<'a, 'i>> {
+    node.descendants().filter(move |n| n.has_tag_name(name))
+}
+
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, WinEvtError> {
+    node.attribute(name).ok_or_else(|| {
+        invalid(
+            node,
+            format!(
+                "{} is missing its {} attribute",
+                node.tag_name().name(),
+                name
+            ),
+        )
+    })
+}
+
+fn required_number<T: TryFrom<u64>>(node: Node, name: &str) -> Result<T, WinEvtError> {
+    number(node, name, attr(node, name)?)
+}
+
+fn optional_number<T: TryFrom<u64>>(node: Node, name: &str) -> Result<Option<T>, WinEvtError> {
+    node.attribute(name)
+        .map(|v| number(node, name, v))
+        .transpose()
+}
+
+/// A decimal or `0x` hex value that fits the type
+fn number<T: TryFrom<u64>>(node: Node, name: &str, value: &str) -> Result<T, WinEvtError> {
+    let n = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    n.ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| invalid(node, format!("{}=\"{}\" isn't a valid value", name, value)))
+}
+
+fn event_value<'a>(node: Node<'a, '_>) -> &'a str {
+    node.attribute("value").unwrap_or("?")
+}
+
+/// An error pointing at the element it's about
+fn invalid<S: Into<String>>(node: Node, msg: S) -> WinEvtError {
+    let pos = node.document().text_pos_at(node.range().start);
+    WinEvtError::new(
+        ERROR_INVALID_DATA,
+        format!("line {}: {}", pos.row, msg.into()),
+    )
+}
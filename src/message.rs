@@ -0,0 +1,316 @@
+use std::convert::TryFrom;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::errors::{WinEvtError, ERROR_INVALID_DATA};
+
+/// Inserts are numbered `%1` to `%99`
+const MAX_INSERT_DIGITS: usize = 2;
+/// The widest width or precision a printf spec may give
+const MAX_WIDTH: usize = 1024;
+
+/// Expands an event message the way `FormatMessage` does, filling its
+/// inserts from an event's `EventData` values.
+///
+/// `%1` to `%99` are replaced by the matching value, formatted by a printf
+/// spec if one follows between `!`s, as in `%1!08X!`; inserts without a
+/// value are left as they are. `%n` is a line break, `%r` a carriage return,
+/// `%t` a tab and `%0` ends the message; `%` before anything else, `%%`
+/// included, just gives that character.
+///
+/// Values often refer to parameter strings as `%%1234`, which are left as
+/// they are; see [`format_message_with_params`] to look them up.
+pub fn format_message<S: AsRef<str>>(message: &str, inserts: &[S]) -> String {
+    format_message_with_params(message, inserts, |_| None)
+}
+
+/// [`format_message`], replacing each `%%1234` in the inserted values with
+/// what `params` gives for 1234, usually a message from the provider's
+/// parameter file. References it has nothing for are left as they are.
+pub fn format_message_with_params<S, F>(message: &str, inserts: &[S], mut params: F) -> String
+where
+    S: AsRef<str>,
+    F: FnMut(u32) -> Option<String>,
+{
+    let mut out = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            None => out.push('%'),
+            Some('0') => break,
+            Some('n') => out.push_str("\r\n"),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(d @ '1'..='9') => {
+                let mut n = d.to_digit(10).unwrap() as usize;
+                for _ in 1..MAX_INSERT_DIGITS {
+                    match chars.peek().and_then(|c| c.to_digit(10)) {
+                        Some(d) => {
+                            n = n * 10 + d as usize;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                let spec = take_spec(&mut chars);
+
+                match inserts.get(n - 1) {
+                    Some(value) => {
+                        let value = expand_params(value.as_ref(), &mut params);
+                        match &spec {
+                            Some(spec) => out.push_str(&printf(spec, &value)),
+                            None => out.push_str(&value),
+                        }
+                    }
+                    None => {
+                        out.push('%');
+                        out.push_str(&n.to_string());
+                        if let Some(spec) = spec {
+                            out.push('!');
+                            out.push_str(&spec);
+                            out.push('!');
+                        }
+                    }
+                }
+            }
+            Some(other) => out.push(other),
+        }
+    }
+
+    out
+}
+
+/// The printf spec of an insert, if there's a complete `!...!` after it
+fn take_spec(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.peek() != Some(&'!') {
+        return None;
+    }
+
+    let mut spec = String::new();
+    for (len, c) in chars.clone().skip(1).enumerate() {
+        if c == '!' {
+            // Past both `!`s
+            chars.nth(len + 1);
+            return Some(spec);
+        }
+        spec.push(c);
+    }
+    None
+}
+
+/// Replaces `%%1234` parameter references
+fn expand_params<F: FnMut(u32) -> Option<String>>(value: &str, params: &mut F) -> String {
+    if !value.contains("%%") {
+        return value.to_string();
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("%%") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+        match after[..digits].parse().ok().and_then(&mut *params) {
+            Some(param) => out.push_str(param.trim_end_matches(['\r', '\n'])),
+            None => out.push_str(&rest[start..start + 2 + digits]),
+        }
+        rest = &after[digits..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Formats a value by a printf spec such as `s`, `-10s`, `08X` or `I64u`.
+///
+/// Event values are already text, so numeric conversions parse them first,
+/// as decimal or `0x` hex; anything that doesn't parse, or a width or
+/// precision over [`MAX_WIDTH`], is inserted as is.
+fn printf(spec: &str, value: &str) -> String {
+    let mut chars = spec.chars().peekable();
+
+    let (mut left, mut zero, mut plus, mut space, mut alt) = (false, false, false, false, false);
+    while let Some(&c) = chars.peek() {
+        match c {
+            '-' => left = true,
+            '0' => zero = true,
+            '+' => plus = true,
+            ' ' => space = true,
+            '#' => alt = true,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    let width = take_number(&mut chars).unwrap_or(0);
+    let precision = if chars.peek() == Some(&'.') {
+        chars.next();
+        Some(take_number(&mut chars).unwrap_or(0))
+    } else {
+        None
+    };
+    if width > MAX_WIDTH || precision.is_some_and(|p| p > MAX_WIDTH) {
+        return value.to_string();
+    }
+
+    // Only whether a number is 64 bits wide matters here
+    let rest: String = chars.collect();
+    let wide = rest.starts_with("I64") || rest.starts_with("ll");
+    let conv = match rest.chars().last() {
+        Some(c) => c,
+        None => return value.to_string(),
+    };
+
+    let (sign, body) = match conv {
+        's' | 'S' | 'Z' => {
+            let body = match precision {
+                Some(p) => value.chars().take(p).collect(),
+                None => value.to_string(),
+            };
+            return pad(String::new(), body, width, left, false);
+        }
+        'c' | 'C' => {
+            let c = match parse_int(value) {
+                Some(n) => u32::try_from(n).ok().and_then(char::from_u32),
+                None => value.chars().next(),
+            };
+            let body = c.map(String::from).unwrap_or_default();
+            return pad(String::new(), body, width, left, false);
+        }
+        'd' | 'i' => match parse_int(value) {
+            Some(n) => {
+                let n = if wide {
+                    n as i64 as i128
+                } else {
+                    n as i32 as i128
+                };
+                let sign = if n < 0 {
+                    "-"
+                } else if plus {
+                    "+"
+                } else if space {
+                    " "
+                } else {
+                    ""
+                };
+                (sign.to_string(), n.unsigned_abs().to_string())
+            }
+            None => return value.to_string(),
+        },
+        'u' | 'x' | 'X' | 'o' => match parse_int(value) {
+            Some(n) => {
+                let n = if wide { n as u64 } else { n as u32 as u64 };
+                match conv {
+                    'u' => (String::new(), n.to_string()),
+                    'x' => (prefix(alt && n != 0, "0x"), format!("{:x}", n)),
+                    'X' => (prefix(alt && n != 0, "0X"), format!("{:X}", n)),
+                    _ => (prefix(alt && n != 0, "0"), format!("{:o}", n)),
+                }
+            }
+            None => return value.to_string(),
+        },
+        _ => return value.to_string(),
+    };
+
+    // A precision is the least number of digits, and turns off zero padding
+    let body = match precision {
+        Some(p) if body.len() < p => format!("{}{}", "0".repeat(p - body.len()), body),
+        _ => body,
+    };
+    pad(sign, body, width, left, zero && precision.is_none())
+}
+
+/// Reads a width or precision; one too big for a `usize` comes back as
+/// `usize::MAX`, past any limit
+fn take_number(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut n: Option<usize> = None;
+    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+        n = Some(
+            n.unwrap_or(0)
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(d as usize))
+                .unwrap_or(usize::MAX),
+        );
+        chars.next();
+    }
+    n
+}
+
+fn prefix(add: bool, prefix: &str) -> String {
+    if add {
+        prefix.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Pads to the width with spaces, or with zeros between the sign and digits
+fn pad(sign: String, body: String, width: usize, left: bool, zero: bool) -> String {
+    let len = sign.chars().count() + body.chars().count();
+    if len >= width {
+        return sign + &body;
+    }
+
+    let fill = width - len;
+    if left {
+        format!("{}{}{}", sign, body, " ".repeat(fill))
+    } else if zero {
+        format!("{}{}{}", sign, "0".repeat(fill), body)
+    } else {
+        format!("{}{}{}", " ".repeat(fill), sign, body)
+    }
+}
+
+fn parse_int(s: &str) -> Option<i128> {
+    let s = s.trim();
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    // The parsers below would take a second sign, as in `--5`
+    if neg && (s.starts_with('-') || s.starts_with('+')) {
+        return None;
+    }
+    let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) if hex.starts_with('-') || hex.starts_with('+') => return None,
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => s.parse::<i128>().ok()?,
+    };
+    Some(if neg { -n } else { n })
+}
+
+/// The values of an event's `EventData`, or failing that its `UserData`, in
+/// the order they're numbered as inserts
+pub fn event_data(xml: &str) -> Result<Vec<String>, WinEvtError> {
+    let doc =
+        roxmltree::Document::parse(xml).map_err(|e| WinEvtError::parse(ERROR_INVALID_DATA, e))?;
+    let root = doc.root_element();
+
+    if let Some(data) = root
+        .children()
+        .find(|n| n.has_tag_name("EventData") || n.has_tag_name("UserData"))
+    {
+        let mut values = Vec::new();
+        collect_leaves(data, &mut values);
+        return Ok(values);
+    }
+    Ok(Vec::new())
+}
+
+/// Text of the elements without element children, which for `UserData`
+/// are the values whatever the provider called them
+fn collect_leaves(node: roxmltree::Node, values: &mut Vec<String>) {
+    for child in node.children().filter(|n| n.is_element()) {
+        if child.children().any(|n| n.is_element()) {
+            collect_leaves(child, values);
+        } else {
+            values.push(child.text().unwrap_or_default().to_string());
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{WinEvtError, ERROR_INVALID_DATA};
+use crate::pub_metadata::PubMetadata;
+
+const VERSION: u64 = 1;
+
+/// The locale metadata is read in when none is asked for
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Which provider, in which version and locale, cached metadata is for
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ProviderKey {
+    pub name: String,
+    /// Usually the version of the provider's message file; `None` if unknown
+    pub version: Option<String>,
+    /// The locale its messages are in, e.g. `en-US`
+    pub locale: String,
+}
+
+impl ProviderKey {
+    pub fn new<N: Into<String>, L: Into<String>>(
+        name: N,
+        version: Option<String>,
+        locale: L,
+    ) -> Self {
+        ProviderKey {
+            name: name.into(),
+            version,
+            locale: locale.into(),
+        }
+    }
+}
+
+/// How a cache file is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheFormat {
+    Json,
+    Cbor,
+}
+
+impl CacheFormat {
+    /// CBOR for `.cbor` files and JSON for anything else
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("cbor") => CacheFormat::Cbor,
+            _ => CacheFormat::Json,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u64,
+    providers: Vec<CacheEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    name: String,
+    version: Option<String>,
+    locale: String,
+    metadata: PubMetadata,
+}
+
+/// Publisher metadata exported from the machine that logged the events, so
+/// they can be rendered somewhere the publishers aren't installed.
+///
+/// The cache is a JSON or CBOR file listing each provider's metadata along
+/// with its name, version and locale:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "providers": [
+///     {
+///       "name": "Microsoft-Windows-Sysmon",
+///       "version": "15.0.0.0",
+///       "locale": "en-US",
+///       "metadata": { "guid": "{5770385F-C22A-43E0-BF4C-06F5698FFBD9}", ... }
+///     }
+///   ]
+/// }
+/// ```
+///
+/// Either encoding is read regardless of the file name; CBOR is written to
+/// `.cbor` files and JSON to everything else.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataCache {
+    providers: BTreeMap<ProviderKey, PubMetadata>,
+}
+
+impl MetadataCache {
+    pub fn new() -> Self {
+        MetadataCache::default()
+    }
+
+    /// Reads a cache file, which is treated as empty if it doesn't exist yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        match fs::read(path) {
+            Ok(data) => MetadataCache::from_slice(&data),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(MetadataCache::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the cache file in the format its name calls for, replacing it
+    /// in one go
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WinEvtError> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, self.to_vec(CacheFormat::from_path(path))?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Parses a cache in either format; JSON always starts with a `{`, which
+    /// CBOR maps never do
+    pub fn from_slice(data: &[u8]) -> Result<Self, WinEvtError> {
+        let json = data
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|&b| b == b'{');
+
+        let file: CacheFile = if json {
+            serde_json::from_slice(data).map_err(|e| WinEvtError::parse(ERROR_INVALID_DATA, e))?
+        } else {
+            ciborium::from_reader(data).map_err(|e| WinEvtError::parse(ERROR_INVALID_DATA, e))?
+        };
+
+        if file.version != VERSION {
+            return Err(invalid(format!(
+                "unsupported metadata cache version {}",
+                file.version
+            )));
+        }
+
+        let providers = file
+            .providers
+            .into_iter()
+            .map(|e| (ProviderKey::new(e.name, e.version, e.locale), e.metadata))
+            .collect();
+        Ok(MetadataCache { providers })
+    }
+
+    pub fn to_vec(&self, format: CacheFormat) -> Result<Vec<u8>, WinEvtError> {
+        let file = CacheFile {
+            version: VERSION,
+            providers: self
+                .providers
+                .iter()
+                .map(|(key, metadata)| CacheEntry {
+                    name: key.name.clone(),
+                    version: key.version.clone(),
+                    locale: key.locale.clone(),
+                    metadata: metadata.clone(),
+                })
+                .collect(),
+        };
+
+        match format {
+            CacheFormat::Json => {
+                let mut json =
+                    serde_json::to_vec_pretty(&file).map_err(|e| invalid(e.to_string()))?;
+                json.push(b'\n');
+                Ok(json)
+            }
+            CacheFormat::Cbor => {
+                let mut cbor = Vec::new();
+                ciborium::into_writer(&file, &mut cbor).map_err(|e| invalid(e.to_string()))?;
+                Ok(cbor)
+            }
+        }
+    }
+
+    /// Adds a provider's metadata, replacing any for the same key
+    pub fn insert(&mut self, key: ProviderKey, metadata: PubMetadata) {
+        self.providers.insert(key, metadata);
+    }
+
+    pub fn get(&self, key: &ProviderKey) -> Option<&PubMetadata> {
+        self.providers.get(key)
+    }
+
+    /// The best match for a provider: the exact version and locale if
+    /// cached, otherwise any version in the locale, otherwise any at all.
+    /// Names are matched ignoring case as Windows does.
+    pub fn find(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        locale: &str,
+    ) -> Option<(&ProviderKey, &PubMetadata)> {
+        let named: Vec<_> = self
+            .providers
+            .iter()
+            .filter(|(key, _)| key.name.eq_ignore_ascii_case(name))
+            .collect();
+
+        let same_locale = |key: &ProviderKey| key.locale.eq_ignore_ascii_case(locale);
+        named
+            .iter()
+            .find(|(key, _)| same_locale(key) && key.version.as_deref() == version)
+            .or_else(|| named.iter().find(|(key, _)| same_locale(key)))
+            .or_else(|| named.first())
+            .copied()
+    }
+
+    pub fn remove(&mut self, key: &ProviderKey) -> Option<PubMetadata> {
+        self.providers.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ProviderKey, &PubMetadata)> {
+        self.providers.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+fn invalid(msg: String) -> WinEvtError {
+    WinEvtError::new(ERROR_INVALID_DATA, msg)
+}
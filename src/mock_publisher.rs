@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::bookmark;
+use crate::errors::WinEvtError;
+use crate::fixture::FixtureRenderer;
+use crate::source::{EventSource, LiveSource, StartAt};
+use crate::xpath::XPathFilter;
+
+#[derive(Default)]
+struct Log {
+    channels: BTreeMap<String, Vec<String>>,
+    closed: bool,
+}
+
+/// A [`LiveSource`] that events are published to by hand, for testing
+/// subscribers without an event log.
+///
+/// Clones share the same channels, so one can be handed to a subscriber
+/// while another publishes from a different thread. Subscriptions end once
+/// the publisher is closed and they've caught up.
+#[derive(Clone, Default)]
+pub struct MockPublisher {
+    log: Arc<(Mutex<Log>, Condvar)>,
+}
+
+impl MockPublisher {
+    pub fn new() -> Self {
+        MockPublisher::default()
+    }
+
+    /// Appends a rendered event to a channel, waking its subscribers
+    pub fn publish<S: Into<String>, E: Into<String>>(&self, channel: S, xml: E) {
+        let (log, published) = &*self.log;
+        log.lock()
+            .unwrap()
+            .channels
+            .entry(channel.into())
+            .or_default()
+            .push(xml.into());
+        published.notify_all();
+    }
+
+    /// Ends every subscription once it has read what was published
+    pub fn close(&self) {
+        let (log, published) = &*self.log;
+        log.lock().unwrap().closed = true;
+        published.notify_all();
+    }
+}
+
+impl LiveSource for MockPublisher {
+    type Events = MockSubscription;
+    type Renderer = FixtureRenderer;
+
+    fn subscribe(
+        &self,
+        channel: &str,
+        query: Option<&str>,
+        start: StartAt,
+    ) -> Result<MockSubscription, WinEvtError> {
+        let filter = query.map(XPathFilter::parse).transpose()?;
+
+        let log = self.log.0.lock().unwrap();
+        let events = log.channels.get(channel).map_or(&[][..], Vec::as_slice);
+        let pos = match start {
+            StartAt::Now => events.len(),
+            StartAt::Oldest => 0,
+            StartAt::After(id) => events
+                .iter()
+                .position(|xml| bookmark::record_id(xml).is_some_and(|r| r > id))
+                .unwrap_or(events.len()),
+        };
+
+        Ok(MockSubscription {
+            publisher: self.clone(),
+            channel: channel.to_string(),
+            pos,
+            filter,
+        })
+    }
+
+    fn renderer(&self) -> FixtureRenderer {
+        FixtureRenderer
+    }
+}
+
+/// Events published to a [`MockPublisher`] channel, blocking until the next
+/// one arrives
+pub struct MockSubscription {
+    publisher: MockPublisher,
+    channel: String,
+    pos: usize,
+    filter: Option<XPathFilter>,
+}
+
+impl Iterator for MockSubscription {
+    type Item = Result<String, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (log, published) = &*self.publisher.log;
+        let mut log = log.lock().unwrap();
+
+        loop {
+            let next = log
+                .channels
+                .get(&self.channel)
+                .and_then(|events| events.get(self.pos));
+
+            match next {
+                Some(xml) => {
+                    self.pos += 1;
+                    let matched = match &self.filter {
+                        None => Ok(true),
+                        Some(filter) => filter.matches(xml),
+                    };
+                    match matched {
+                        Ok(true) => return Some(Ok(xml.clone())),
+                        Ok(false) => {}
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                None if log.closed => return None,
+                None => log = published.wait(log).unwrap(),
+            }
+        }
+    }
+}
+
+impl EventSource for MockSubscription {
+    type Event = String;
+}
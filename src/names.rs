@@ -0,0 +1,258 @@
+use roxmltree::{Document, Node};
+
+use crate::binxml::escape;
+use crate::errors::{WinEvtError, ERROR_EVT_MALFORMED_XML_TEXT};
+use crate::event::{Event, RenderingInfo, System};
+use crate::metadata_cache::{MetadataCache, DEFAULT_LOCALE};
+use crate::output::EventWriter;
+use crate::pub_metadata::PubMetadata;
+use crate::winmeta;
+
+/// Display names for the level, task, opcode and keywords of a publisher's
+/// events.
+///
+/// Each comes from the text of the message the metadata gives it, when
+/// [`messages`] can look that up, or else its name; standard `win:` names,
+/// and values the metadata doesn't define, get the text Windows displays
+/// for them.
+///
+/// [`messages`]: EventNames::messages
+pub struct EventNames<'a> {
+    meta: &'a PubMetadata,
+    messages: Option<Box<dyn Fn(u32) -> Option<String> + 'a>>,
+}
+
+impl<'a> EventNames<'a> {
+    pub fn new(meta: &'a PubMetadata) -> Self {
+        EventNames {
+            meta,
+            messages: None,
+        }
+    }
+
+    /// Looks up message text by ID, e.g. in the publisher's [`MessageTable`]
+    ///
+    /// [`MessageTable`]: crate::pe::MessageTable
+    pub fn messages<F: Fn(u32) -> Option<String> + 'a>(mut self, messages: F) -> Self {
+        self.messages = Some(Box::new(messages));
+        self
+    }
+
+    pub fn level(&self, level: u8) -> Option<String> {
+        match self.meta.levels.iter().find(|l| l.id == Some(level.into())) {
+            Some(l) => self.display(l.name.as_deref(), l.message_id, winmeta::LEVELS),
+            None => winmeta::display(winmeta::LEVELS, level).map(str::to_string),
+        }
+    }
+
+    /// Task zero is no task at all
+    pub fn task(&self, task: u16) -> Option<String> {
+        let t = self
+            .meta
+            .tasks
+            .iter()
+            .find(|t| t.value == Some(task.into()))?;
+        self.display::<u16>(t.name.as_deref(), t.message_id, &[])
+    }
+
+    /// Opcodes can belong to a task, or to the whole publisher
+    pub fn opcode(&self, task: u16, opcode: u8) -> Option<String> {
+        let defined = |task| {
+            self.meta
+                .opcodes
+                .iter()
+                .find(|o| o.task_id == Some(task) && o.opcode_value == Some(opcode.into()))
+        };
+        match defined(task).or_else(|| defined(0)) {
+            Some(o) => self.display(o.name.as_deref(), o.message_id, winmeta::OPCODES),
+            None => winmeta::display(winmeta::OPCODES, opcode).map(str::to_string),
+        }
+    }
+
+    /// The name of every keyword whose bits are all set in the mask, the
+    /// publisher's own first. Bits no keyword covers, like those marking
+    /// the channel, are left out.
+    pub fn keywords(&self, mask: u64) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut named = 0;
+
+        for k in &self.meta.keywords {
+            let bits = match k.mask {
+                Some(bits) if bits != 0 && mask & bits == bits => bits,
+                _ => continue,
+            };
+            if let Some(name) = self.display(k.name.as_deref(), k.message_id, winmeta::KEYWORDS) {
+                names.push(name);
+                named |= bits;
+            }
+        }
+
+        for &(_, bits, display) in winmeta::KEYWORDS {
+            if bits != 0 && mask & bits == bits && named & bits != bits {
+                names.push(display.to_string());
+            }
+        }
+
+        names
+    }
+
+    /// The names of an event's fields, for those it has
+    pub fn rendering_info(&self, system: &System) -> RenderingInfo {
+        RenderingInfo {
+            level: system.level.and_then(|l| self.level(l)),
+            task: system.task.and_then(|t| self.task(t)),
+            opcode: system
+                .opcode
+                .and_then(|o| self.opcode(system.task.unwrap_or_default(), o)),
+            keywords: system
+                .keywords
+                .map(|k| self.keywords(k))
+                .unwrap_or_default(),
+            ..RenderingInfo::default()
+        }
+    }
+
+    /// Adds a `<RenderingInfo>` with the names to the end of a rendered
+    /// event, as the event log does when formatting its message. Events that
+    /// already have one, or have nothing to name, are left as they are.
+    pub fn enrich(&self, xml: &str) -> Result<String, WinEvtError> {
+        let doc = Document::parse(xml)
+            .map_err(|e| WinEvtError::parse(ERROR_EVT_MALFORMED_XML_TEXT, e))?;
+        self.enrich_parsed(xml, &doc, &Event::from_doc(&doc)?)
+    }
+
+    /// [`enrich`] for an event already parsed into `doc` and `event`
+    ///
+    /// [`enrich`]: EventNames::enrich
+    pub fn enrich_parsed(
+        &self,
+        xml: &str,
+        doc: &Document,
+        event: &Event,
+    ) -> Result<String, WinEvtError> {
+        let root = doc.root_element();
+        if event.rendering_info.is_some() {
+            return Ok(xml.to_string());
+        }
+
+        let info = self.rendering_info(&event.system);
+        if info == RenderingInfo::default() {
+            return Ok(xml.to_string());
+        }
+        let end = closing_tag(root, xml)?;
+
+        Ok(format!(
+            "{}{}{}",
+            &xml[..end],
+            rendering_info_xml(&info),
+            &xml[end..]
+        ))
+    }
+
+    fn display<T>(
+        &self,
+        name: Option<&str>,
+        message_id: Option<u32>,
+        standard: &[(&str, T, &str)],
+    ) -> Option<String> {
+        let message = message_id
+            .zip(self.messages.as_ref())
+            .and_then(|(id, messages)| messages(id));
+        if let Some(text) = message {
+            return Some(text.trim_end_matches(['\r', '\n']).to_string());
+        }
+
+        let name = name?;
+        Some(
+            winmeta::display_name(standard, name)
+                .unwrap_or(name)
+                .to_string(),
+        )
+    }
+}
+
+/// Where the root element's closing tag starts
+fn closing_tag(root: Node, xml: &str) -> Result<usize, WinEvtError> {
+    xml[..root.range().end]
+        .rfind("</")
+        .filter(|&at| at > root.range().start)
+        .ok_or_else(|| WinEvtError::new(ERROR_EVT_MALFORMED_XML_TEXT, "the event has no content"))
+}
+
+fn rendering_info_xml(info: &RenderingInfo) -> String {
+    let mut xml = String::from("<RenderingInfo>");
+    let mut element = |tag: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            xml.push_str(&format!("<{}>", tag));
+            escape(&mut xml, value, false);
+            xml.push_str(&format!("</{}>", tag));
+        }
+    };
+    element("Level", &info.level);
+    element("Task", &info.task);
+    element("Opcode", &info.opcode);
+
+    if !info.keywords.is_empty() {
+        xml.push_str("<Keywords>");
+        for k in &info.keywords {
+            xml.push_str("<Keyword>");
+            escape(&mut xml, k, false);
+            xml.push_str("</Keyword>");
+        }
+        xml.push_str("</Keywords>");
+    }
+    xml.push_str("</RenderingInfo>");
+    xml
+}
+
+/// Enriches events with [`EventNames`] before passing them on, using the
+/// metadata cached for each event's publisher; events from publishers the
+/// cache doesn't have still get the standard names.
+pub struct NamingWriter<W: EventWriter> {
+    inner: W,
+    cache: MetadataCache,
+    locale: String,
+}
+
+impl<W: EventWriter> NamingWriter<W> {
+    pub fn new(inner: W, cache: MetadataCache) -> Self {
+        NamingWriter {
+            inner,
+            cache,
+            locale: DEFAULT_LOCALE.to_string(),
+        }
+    }
+
+    /// Which locale's metadata to prefer
+    pub fn locale<S: Into<String>>(mut self, locale: S) -> Self {
+        self.locale = locale.into();
+        self
+    }
+}
+
+impl<W: EventWriter> EventWriter for NamingWriter<W> {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        let doc = Document::parse(xml)
+            .map_err(|e| WinEvtError::parse(ERROR_EVT_MALFORMED_XML_TEXT, e))?;
+        let event = Event::from_doc(&doc)?;
+        let none = PubMetadata::default();
+        let meta = event
+            .system
+            .provider
+            .name
+            .as_deref()
+            .and_then(|name| self.cache.find(name, None, &self.locale))
+            .map_or(&none, |(_, meta)| meta);
+
+        let xml = EventNames::new(meta).enrich_parsed(xml, &doc, &event)?;
+        self.inner.write_event(&xml)
+    }
+
+    fn flush(&mut self) -> Result<(), WinEvtError> {
+        self.inner.flush()
+    }
+
+    fn finish(&mut self) -> Result<(), WinEvtError> {
+        self.inner.finish()
+    }
+}
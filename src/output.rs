@@ -0,0 +1,300 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use crate::errors::WinEvtError;
+use crate::event::{Element, Event};
+
+/// How rendered events are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One XML event per line, exactly as rendered
+    Xml,
+    /// One JSON object per line
+    NdJson,
+    /// A single JSON array of every event
+    Json,
+}
+
+impl Format {
+    /// File extension for output in this format, before any compression
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Xml => "xml",
+            Format::NdJson => "ndjson",
+            Format::Json => "json",
+        }
+    }
+
+    pub fn writer<'a, W: Write + 'a>(self, w: W) -> Box<dyn EventWriter + 'a> {
+        match self {
+            Format::Xml => Box::new(XmlWriter::new(w)),
+            Format::NdJson => Box::new(JsonWriter::ndjson(w)),
+            Format::Json => Box::new(JsonWriter::array(w)),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "xml" => Ok(Format::Xml),
+            "ndjson" | "jsonl" => Ok(Format::NdJson),
+            "json" => Ok(Format::Json),
+            other => Err(format!(
+                "unknown format {}, expected xml, ndjson or json",
+                other
+            )),
+        }
+    }
+}
+
+/// Writes rendered events to some output
+pub trait EventWriter {
+    /// Writes one event. An event the writer can't make sense of fails with
+    /// [`WinEvtError::Parse`] and nothing written, so the caller can skip it
+    /// and carry on; any other error means the output itself has failed.
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError>;
+
+    /// Pushes what's been written so far through to the output
+    fn flush(&mut self) -> Result<(), WinEvtError> {
+        Ok(())
+    }
+
+    /// Writes anything needed to close off the output; call once at the end
+    fn finish(&mut self) -> Result<(), WinEvtError> {
+        Ok(())
+    }
+}
+
+impl<W: EventWriter + ?Sized> EventWriter for Box<W> {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        (**self).write_event(xml)
+    }
+
+    fn flush(&mut self) -> Result<(), WinEvtError> {
+        (**self).flush()
+    }
+
+    fn finish(&mut self) -> Result<(), WinEvtError> {
+        (**self).finish()
+    }
+}
+
+pub struct XmlWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> XmlWriter<W> {
+    pub fn new(out: W) -> Self {
+        XmlWriter { out }
+    }
+}
+
+impl<W: Write> EventWriter for XmlWriter<W> {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        writeln!(self.out, "{}", xml).map_err(WinEvtError::output)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), WinEvtError> {
+        self.out.flush().map_err(WinEvtError::output)
+    }
+
+    fn finish(&mut self) -> Result<(), WinEvtError> {
+        self.out.flush().map_err(WinEvtError::output)?;
+        Ok(())
+    }
+}
+
+/// Converts each rendered event into a [`to_json`] object
+pub struct JsonWriter<W: Write> {
+    out: W,
+    array: bool,
+    written: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    /// Newline delimited JSON, one event per line
+    pub fn ndjson(out: W) -> Self {
+        JsonWriter {
+            out,
+            array: false,
+            written: 0,
+        }
+    }
+
+    /// A JSON array, which isn't valid until [`finish`] is called
+    ///
+    /// [`finish`]: EventWriter::finish
+    pub fn array(out: W) -> Self {
+        JsonWriter {
+            out,
+            array: true,
+            written: 0,
+        }
+    }
+}
+
+impl<W: Write> EventWriter for JsonWriter<W> {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        let event = Event::from_xml(xml).map_err(|e| WinEvtError::Parse {
+            code: e.code(),
+            msg: format!("can't convert event to JSON: {}", e),
+            source: Some(Box::new(e)),
+        })?;
+        let json = to_json(&event);
+
+        if self.array {
+            self.out
+                .write_all(if self.written == 0 { b"[\n" } else { b",\n" })
+                .map_err(WinEvtError::output)?;
+        }
+        serde_json::to_writer(&mut self.out, &json).map_err(|e| WinEvtError::output(e.into()))?;
+        if !self.array {
+            self.out.write_all(b"\n").map_err(WinEvtError::output)?;
+        }
+
+        self.written += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), WinEvtError> {
+        self.out.flush().map_err(WinEvtError::output)
+    }
+
+    fn finish(&mut self) -> Result<(), WinEvtError> {
+        if self.array {
+            self.out
+                .write_all(if self.written == 0 { b"[]\n" } else { b"\n]\n" })
+                .map_err(WinEvtError::output)?;
+        }
+        self.out.flush().map_err(WinEvtError::output)?;
+        Ok(())
+    }
+}
+
+/// Flattens an event into a JSON object.
+///
+/// The `System` fields come first, in the order they're rendered, and are
+/// left out when the event doesn't have them. `Keywords` is a hex string as it
+/// doesn't fit in a JSON number. Any `RenderingInfo` follows as `Message`,
+/// `LevelName`, `TaskName`, `OpcodeName` and `KeywordNames`. `EventData` maps
+/// each `Data` name to its value, with unnamed values keyed `param1`,
+/// `param2`, ... and a repeated name collecting its values into an array.
+/// Any `Binary` data is keyed `#Binary`, as a `Data` value may well be named
+/// `Binary`. `UserData` is converted as a whole.
+pub fn to_json(event: &Event) -> Value {
+    let sys = &event.system;
+    let mut obj = Map::new();
+
+    fn put<T: Into<Value>>(obj: &mut Map<String, Value>, key: &str, val: Option<T>) {
+        if let Some(val) = val {
+            obj.insert(key.to_string(), val.into());
+        }
+    }
+
+    put(&mut obj, "ProviderName", sys.provider.name.clone());
+    put(&mut obj, "ProviderGuid", sys.provider.guid.clone());
+    put(
+        &mut obj,
+        "EventSourceName",
+        sys.provider.event_source_name.clone(),
+    );
+    put(&mut obj, "EventID", sys.event_id);
+    put(&mut obj, "Qualifiers", sys.qualifiers);
+    put(&mut obj, "Version", sys.version);
+    put(&mut obj, "Level", sys.level);
+    put(&mut obj, "Task", sys.task);
+    put(&mut obj, "Opcode", sys.opcode);
+    put(
+        &mut obj,
+        "Keywords",
+        sys.keywords.map(|k| format!("0x{:x}", k)),
+    );
+    put(&mut obj, "TimeCreated", sys.time_created.clone());
+    put(&mut obj, "EventRecordID", sys.event_record_id);
+    put(&mut obj, "ActivityID", sys.correlation.activity_id.clone());
+    put(
+        &mut obj,
+        "RelatedActivityID",
+        sys.correlation.related_activity_id.clone(),
+    );
+    put(&mut obj, "ProcessID", sys.execution.process_id);
+    put(&mut obj, "ThreadID", sys.execution.thread_id);
+    put(&mut obj, "Channel", sys.channel.clone());
+    put(&mut obj, "Computer", sys.computer.clone());
+    put(&mut obj, "UserID", sys.user_id.clone());
+
+    if let Some(ri) = &event.rendering_info {
+        put(&mut obj, "Message", ri.message.clone());
+        put(&mut obj, "LevelName", ri.level.clone());
+        put(&mut obj, "TaskName", ri.task.clone());
+        put(&mut obj, "OpcodeName", ri.opcode.clone());
+        if !ri.keywords.is_empty() {
+            obj.insert("KeywordNames".to_string(), ri.keywords.clone().into());
+        }
+    }
+
+    if !event.event_data.is_empty() || event.binary.is_some() {
+        let mut data = Map::new();
+
+        for (i, d) in event.event_data.iter().enumerate() {
+            let key = match &d.name {
+                Some(name) => name.clone(),
+                None => format!("param{}", i + 1),
+            };
+            insert_multi(&mut data, key, Value::String(d.value.clone()));
+        }
+        put(&mut data, "#Binary", event.binary.clone());
+
+        obj.insert("EventData".to_string(), Value::Object(data));
+    }
+
+    if !event.user_data.is_empty() {
+        let mut data = Map::new();
+        for el in &event.user_data {
+            insert_multi(&mut data, el.name.clone(), element_json(el));
+        }
+        obj.insert("UserData".to_string(), Value::Object(data));
+    }
+
+    Value::Object(obj)
+}
+
+fn insert_multi(obj: &mut Map<String, Value>, key: String, val: Value) {
+    match obj.get_mut(&key) {
+        None => {
+            obj.insert(key, val);
+        }
+        Some(Value::Array(vals)) => vals.push(val),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, val]);
+        }
+    }
+}
+
+/// Leaf elements become their text, anything else an object of attributes
+/// (prefixed `@`), child elements and any `#text`
+fn element_json(el: &Element) -> Value {
+    if el.attrs.is_empty() && el.children.is_empty() {
+        return Value::String(el.text.clone());
+    }
+
+    let mut obj = Map::new();
+    for (name, val) in &el.attrs {
+        obj.insert(format!("@{}", name), Value::String(val.clone()));
+    }
+    for child in &el.children {
+        insert_multi(&mut obj, child.name.clone(), element_json(child));
+    }
+    if !el.text.is_empty() {
+        obj.insert("#text".to_string(), Value::String(el.text.clone()));
+    }
+
+    Value::Object(obj)
+}
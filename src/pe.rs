@@ -0,0 +1,362 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{WinEvtError, ERROR_BAD_EXE_FORMAT, ERROR_RESOURCE_TYPE_NOT_FOUND};
+
+const DOS_MAGIC: &[u8] = b"MZ";
+const PE_MAGIC: &[u8] = b"PE\0\0";
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+const RESOURCE_DIRECTORY: usize = 2;
+/// Directories nest type, then name, then language
+const RESOURCE_DEPTH: usize = 3;
+const RT_MESSAGETABLE: u32 = 11;
+/// Set on a directory entry's name when it's a string, and on its offset
+/// when it points at another directory
+const RESOURCE_HIGH_BIT: u32 = 0x8000_0000;
+
+const LANG_NEUTRAL: u16 = 0;
+const LANG_EN_US: u16 = 0x409;
+
+/// `MESSAGE_RESOURCE_ENTRY` flag for UTF-16 text; otherwise it's ANSI
+const MESSAGE_UNICODE: u16 = 0x1;
+
+/// A section's place in memory and in the file
+struct Section {
+    rva: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// A resource type; the predefined ones have IDs, others like
+/// `WEVT_TEMPLATE` are named
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ResourceType<'n> {
+    Id(u32),
+    Name(&'n str),
+}
+
+/// The parts of a PE image needed to find its resources
+struct PeImage<'a> {
+    data: &'a [u8],
+    sections: Vec<Section>,
+    /// Address of the resource directory, if there is one
+    resources: Option<u32>,
+}
+
+impl<'a> PeImage<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, WinEvtError> {
+        if !data.starts_with(DOS_MAGIC) {
+            return Err(bad_image("not a PE file"));
+        }
+
+        let pe = u32_at(data, 0x3c)? as usize;
+        if data.get(pe..pe + 4) != Some(PE_MAGIC) {
+            return Err(bad_image("missing PE signature"));
+        }
+
+        let coff = pe + 4;
+        let section_count = u16_at(data, coff + 2)? as usize;
+        let optional_size = u16_at(data, coff + 16)? as usize;
+        let optional = coff + 20;
+
+        let dirs = match u16_at(data, optional)? {
+            PE32_MAGIC => optional + 96,
+            PE32_PLUS_MAGIC => optional + 112,
+            magic => return Err(bad_image(format!("unknown optional header {:#x}", magic))),
+        };
+        let dir_count = u32_at(data, dirs - 4)? as usize;
+        let resources = if dir_count > RESOURCE_DIRECTORY {
+            Some(u32_at(data, dirs + RESOURCE_DIRECTORY * 8)?).filter(|&rva| rva != 0)
+        } else {
+            None
+        };
+
+        let sections = (0..section_count)
+            .map(|i| {
+                let at = optional + optional_size + i * 40;
+                Ok(Section {
+                    rva: u32_at(data, at + 12)?,
+                    size: u32_at(data, at + 8)?.max(u32_at(data, at + 16)?),
+                    offset: u32_at(data, at + 20)?,
+                })
+            })
+            .collect::<Result<_, WinEvtError>>()?;
+
+        Ok(PeImage {
+            data,
+            sections,
+            resources,
+        })
+    }
+
+    /// Where in the file an address of the loaded image is
+    fn offset(&self, rva: u32) -> Result<usize, WinEvtError> {
+        let section = self
+            .sections
+            .iter()
+            .find(|s| rva >= s.rva && rva - s.rva < s.size)
+            .ok_or_else(|| bad_image(format!("address {:#x} isn't in any section", rva)))?;
+        (rva - section.rva)
+            .checked_add(section.offset)
+            .map(|offset| offset as usize)
+            .ok_or_else(|| bad_image(format!("address {:#x} is past the end of the file", rva)))
+    }
+
+    /// The data of every resource of a type, with its language
+    fn resources_of_type(&self, kind: ResourceType) -> Result<Vec<(u16, &'a [u8])>, WinEvtError> {
+        let rva = match self.resources {
+            Some(rva) => rva,
+            None => return Ok(Vec::new()),
+        };
+        let base = self.offset(rva)?;
+
+        let mut found = Vec::new();
+        let mut dirs = vec![0];
+        self.walk(
+            base,
+            &mut dirs,
+            kind,
+            [0; RESOURCE_DEPTH],
+            &mut |path, entry| {
+                let data_rva = u32_at(self.data, base + entry)?;
+                let size = u32_at(self.data, base + entry + 4)? as usize;
+                let start = self.offset(data_rva)?;
+                let data = self
+                    .data
+                    .get(start..start + size)
+                    .ok_or_else(|| bad_image("resource runs past the end of the file"))?;
+                found.push((path[2] as u16, data));
+                Ok(())
+            },
+        )?;
+        Ok(found)
+    }
+
+    /// Whether a directory entry's name is the type asked for
+    fn is_type(&self, base: usize, name: u32, kind: ResourceType) -> Result<bool, WinEvtError> {
+        match kind {
+            ResourceType::Id(id) => Ok(name == id),
+            ResourceType::Name(_) if name & RESOURCE_HIGH_BIT == 0 => Ok(false),
+            ResourceType::Name(wanted) => {
+                let at = base + (name & !RESOURCE_HIGH_BIT) as usize;
+                let len = u16_at(self.data, at)? as usize;
+                let units = (0..len)
+                    .map(|i| u16_at(self.data, at + 2 + i * 2))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(String::from_utf16_lossy(&units).eq_ignore_ascii_case(wanted))
+            }
+        }
+    }
+
+    /// Calls `leaf` with the type, name and language of each resource of the
+    /// type and the offset of its data entry. Each is an ID, or for named
+    /// entries the offset of the name with [`RESOURCE_HIGH_BIT`] set.
+    ///
+    /// `dirs` holds the offsets of the directories down to the one being
+    /// walked, the root first.
+    fn walk(
+        &self,
+        base: usize,
+        dirs: &mut Vec<usize>,
+        kind: ResourceType,
+        mut path: [u32; RESOURCE_DEPTH],
+        leaf: &mut dyn FnMut([u32; RESOURCE_DEPTH], usize) -> Result<(), WinEvtError>,
+    ) -> Result<(), WinEvtError> {
+        let depth = dirs.len() - 1;
+        let at = base + dirs[depth];
+        let count = u16_at(self.data, at + 12)? as usize + u16_at(self.data, at + 14)? as usize;
+
+        for i in 0..count {
+            let entry = at + 16 + i * 8;
+            let name = u32_at(self.data, entry)?;
+            let target = u32_at(self.data, entry + 4)?;
+
+            // Other types are never descended into
+            if depth == 0 && !self.is_type(base, name, kind)? {
+                continue;
+            }
+            path[depth] = name;
+            let next = (target & !RESOURCE_HIGH_BIT) as usize;
+
+            match (target & RESOURCE_HIGH_BIT != 0, depth + 1 < RESOURCE_DEPTH) {
+                // A directory already on the way down here, which loops
+                (true, true) if dirs.contains(&next) => {}
+                (true, true) => {
+                    dirs.push(next);
+                    self.walk(base, dirs, kind, path, leaf)?;
+                    dirs.pop();
+                }
+                (false, false) => leaf(path, next)?,
+                // Nested the wrong way; skipped rather than failing the resources
+                // wanted over some other type's
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `RT_MESSAGETABLE` resources of a message file, as the DLLs and EXEs
+/// named by a publisher's message, parameter and resource file paths carry.
+///
+/// A file can hold a table per language; each is kept under its `LANGID`,
+/// e.g. `0x409` for en-US. Many system files keep their messages in `.mui`
+/// satellites instead, under a directory named after the locale next to
+/// them, which [`open_localized`] looks in.
+///
+/// [`open_localized`]: MessageTable::open_localized
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageTable {
+    languages: BTreeMap<u16, BTreeMap<u32, String>>,
+}
+
+impl MessageTable {
+    /// Reads the message tables of a PE image; one without any is an error
+    pub fn from_pe(data: &[u8]) -> Result<Self, WinEvtError> {
+        let image = PeImage::parse(data)?;
+        let mut table = MessageTable::default();
+
+        for (lang, data) in image.resources_of_type(ResourceType::Id(RT_MESSAGETABLE))? {
+            table
+                .languages
+                .entry(lang)
+                .or_default()
+                .extend(parse_messages(data)?);
+        }
+
+        if table.languages.is_empty() {
+            return Err(WinEvtError::new(
+                ERROR_RESOURCE_TYPE_NOT_FOUND,
+                "no message table in the image",
+            ));
+        }
+        Ok(table)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WinEvtError> {
+        MessageTable::from_pe(&fs::read(path)?)
+    }
+
+    /// Reads a message file along with its `.mui` satellite for the locale,
+    /// e.g. `en-US\wevtapi.dll.mui` for `wevtapi.dll`. Either may be
+    /// missing, but not both; messages in the satellite win.
+    pub fn open_localized<P: AsRef<Path>>(path: P, locale: &str) -> Result<Self, WinEvtError> {
+        let path = path.as_ref();
+        let main = MessageTable::open(path);
+        let mui = match mui_path(path, locale) {
+            Some(mui) if mui.exists() => Some(MessageTable::open(mui)),
+            _ => None,
+        };
+
+        match (main, mui) {
+            (Ok(mut main), Some(Ok(mui))) => {
+                main.merge(mui);
+                Ok(main)
+            }
+            (Ok(table), None) | (_, Some(Ok(table))) => Ok(table),
+            (Err(e), _) | (Ok(_), Some(Err(e))) => Err(e),
+        }
+    }
+
+    /// Adds another file's messages, replacing any with the same language and ID
+    pub fn merge(&mut self, other: MessageTable) {
+        for (lang, messages) in other.languages {
+            self.languages.entry(lang).or_default().extend(messages);
+        }
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = u16> + '_ {
+        self.languages.keys().copied()
+    }
+
+    /// The message in the language asked for, falling back to the neutral
+    /// language, then en-US, then whichever language has it
+    pub fn get(&self, id: u32, lang: u16) -> Option<&str> {
+        [lang, LANG_NEUTRAL, LANG_EN_US]
+            .iter()
+            .filter_map(|l| self.languages.get(l))
+            .chain(self.languages.values())
+            .find_map(|messages| messages.get(&id))
+            .map(String::as_str)
+    }
+
+    /// Every message of one language
+    pub fn messages(&self, lang: u16) -> impl Iterator<Item = (u32, &str)> {
+        self.languages
+            .get(&lang)
+            .into_iter()
+            .flatten()
+            .map(|(&id, text)| (id, text.as_str()))
+    }
+}
+
+/// The data of every resource of a type in a PE image, with its language
+pub(crate) fn resources<'a>(
+    data: &'a [u8],
+    kind: ResourceType,
+) -> Result<Vec<(u16, &'a [u8])>, WinEvtError> {
+    PeImage::parse(data)?.resources_of_type(kind)
+}
+
+/// Where Windows looks for a file's satellite for a locale
+pub fn mui_path(path: &Path, locale: &str) -> Option<PathBuf> {
+    let mut name = path.file_name()?.to_owned();
+    name.push(".mui");
+    Some(path.with_file_name(locale).join(name))
+}
+
+/// Decodes a `MESSAGE_RESOURCE_DATA` blob. Messages keep their trailing
+/// line break, as `FormatMessage` gives them, but not their padding.
+fn parse_messages(data: &[u8]) -> Result<BTreeMap<u32, String>, WinEvtError> {
+    let mut messages = BTreeMap::new();
+
+    for block in 0..u32_at(data, 0)? as usize {
+        let at = 4 + block * 12;
+        let (low, high) = (u32_at(data, at)?, u32_at(data, at + 4)?);
+        let mut entry = u32_at(data, at + 8)? as usize;
+
+        for id in low..=high {
+            let len = u16_at(data, entry)? as usize;
+            let flags = u16_at(data, entry + 2)?;
+            if len < 4 {
+                return Err(bad_image(format!("message {:#x} is too short", id)));
+            }
+            let text = data
+                .get(entry + 4..entry + len)
+                .ok_or_else(|| bad_image(format!("message {:#x} runs past the table", id)))?;
+
+            let text = if flags & MESSAGE_UNICODE != 0 {
+                let units: Vec<u16> = text
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            } else {
+                text.iter().map(|&b| b as char).collect()
+            };
+            messages.insert(id, text.trim_end_matches('\0').to_string());
+            entry += len;
+        }
+    }
+
+    Ok(messages)
+}
+
+fn u16_at(data: &[u8], at: usize) -> Result<u16, WinEvtError> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| bad_image("truncated image"))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Result<u32, WinEvtError> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| bad_image("truncated image"))
+}
+
+fn bad_image<S: Into<String>>(msg: S) -> WinEvtError {
+    WinEvtError::new(ERROR_BAD_EXE_FORMAT, msg)
+}
@@ -1,5 +1,116 @@
-use shared::guiddef::GUID;
+use std::convert::TryFrom;
 
+use serde::{Deserialize, Serialize};
+
+use crate::errors::WinEvtError;
+
+/// Message IDs are this when a publisher doesn't give one
+const NO_MESSAGE_ID: u32 = 0xFFFF_FFFF;
+
+/// The channel reference flag marking a channel defined by another publisher
+const CHANNEL_IMPORTED: u32 = 0x1;
+
+/// The publisher metadata properties, numbered as
+/// `EVT_PUBLISHER_METADATA_PROPERTY_ID` numbers them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum PubMetaProp {
+    PublisherGuid = 0,
+    ResourceFilePath,
+    ParameterFilePath,
+    MessageFilePath,
+    HelpLink,
+    PublisherMessageId,
+    ChannelReferences,
+    ChannelReferencePath,
+    ChannelReferenceIndex,
+    ChannelReferenceId,
+    ChannelReferenceFlags,
+    ChannelReferenceMessageId,
+    Levels,
+    LevelName,
+    LevelValue,
+    LevelMessageId,
+    Tasks,
+    TaskName,
+    TaskEventGuid,
+    TaskValue,
+    TaskMessageId,
+    Opcodes,
+    OpcodeName,
+    OpcodeValue,
+    OpcodeMessageId,
+    Keywords,
+    KeywordName,
+    KeywordValue,
+    KeywordMessageId,
+}
+
+/// The value of a publisher metadata property
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropValue {
+    /// The publisher doesn't have the property
+    Null,
+    String(String),
+    /// A GUID formatted the way the event log does, `{XXXXXXXX-...}`
+    Guid(String),
+    UInt32(u32),
+    UInt64(u64),
+}
+
+impl PropValue {
+    pub fn into_string(self) -> Option<String> {
+        match self {
+            PropValue::String(s) | PropValue::Guid(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            PropValue::UInt32(n) => Some(n),
+            PropValue::UInt64(n) => u32::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            PropValue::UInt32(n) => Some(n.into()),
+            PropValue::UInt64(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn message_id(&self) -> Option<u32> {
+        self.as_u32().filter(|&id| id != NO_MESSAGE_ID)
+    }
+}
+
+/// Somewhere a publisher's metadata properties are read from; the local
+/// event log through [`PubMetadataFetcher`], or a fake in tests.
+///
+/// Channels, levels, tasks, opcodes and keywords are arrays whose elements
+/// have properties of their own, e.g. the [`PubMetaProp::LevelName`] of each
+/// of the [`PubMetaProp::Levels`].
+///
+/// [`PubMetadataFetcher`]: crate::pub_metadata_fetcher::PubMetadataFetcher
+pub trait PubMetadataProps {
+    fn prop(&mut self, prop: PubMetaProp) -> Result<PropValue, WinEvtError>;
+
+    /// The number of elements in an array property; zero if it's missing
+    fn array_len(&mut self, array: PubMetaProp) -> Result<u32, WinEvtError>;
+
+    fn array_prop(
+        &mut self,
+        array: PubMetaProp,
+        index: u32,
+        prop: PubMetaProp,
+    ) -> Result<PropValue, WinEvtError>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Channel {
     pub name: Option<String>,
     pub index: Option<u32>,
@@ -8,12 +119,16 @@ pub struct Channel {
     pub message_id: Option<u32>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Level {
     pub name: Option<String>,
     pub id: Option<u32>,
     pub message_id: Option<u32>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Task {
     pub name: Option<String>,
     pub guid: Option<String>,
@@ -21,6 +136,8 @@ pub struct Task {
     pub message_id: Option<u32>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct OpCode {
     pub name: Option<String>,
     pub opcode_value: Option<u16>,
@@ -28,12 +145,32 @@ pub struct OpCode {
     pub message_id: Option<u32>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Keyword {
     pub name: Option<String>,
     pub mask: Option<u64>,
     pub message_id: Option<u32>,
 }
 
+/// An event a publisher defines, with the values its `System` section gets
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventMetadata {
+    pub id: u32,
+    pub version: u8,
+    pub channel: u8,
+    pub level: u8,
+    pub opcode: u8,
+    pub task: u16,
+    pub keywords: u64,
+    pub message_id: Option<u32>,
+    /// The `<template>` describing its `EventData`, as XML
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PubMetadata {
     pub guid: Option<String>,
 
@@ -50,4 +187,81 @@ pub struct PubMetadata {
     pub tasks: Vec<Task>,
     pub opcodes: Vec<OpCode>,
     pub keywords: Vec<Keyword>,
-}
\ No newline at end of file
+
+    /// The events the publisher defines; left empty by [`fetch`]
+    ///
+    /// [`fetch`]: PubMetadata::fetch
+    pub events: Vec<EventMetadata>,
+}
+
+impl PubMetadata {
+    /// Reads every property, including each element of the arrays
+    pub fn fetch<P: PubMetadataProps + ?Sized>(props: &mut P) -> Result<Self, WinEvtError> {
+        use PubMetaProp::*;
+
+        let mut meta = PubMetadata {
+            guid: props.prop(PublisherGuid)?.into_string(),
+            resource_file_path: props.prop(ResourceFilePath)?.into_string(),
+            parameter_file_path: props.prop(ParameterFilePath)?.into_string(),
+            message_file_path: props.prop(MessageFilePath)?.into_string(),
+            help_link: props.prop(HelpLink)?.into_string(),
+            message_id: props.prop(PublisherMessageId)?.message_id(),
+            ..PubMetadata::default()
+        };
+
+        for i in 0..props.array_len(ChannelReferences)? {
+            let mut get = |prop| props.array_prop(ChannelReferences, i, prop);
+            meta.channels.push(Channel {
+                name: get(ChannelReferencePath)?.into_string(),
+                index: get(ChannelReferenceIndex)?.as_u32(),
+                id: get(ChannelReferenceId)?.as_u32(),
+                imported: get(ChannelReferenceFlags)?
+                    .as_u32()
+                    .is_some_and(|f| f & CHANNEL_IMPORTED != 0),
+                message_id: get(ChannelReferenceMessageId)?.message_id(),
+            });
+        }
+
+        for i in 0..props.array_len(Levels)? {
+            let mut get = |prop| props.array_prop(Levels, i, prop);
+            meta.levels.push(Level {
+                name: get(LevelName)?.into_string(),
+                id: get(LevelValue)?.as_u32(),
+                message_id: get(LevelMessageId)?.message_id(),
+            });
+        }
+
+        for i in 0..props.array_len(Tasks)? {
+            let mut get = |prop| props.array_prop(Tasks, i, prop);
+            meta.tasks.push(Task {
+                name: get(TaskName)?.into_string(),
+                guid: get(TaskEventGuid)?.into_string(),
+                value: get(TaskValue)?.as_u32(),
+                message_id: get(TaskMessageId)?.message_id(),
+            });
+        }
+
+        for i in 0..props.array_len(Opcodes)? {
+            let mut get = |prop| props.array_prop(Opcodes, i, prop);
+            // The opcode is in the high word and the task it belongs to in the low
+            let value = get(OpcodeValue)?.as_u32();
+            meta.opcodes.push(OpCode {
+                name: get(OpcodeName)?.into_string(),
+                opcode_value: value.map(|v| (v >> 16) as u16),
+                task_id: value.map(|v| v as u16),
+                message_id: get(OpcodeMessageId)?.message_id(),
+            });
+        }
+
+        for i in 0..props.array_len(Keywords)? {
+            let mut get = |prop| props.array_prop(Keywords, i, prop);
+            meta.keywords.push(Keyword {
+                name: get(KeywordName)?.into_string(),
+                mask: get(KeywordValue)?.as_u64(),
+                message_id: get(KeywordMessageId)?.message_id(),
+            });
+        }
+
+        Ok(meta)
+    }
+}
@@ -1,15 +1,19 @@
+use std::collections::HashMap;
 use std::ptr;
 
-use widestring::U16CString;
+use widestring::{U16CStr, U16CString};
 use winapi::um::winevt::{
-    EvtClose, EvtGetPublisherMetadataProperty, EvtOpenPublisherMetadata, EVT_HANDLE,
+    self, EvtClose, EvtGetObjectArrayProperty, EvtGetObjectArraySize,
+    EvtGetPublisherMetadataProperty, EvtOpenPublisherMetadata, EVT_HANDLE, EVT_VARIANT,
 };
 use winapi::um::winnt::{
     LANG_ENGLISH, LCID, MAKELANGID, MAKELCID, SORT_DEFAULT, SUBLANG_ENGLISH_US,
 };
 
+use crate::binxml;
 use crate::errors::WinError;
 use crate::errors::WinEvtError;
+use crate::pub_metadata::{PropValue, PubMetaProp, PubMetadata, PubMetadataProps};
 use crate::pub_metadata_fields::PubMetaField;
 use crate::utils;
 use crate::vwrapper::WevWrapper;
@@ -17,6 +21,9 @@ use crate::vwrapper::WevWrapper;
 pub struct PubMetadataFetcher {
     pub name: String,
     handle: EVT_HANDLE,
+    /// Array property handles opened so far, `None` if the publisher has none
+    arrays: HashMap<PubMetaProp, Option<EVT_HANDLE>>,
+    buf: WevWrapper,
 }
 
 impl PubMetadataFetcher {
@@ -33,7 +40,12 @@ impl PubMetadataFetcher {
             )
         })?;
 
-        Ok(PubMetadataFetcher { name, handle })
+        Ok(PubMetadataFetcher {
+            name,
+            handle,
+            arrays: HashMap::new(),
+            buf: WevWrapper::new().unwrap(),
+        })
     }
 
     pub fn for_publisher(name: String) -> Result<Self, WinEvtError> {
@@ -48,35 +60,131 @@ impl PubMetadataFetcher {
         field: &PubMetaField,
         varw: &mut WevWrapper,
     ) -> Result<(), WinEvtError> {
-        let mut buf_used = 0;
+        get_prop(self.handle, field.id, varw)
+    }
 
-        let (var, vsize) = varw.get_pointer();
+    /// Reads all of the publisher's metadata, channels, levels, tasks,
+    /// opcodes and keywords included
+    pub fn fetch_all(&mut self) -> Result<PubMetadata, WinEvtError> {
+        PubMetadata::fetch(self)
+    }
 
-        if let Err(e) = utils::check_okay_check(unsafe {
-            EvtGetPublisherMetadataProperty(
-                self.handle,
-                field.id,
-                0,
-                vsize as u32,
-                var,
-                &mut buf_used,
-            )
-        }) {
-            return match e {
-                WinError::InsufficientBuffer => {
-                    varw.resize(buf_used as usize).unwrap();
-                    return self.get_prop(field, varw);
-                }
-                err => Err(err.into_err()),
-            };
+    fn array(&mut self, array: PubMetaProp) -> Result<Option<EVT_HANDLE>, WinEvtError> {
+        if let Some(&handle) = self.arrays.get(&array) {
+            return Ok(handle);
         }
 
-        Ok(())
+        get_prop(self.handle, array as u32, &mut self.buf)?;
+        let handle = if self.buf.Type == winevt::EvtVarTypeEvtHandle {
+            Some(unsafe { *self.buf.u.EvtHandleVal() })
+        } else {
+            None
+        };
+
+        self.arrays.insert(array, handle);
+        Ok(handle)
+    }
+}
+
+impl PubMetadataProps for PubMetadataFetcher {
+    fn prop(&mut self, prop: PubMetaProp) -> Result<PropValue, WinEvtError> {
+        get_prop(self.handle, prop as u32, &mut self.buf)?;
+        Ok(prop_value(&self.buf))
+    }
+
+    fn array_len(&mut self, array: PubMetaProp) -> Result<u32, WinEvtError> {
+        let handle = match self.array(array)? {
+            Some(handle) => handle,
+            None => return Ok(0),
+        };
+
+        let mut size = 0;
+        utils::check_okay(unsafe { EvtGetObjectArraySize(handle, &mut size) })?;
+        Ok(size)
+    }
+
+    fn array_prop(
+        &mut self,
+        array: PubMetaProp,
+        index: u32,
+        prop: PubMetaProp,
+    ) -> Result<PropValue, WinEvtError> {
+        let handle = match self.array(array)? {
+            Some(handle) => handle,
+            None => return Ok(PropValue::Null),
+        };
+
+        get_array_prop(handle, prop as u32, index, &mut self.buf)?;
+        Ok(prop_value(&self.buf))
+    }
+}
+
+fn get_prop(handle: EVT_HANDLE, id: u32, varw: &mut WevWrapper) -> Result<(), WinEvtError> {
+    let mut buf_used = 0;
+
+    let (var, vsize) = varw.get_pointer();
+
+    if let Err(e) = utils::check_okay_check(unsafe {
+        EvtGetPublisherMetadataProperty(handle, id, 0, vsize as u32, var, &mut buf_used)
+    }) {
+        return match e {
+            WinError::InsufficientBuffer => {
+                varw.resize(buf_used as usize).unwrap();
+                get_prop(handle, id, varw)
+            }
+            err => Err(err.into_err()),
+        };
+    }
+
+    Ok(())
+}
+
+fn get_array_prop(
+    handle: EVT_HANDLE,
+    id: u32,
+    index: u32,
+    varw: &mut WevWrapper,
+) -> Result<(), WinEvtError> {
+    let mut buf_used = 0;
+
+    let (var, vsize) = varw.get_pointer();
+
+    if let Err(e) = utils::check_okay_check(unsafe {
+        EvtGetObjectArrayProperty(handle, id, index, 0, vsize as u32, var, &mut buf_used)
+    }) {
+        return match e {
+            WinError::InsufficientBuffer => {
+                varw.resize(buf_used as usize).unwrap();
+                get_array_prop(handle, id, index, varw)
+            }
+            err => Err(err.into_err()),
+        };
+    }
+
+    Ok(())
+}
+
+fn prop_value(var: &EVT_VARIANT) -> PropValue {
+    unsafe {
+        match var.Type {
+            winevt::EvtVarTypeString if !(*var.u.StringVal()).is_null() => {
+                PropValue::String(U16CStr::from_ptr_str(*var.u.StringVal()).to_string_lossy())
+            }
+            winevt::EvtVarTypeGuid if !(*var.u.GuidVal()).is_null() => {
+                PropValue::Guid(binxml::format_guid(&*(*var.u.GuidVal() as *const [u8; 16])))
+            }
+            winevt::EvtVarTypeUInt32 => PropValue::UInt32(*var.u.UInt32Val()),
+            winevt::EvtVarTypeUInt64 => PropValue::UInt64(*var.u.UInt64Val()),
+            _ => PropValue::Null,
+        }
     }
 }
 
 impl Drop for PubMetadataFetcher {
     fn drop(&mut self) {
+        for handle in self.arrays.values().flatten() {
+            unsafe { EvtClose(*handle) };
+        }
         crate::utils::check_okay(unsafe { EvtClose(self.handle) })
             .expect("Couldn't close the pub metadata handle")
     }
@@ -0,0 +1,229 @@
+use std::ops::RangeInclusive;
+use std::time::{Duration, SystemTime};
+
+use crate::errors::{WinEvtError, ERROR_EVT_INVALID_QUERY};
+use crate::filetime;
+use crate::query_list::{Query, QueryList};
+
+#[derive(Debug, Clone, PartialEq)]
+enum EventIds {
+    One(u32),
+    Range(u32, u32),
+}
+
+/// Builds the XPath filters `EvtQuery` accepts, or a `<QueryList>` wrapping
+/// one, out of typed conditions.
+///
+/// Repeating a condition of the same kind widens the match, so
+/// `.event_id(4624).event_id(4625)` matches either ID. Different kinds narrow
+/// it, as do `data` matches which must all hold. With no conditions the
+/// filter matches every event.
+///
+/// ```
+/// use win_events::query::QueryBuilder;
+///
+/// let xpath = QueryBuilder::new()
+///     .event_id(4624)
+///     .level(4)
+///     .data("TargetUserName", "bob")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(
+///     xpath,
+///     "*[System[EventID=4624 and Level=4] and EventData[Data[@Name='TargetUserName']='bob']]"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    event_ids: Vec<EventIds>,
+    levels: Vec<u8>,
+    providers: Vec<String>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+    within: Option<Duration>,
+    keywords: u64,
+    data: Vec<(String, String)>,
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        QueryBuilder::default()
+    }
+
+    pub fn event_id(mut self, id: u32) -> Self {
+        self.event_ids.push(EventIds::One(id));
+        self
+    }
+
+    pub fn event_ids<I: IntoIterator<Item = u32>>(mut self, ids: I) -> Self {
+        self.event_ids.extend(ids.into_iter().map(EventIds::One));
+        self
+    }
+
+    pub fn event_id_range(mut self, ids: RangeInclusive<u32>) -> Self {
+        self.event_ids
+            .push(EventIds::Range(*ids.start(), *ids.end()));
+        self
+    }
+
+    /// 1 critical, 2 error, 3 warning, 4 information and 5 verbose; classic
+    /// event log entries that are informational are level 0
+    pub fn level(mut self, level: u8) -> Self {
+        self.levels.push(level);
+        self
+    }
+
+    pub fn levels<I: IntoIterator<Item = u8>>(mut self, levels: I) -> Self {
+        self.levels.extend(levels);
+        self
+    }
+
+    /// Matches the provider's name, e.g. `Microsoft-Windows-Security-Auditing`
+    pub fn provider<S: Into<String>>(mut self, name: S) -> Self {
+        self.providers.push(name.into());
+        self
+    }
+
+    /// Only events created at or after `time`
+    pub fn since(mut self, time: SystemTime) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    /// Only events created at or before `time`
+    pub fn until(mut self, time: SystemTime) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    /// Only events created in the last `age`, relative to when the query runs
+    pub fn within_last(mut self, age: Duration) -> Self {
+        self.within = Some(age);
+        self
+    }
+
+    /// Only events with any of the keyword bits in `mask` set
+    pub fn any_keywords(mut self, mask: u64) -> Self {
+        self.keywords |= mask;
+        self
+    }
+
+    /// Only events with a `<Data Name="name">` whose value is `value`
+    pub fn data<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.data.push((name.into(), value.into()));
+        self
+    }
+
+    /// Renders the XPath filter.
+    ///
+    /// Fails if a string contains both kinds of quote, as the event log's
+    /// XPath has no way of escaping them.
+    pub fn build(&self) -> Result<String, WinEvtError> {
+        let mut system = Vec::new();
+
+        if !self.event_ids.is_empty() {
+            system.push(any_of(self.event_ids.iter().map(|ids| match ids {
+                EventIds::One(id) => format!("EventID={}", id),
+                EventIds::Range(lo, hi) => format!("(EventID>={} and EventID<={})", lo, hi),
+            })));
+        }
+
+        if !self.levels.is_empty() {
+            system.push(any_of(self.levels.iter().map(|l| format!("Level={}", l))));
+        }
+
+        if !self.providers.is_empty() {
+            let names = self
+                .providers
+                .iter()
+                .map(|p| Ok(format!("@Name={}", literal(p)?)))
+                .collect::<Result<Vec<_>, WinEvtError>>()?;
+            system.push(format!("Provider[{}]", names.join(" or ")));
+        }
+
+        let mut times = Vec::new();
+        if let Some(since) = self.since {
+            times.push(format!("@SystemTime>='{}'", system_time(since)));
+        }
+        if let Some(until) = self.until {
+            times.push(format!("@SystemTime<='{}'", system_time(until)));
+        }
+        if let Some(within) = self.within {
+            times.push(format!("timediff(@SystemTime)<={}", within.as_millis()));
+        }
+        if !times.is_empty() {
+            system.push(format!("TimeCreated[{}]", times.join(" and ")));
+        }
+
+        if self.keywords != 0 {
+            system.push(format!("band(Keywords,{})", self.keywords));
+        }
+
+        let mut conds = Vec::new();
+        if !system.is_empty() {
+            conds.push(format!("System[{}]", system.join(" and ")));
+        }
+        for (name, value) in &self.data {
+            conds.push(format!(
+                "EventData[Data[@Name={}]={}]",
+                literal(name)?,
+                literal(value)?
+            ));
+        }
+
+        if conds.is_empty() {
+            Ok("*".to_string())
+        } else {
+            Ok(format!("*[{}]", conds.join(" and ")))
+        }
+    }
+
+    /// Renders a `<QueryList>` selecting the filtered events of one channel
+    pub fn query_list(&self, channel: &str) -> Result<String, WinEvtError> {
+        Ok(QueryList::new()
+            .query(Query::new(channel).select_from(channel, self.build()?))
+            .to_xml())
+    }
+}
+
+fn any_of<I: Iterator<Item = String>>(conds: I) -> String {
+    let conds = conds.collect::<Vec<_>>();
+    if conds.len() == 1 {
+        conds.into_iter().next().unwrap()
+    } else {
+        format!("({})", conds.join(" or "))
+    }
+}
+
+fn system_time(time: SystemTime) -> String {
+    filetime::to_system_time(filetime::from_system_time(time))
+}
+
+/// Quotes an XPath string literal, which can't contain its own quote
+pub(crate) fn literal(s: &str) -> Result<String, WinEvtError> {
+    if !s.contains('\'') {
+        Ok(format!("'{}'", s))
+    } else if !s.contains('"') {
+        Ok(format!("\"{}\"", s))
+    } else {
+        Err(WinEvtError::new(
+            ERROR_EVT_INVALID_QUERY,
+            format!("can't quote {} as it contains both ' and \"", s),
+        ))
+    }
+}
+
+pub(crate) fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
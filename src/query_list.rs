@@ -0,0 +1,395 @@
+use std::collections::{HashMap, HashSet};
+use std::vec;
+
+use roxmltree::{Document, Node};
+
+use crate::errors::{WinEvtError, ERROR_EVT_INVALID_QUERY, ERROR_EVT_MALFORMED_XML_TEXT};
+use crate::query::escape_xml;
+use crate::source::{ChannelSource, EventRenderer, EventSource};
+use crate::xpath::XPathFilter;
+
+/// An XPath filter applied to one channel, as in a `<Select>` or `<Suppress>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    /// The channel, or the enclosing [`Query`]'s when missing
+    pub path: Option<String>,
+    pub xpath: String,
+}
+
+/// One `<Query>` of a [`QueryList`].
+///
+/// Its result is every event matched by a `<Select>` that isn't also matched
+/// by a `<Suppress>` on the same channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub path: Option<String>,
+    pub selects: Vec<Selector>,
+    pub suppresses: Vec<Selector>,
+}
+
+impl Query {
+    /// A query whose selectors default to the channel `path`
+    pub fn new<S: Into<String>>(path: S) -> Self {
+        Query {
+            path: Some(path.into()),
+            ..Query::default()
+        }
+    }
+
+    pub fn select<S: Into<String>>(mut self, xpath: S) -> Self {
+        self.selects.push(Selector {
+            path: None,
+            xpath: xpath.into(),
+        });
+        self
+    }
+
+    pub fn select_from<P: Into<String>, S: Into<String>>(mut self, path: P, xpath: S) -> Self {
+        self.selects.push(Selector {
+            path: Some(path.into()),
+            xpath: xpath.into(),
+        });
+        self
+    }
+
+    pub fn suppress<S: Into<String>>(mut self, xpath: S) -> Self {
+        self.suppresses.push(Selector {
+            path: None,
+            xpath: xpath.into(),
+        });
+        self
+    }
+
+    pub fn suppress_from<P: Into<String>, S: Into<String>>(mut self, path: P, xpath: S) -> Self {
+        self.suppresses.push(Selector {
+            path: Some(path.into()),
+            xpath: xpath.into(),
+        });
+        self
+    }
+
+    /// The channel a selector applies to
+    fn path_of<'a>(&'a self, sel: &'a Selector) -> Result<&'a str, WinEvtError> {
+        sel.path.as_deref().or(self.path.as_deref()).ok_or_else(|| {
+            WinEvtError::new(
+                ERROR_EVT_INVALID_QUERY,
+                format!("no channel given for {}", sel.xpath),
+            )
+        })
+    }
+}
+
+/// A structured query spanning several channels, the `<QueryList>` document
+/// `EvtQuery` accepts in place of a single XPath
+///
+/// ```
+/// use win_events::query_list::{Query, QueryList};
+///
+/// let list = QueryList::new()
+///     .query(
+///         Query::new("Security")
+///             .select("*[System[EventID=4624]]")
+///             .suppress("*[EventData[Data[@Name='LogonType']='5']]"),
+///     )
+///     .query(Query::new("System").select("*[System[Level<=2]]"));
+///
+/// assert_eq!(QueryList::from_xml(&list.to_xml()).unwrap(), list);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryList {
+    pub queries: Vec<Query>,
+}
+
+impl QueryList {
+    pub fn new() -> Self {
+        QueryList::default()
+    }
+
+    pub fn query(mut self, query: Query) -> Self {
+        self.queries.push(query);
+        self
+    }
+
+    /// Parses a `<QueryList>` document, as saved by Event Viewer's custom views
+    pub fn from_xml(xml: &str) -> Result<Self, WinEvtError> {
+        let doc = Document::parse(xml)
+            .map_err(|e| WinEvtError::parse(ERROR_EVT_MALFORMED_XML_TEXT, e))?;
+
+        let root = doc.root_element();
+        if root.tag_name().name() != "QueryList" {
+            return Err(WinEvtError::new(
+                ERROR_EVT_MALFORMED_XML_TEXT,
+                format!(
+                    "expected a QueryList element, not {}",
+                    root.tag_name().name()
+                ),
+            ));
+        }
+
+        let selector = |node: Node| Selector {
+            path: node.attribute("Path").map(str::to_string),
+            xpath: node.text().unwrap_or_default().trim().to_string(),
+        };
+
+        let mut list = QueryList::new();
+        for q in root.children().filter(|n| n.has_tag_name("Query")) {
+            let mut query = Query {
+                path: q.attribute("Path").map(str::to_string),
+                ..Query::default()
+            };
+            for node in q.children().filter(Node::is_element) {
+                match node.tag_name().name() {
+                    "Select" => query.selects.push(selector(node)),
+                    "Suppress" => query.suppresses.push(selector(node)),
+                    _ => {}
+                }
+            }
+            list.queries.push(query);
+        }
+
+        Ok(list)
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<QueryList>");
+
+        let selector = |xml: &mut String, tag: &str, sel: &Selector| {
+            xml.push('<');
+            xml.push_str(tag);
+            if let Some(path) = &sel.path {
+                xml.push_str(&format!(" Path=\"{}\"", escape_xml(path)));
+            }
+            xml.push_str(&format!(">{}</{}>", escape_xml(&sel.xpath), tag));
+        };
+
+        for (id, query) in self.queries.iter().enumerate() {
+            xml.push_str(&format!("<Query Id=\"{}\"", id));
+            if let Some(path) = &query.path {
+                xml.push_str(&format!(" Path=\"{}\"", escape_xml(path)));
+            }
+            xml.push('>');
+            for sel in &query.selects {
+                selector(&mut xml, "Select", sel);
+            }
+            for sel in &query.suppresses {
+                selector(&mut xml, "Suppress", sel);
+            }
+            xml.push_str("</Query>");
+        }
+
+        xml.push_str("</QueryList>");
+        xml
+    }
+
+    /// Every channel selected from, in order, without repeats
+    pub fn channels(&self) -> Result<Vec<String>, WinEvtError> {
+        let mut channels: Vec<String> = Vec::new();
+        for query in &self.queries {
+            for sel in &query.selects {
+                let path = query.path_of(sel)?;
+                if !channels.iter().any(|c| c == path) {
+                    channels.push(path.to_string());
+                }
+            }
+        }
+        Ok(channels)
+    }
+
+    /// Runs the whole list against `source` as a single stream of rendered
+    /// events, query by query and channel by channel.
+    ///
+    /// Each channel's selects are run through [`ChannelSource::events_for`], so
+    /// the XPath is evaluated however the source evaluates it. Suppresses are
+    /// evaluated against the selected events with an [`XPathFilter`]. Events
+    /// are told apart by their channel and `EventRecordID`, so one matched by
+    /// several selects, in the same query or not, is only returned once.
+    pub fn events<'a, S: ChannelSource>(
+        &self,
+        source: &'a S,
+    ) -> Result<QueryListEvents<'a, S>, WinEvtError> {
+        let mut groups: Vec<Group> = Vec::new();
+
+        for query in &self.queries {
+            let first = groups.len();
+            for sel in &query.selects {
+                let path = query.path_of(sel)?;
+                match groups[first..].iter_mut().find(|g| g.path == path) {
+                    Some(group) => group.selects.push(sel.xpath.clone()),
+                    None => groups.push(Group {
+                        path: path.to_string(),
+                        selects: vec![sel.xpath.clone()],
+                        suppresses: Vec::new(),
+                    }),
+                }
+            }
+            for sel in &query.suppresses {
+                let path = query.path_of(sel)?;
+                if let Some(group) = groups[first..].iter_mut().find(|g| g.path == path) {
+                    group.suppresses.push(XPathFilter::parse(&sel.xpath)?);
+                }
+            }
+        }
+
+        // Only channels selected from more than once can return an event twice
+        let mut selects: HashMap<&str, usize> = HashMap::new();
+        for group in &groups {
+            *selects.entry(&group.path).or_default() += group.selects.len();
+        }
+        let seen = selects
+            .into_iter()
+            .filter(|&(_, n)| n > 1)
+            .map(|(path, _)| (path.to_string(), HashSet::new()))
+            .collect();
+
+        Ok(QueryListEvents {
+            source,
+            renderer: source.renderer(),
+            groups: groups.into_iter(),
+            current: None,
+            seen,
+        })
+    }
+}
+
+/// The selects and suppresses of one query that apply to the same channel
+struct Group {
+    path: String,
+    selects: Vec<String>,
+    suppresses: Vec<XPathFilter>,
+}
+
+struct Current<S: ChannelSource> {
+    path: String,
+    pending: vec::IntoIter<String>,
+    suppresses: Vec<XPathFilter>,
+    events: Option<S::Events>,
+}
+
+/// What tells two events of a channel apart; their record ID when they
+/// have one, otherwise everything about them
+#[derive(PartialEq, Eq, Hash)]
+enum Identity {
+    RecordId(u64),
+    Xml(String),
+}
+
+impl Identity {
+    fn of(doc: &Document, xml: &str) -> Self {
+        let id = doc
+            .root_element()
+            .children()
+            .find(|n| n.has_tag_name("System"))
+            .and_then(|sys| sys.children().find(|n| n.has_tag_name("EventRecordID")))
+            .and_then(|n| n.text())
+            .and_then(|id| id.trim().parse().ok());
+
+        match id {
+            Some(id) => Identity::RecordId(id),
+            None => Identity::Xml(xml.to_string()),
+        }
+    }
+}
+
+/// The rendered events of a [`QueryList`], see [`QueryList::events`].
+///
+/// An error opening or reading one channel is returned in place of its
+/// events, after which the stream carries on with the next.
+pub struct QueryListEvents<'a, S: ChannelSource> {
+    source: &'a S,
+    renderer: S::Renderer,
+    groups: vec::IntoIter<Group>,
+    current: Option<Current<S>>,
+    /// Events already returned from the channels that need tracking
+    seen: HashMap<String, HashSet<Identity>>,
+}
+
+impl<'a, S: ChannelSource> QueryListEvents<'a, S> {
+    /// The channel events are currently being read from
+    pub fn channel(&self) -> Option<&str> {
+        self.current.as_ref().map(|c| c.path.as_str())
+    }
+
+    /// Whether `xml`, just read from the current channel, should be returned;
+    /// the event is only parsed when it has to be suppressed or tracked
+    fn keep(&mut self, xml: &str) -> Result<bool, WinEvtError> {
+        let current = match &self.current {
+            Some(current) => current,
+            None => return Ok(true),
+        };
+        let seen = self.seen.get_mut(&current.path);
+        if current.suppresses.is_empty() && seen.is_none() {
+            return Ok(true);
+        }
+
+        let doc = Document::parse(xml)
+            .map_err(|e| WinEvtError::parse(ERROR_EVT_MALFORMED_XML_TEXT, e))?;
+        if current.suppresses.iter().any(|f| f.matches_doc(&doc)) {
+            return Ok(false);
+        }
+        Ok(match seen {
+            Some(seen) => seen.insert(Identity::of(&doc, xml)),
+            None => true,
+        })
+    }
+}
+
+impl<'a, S: ChannelSource> Iterator for QueryListEvents<'a, S> {
+    type Item = Result<String, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let current = match &mut self.current {
+                Some(current) => current,
+                None => {
+                    let group = self.groups.next()?;
+                    self.current = Some(Current {
+                        path: group.path,
+                        pending: group.selects.into_iter(),
+                        suppresses: group.suppresses,
+                        events: None,
+                    });
+                    continue;
+                }
+            };
+
+            let events = match &mut current.events {
+                Some(events) => events,
+                None => match current.pending.next() {
+                    None => {
+                        self.current = None;
+                        continue;
+                    }
+                    Some(xpath) => match self.source.events_for(&current.path, Some(&xpath)) {
+                        Ok(events) => current.events.insert(events),
+                        Err(e) => return Some(Err(e)),
+                    },
+                },
+            };
+
+            let xml = match events.next() {
+                None => {
+                    current.events = None;
+                    continue;
+                }
+                Some(Err(e)) => {
+                    current.events = None;
+                    return Some(Err(e));
+                }
+                Some(Ok(e)) => match self.renderer.render(e) {
+                    Ok(xml) => xml,
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+
+            match self.keep(&xml) {
+                Ok(true) => return Some(Ok(xml)),
+                Ok(false) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl<'a, S: ChannelSource> EventSource for QueryListEvents<'a, S> {
+    type Event = String;
+}
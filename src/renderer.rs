@@ -4,6 +4,7 @@ use winapi::um::winevt::{self, EvtRender};
 
 use crate::errors::WinError;
 use crate::errors::WinEvtError;
+use crate::source::EventRenderer;
 use crate::utils;
 use crate::win_event::WinEvent;
 
@@ -69,3 +70,9 @@ impl Renderer {
         Ok(xml)
     }
 }
+
+impl EventRenderer<WinEvent> for Renderer {
+    fn render(&mut self, event: WinEvent) -> Result<String, WinEvtError> {
+        Renderer::render(self, event)
+    }
+}
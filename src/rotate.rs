@@ -0,0 +1,244 @@
+use std::cell::{Cell, RefCell};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::codec::{Codec, Encoder};
+use crate::errors::WinEvtError;
+use crate::output::{EventWriter, Format};
+
+/// When a [`RotatingWriter`] moves on to a new file; with neither set
+/// everything goes to one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rotation {
+    /// Bytes written to a file, after compression, after which it's
+    /// finished. Compressors hand over their output in blocks, so files run
+    /// over by up to a block
+    pub max_size: Option<u64>,
+    /// How long a file is written to before it's finished. This is checked as
+    /// each event arrives, so a file stays open past it until the next one
+    pub max_age: Option<Duration>,
+}
+
+impl Rotation {
+    pub fn is_none(&self) -> bool {
+        self.max_size.is_none() && self.max_age.is_none()
+    }
+}
+
+/// A segment's file, counting the bytes the compressor writes to it
+struct CountedFile {
+    file: BufWriter<File>,
+    written: Rc<Cell<u64>>,
+}
+
+impl Write for CountedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.file.write(buf)?;
+        self.written.set(self.written.get() + n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// A segment's compressor, until it's finished
+type SharedEncoder = Rc<RefCell<Option<Encoder<CountedFile>>>>;
+
+/// Lets a segment's [`EventWriter`] write through the encoder while the
+/// [`RotatingWriter`] keeps hold of it to finish it
+struct SharedWriter(SharedEncoder);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.borrow_mut().as_mut() {
+            Some(e) => e.write(buf),
+            None => Err(io::Error::other("segment already finished")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.borrow_mut().as_mut() {
+            Some(e) => e.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Segment {
+    path: PathBuf,
+    out: Box<dyn EventWriter>,
+    encoder: SharedEncoder,
+    /// Bytes written to the file so far
+    written: Rc<Cell<u64>>,
+    started: Instant,
+    events: usize,
+}
+
+/// Writes events in `format`, compressed with `codec`, to a series of files,
+/// moving on to the next when the current one reaches the size or age given
+/// by [`Rotation`].
+///
+/// Without rotation everything goes to `path` itself. With it, files are
+/// numbered from zero before the first `.` of the file name, so `events.xml.gz`
+/// is written as `events-00000.xml.gz`, `events-00001.xml.gz` and so on,
+/// skipping numbers already taken by files from an earlier run rather than
+/// overwriting them. Each file is complete when rotated away from, ending with
+/// its codec's trailer and holding valid output on its own; a JSON array is
+/// closed in every file.
+pub struct RotatingWriter {
+    path: PathBuf,
+    format: Format,
+    codec: Codec,
+    level: u32,
+    rotation: Rotation,
+    next_index: usize,
+    current: Option<Segment>,
+    finished: Vec<PathBuf>,
+}
+
+impl RotatingWriter {
+    /// The level is checked when the first file is opened
+    pub fn new<P: Into<PathBuf>>(
+        path: P,
+        format: Format,
+        codec: Codec,
+        level: u32,
+        rotation: Rotation,
+    ) -> Self {
+        RotatingWriter {
+            path: path.into(),
+            format,
+            codec,
+            level,
+            rotation,
+            next_index: 0,
+            current: None,
+            finished: Vec::new(),
+        }
+    }
+
+    /// The name of the `index`th file
+    pub fn segment_path(&self, index: usize) -> PathBuf {
+        if self.rotation.is_none() {
+            return self.path.clone();
+        }
+
+        let name = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match name.split_once('.') {
+            Some((stem, ext)) => format!("{}-{:05}.{}", stem, index, ext),
+            None => format!("{}-{:05}", name, index),
+        };
+        self.path.with_file_name(name)
+    }
+
+    /// The files written and finished so far
+    pub fn segments(&self) -> &[PathBuf] {
+        &self.finished
+    }
+
+    fn open(&mut self) -> Result<(), WinEvtError> {
+        let (path, file) = loop {
+            let path = self.segment_path(self.next_index);
+            self.next_index += 1;
+
+            let file = if self.rotation.is_none() {
+                File::create(&path)
+            } else {
+                OpenOptions::new().write(true).create_new(true).open(&path)
+            };
+            match file {
+                Ok(file) => break (path, file),
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(WinEvtError::output(e)),
+            }
+        };
+
+        let written = Rc::new(Cell::new(0));
+        let file = CountedFile {
+            file: BufWriter::new(file),
+            written: written.clone(),
+        };
+        let encoder = Rc::new(RefCell::new(Some(self.codec.encoder(file, self.level)?)));
+
+        self.current = Some(Segment {
+            path,
+            out: self.format.writer(SharedWriter(encoder.clone())),
+            encoder,
+            written,
+            started: Instant::now(),
+            events: 0,
+        });
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), WinEvtError> {
+        let mut seg = match self.current.take() {
+            Some(seg) => seg,
+            None => return Ok(()),
+        };
+
+        seg.out.finish()?;
+        drop(seg.out);
+        if let Some(enc) = seg.encoder.borrow_mut().take() {
+            enc.finish()
+                .and_then(|f| f.file.into_inner().map_err(io::IntoInnerError::into_error))
+                .and_then(|file| file.sync_all())
+                .map_err(WinEvtError::output)?;
+        }
+
+        self.finished.push(seg.path);
+        Ok(())
+    }
+
+    fn is_full(&self, seg: &Segment) -> bool {
+        seg.events > 0
+            && (self
+                .rotation
+                .max_size
+                .is_some_and(|max| seg.written.get() >= max)
+                || self
+                    .rotation
+                    .max_age
+                    .is_some_and(|max| seg.started.elapsed() >= max))
+    }
+}
+
+impl EventWriter for RotatingWriter {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        if self.current.as_ref().is_some_and(|seg| self.is_full(seg)) {
+            self.close()?;
+        }
+        if self.current.is_none() {
+            self.open()?;
+        }
+
+        let seg = self.current.as_mut().unwrap();
+        seg.out.write_event(xml)?;
+        seg.events += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), WinEvtError> {
+        match self.current.as_mut() {
+            Some(seg) => seg.out.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Finishes the current file; an empty one is written if nothing else was
+    fn finish(&mut self) -> Result<(), WinEvtError> {
+        if self.current.is_none() && self.finished.is_empty() {
+            self.open()?;
+        }
+        self.close()
+    }
+}
@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+use crate::errors::WinEvtError;
+
+/// An iterator of events read from a single channel.
+///
+/// The events themselves are opaque; they are turned into XML by the matching
+/// [`EventRenderer`] of the [`ChannelSource`] that produced them.
+pub trait EventSource: Iterator<Item = Result<<Self as EventSource>::Event, WinEvtError>> {
+    type Event;
+}
+
+/// Turns events from an [`EventSource`] into their XML form.
+pub trait EventRenderer<E> {
+    fn render(&mut self, event: E) -> Result<String, WinEvtError>;
+}
+
+/// Somewhere channels and their events can be read from; either the local
+/// event log or an offline backend such as [`FixtureSource`].
+///
+/// [`FixtureSource`]: crate::fixture::FixtureSource
+pub trait ChannelSource {
+    type Channels: Iterator<Item = Result<String, WinEvtError>>;
+    type Events: EventSource;
+    type Renderer: EventRenderer<<Self::Events as EventSource>::Event>;
+
+    fn channels(&self) -> Result<Self::Channels, WinEvtError>;
+
+    fn events_for(&self, name: &str, query: Option<&str>) -> Result<Self::Events, WinEvtError>;
+
+    fn renderer(&self) -> Self::Renderer;
+}
+
+/// Where a subscription starts reading from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StartAt {
+    /// Only events published after subscribing
+    #[default]
+    Now,
+    /// Every event still in the channel, then new ones
+    Oldest,
+    /// The events after the one with this `EventRecordID`, as saved in a
+    /// [`Bookmarks`] file
+    ///
+    /// [`Bookmarks`]: crate::bookmark::Bookmarks
+    After(u64),
+}
+
+impl FromStr for StartAt {
+    type Err = String;
+
+    /// `now`, `oldest` or the record ID to start after
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "now" => Ok(StartAt::Now),
+            "oldest" => Ok(StartAt::Oldest),
+            other => other.parse().map(StartAt::After).map_err(|_| {
+                format!(
+                    "unknown start {}, expected now, oldest or a record ID",
+                    other
+                )
+            }),
+        }
+    }
+}
+
+/// Somewhere channels can be followed as events are published to them;
+/// either the local event log or a [`MockPublisher`].
+///
+/// The events of a subscription never run out, its iterator blocks until
+/// the next one is published.
+///
+/// [`MockPublisher`]: crate::mock_publisher::MockPublisher
+pub trait LiveSource {
+    type Events: EventSource;
+    type Renderer: EventRenderer<<Self::Events as EventSource>::Event>;
+
+    fn subscribe(
+        &self,
+        channel: &str,
+        query: Option<&str>,
+        start: StartAt,
+    ) -> Result<Self::Events, WinEvtError>;
+
+    fn renderer(&self) -> Self::Renderer;
+}
@@ -0,0 +1,103 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures::channel::mpsc;
+use futures::executor;
+use futures::{SinkExt, Stream};
+
+use crate::errors::WinEvtError;
+use crate::source::{ChannelSource, EventRenderer, LiveSource, StartAt};
+
+/// How many items an [`EventStream`] reads ahead of whoever is polling it
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// A [`Stream`] over an iterator run on a thread of its own, so blocking
+/// event log calls stay off the async runtime.
+///
+/// The iterator is opened, read and dropped on that thread, which matters
+/// for the local event log since its handles can't be sent between
+/// threads; for the same reason events come out already rendered. Up to
+/// `capacity` items are read ahead. Dropping the stream stops the thread the
+/// next time it has an item to hand over, so for a subscription that's once
+/// the next event arrives.
+pub struct EventStream<T> {
+    rx: mpsc::Receiver<Result<T, WinEvtError>>,
+}
+
+impl<T: Send + 'static> EventStream<T> {
+    /// Runs the iterator `open` returns; an error opening it is the only
+    /// item of the stream
+    pub fn spawn<I, F>(capacity: usize, open: F) -> Self
+    where
+        F: FnOnce() -> Result<I, WinEvtError> + Send + 'static,
+        I: Iterator<Item = Result<T, WinEvtError>>,
+    {
+        // Each sender gets a slot on top of the buffer
+        let (mut tx, rx) = mpsc::channel(capacity.saturating_sub(1));
+
+        thread::spawn(move || {
+            let iter = match open() {
+                Ok(iter) => iter,
+                Err(e) => {
+                    let _ = executor::block_on(tx.send(Err(e)));
+                    return;
+                }
+            };
+
+            for item in iter {
+                if executor::block_on(tx.send(item)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        EventStream { rx }
+    }
+}
+
+impl EventStream<String> {
+    /// The rendered events of a channel, optionally filtered by a query
+    pub fn events<S, C>(source: S, channel: C, query: Option<String>) -> Self
+    where
+        S: ChannelSource + Send + 'static,
+        C: Into<String>,
+    {
+        let channel = channel.into();
+        EventStream::spawn(DEFAULT_CAPACITY, move || {
+            let events = source.events_for(&channel, query.as_deref())?;
+            let mut rend = source.renderer();
+            Ok(events.map(move |e| e.and_then(|e| rend.render(e))))
+        })
+    }
+
+    /// The names of a source's channels; for the local event log these come
+    /// from a [`ChannelIter`]
+    ///
+    /// [`ChannelIter`]: crate::channel_iter::ChannelIter
+    pub fn channels<S: ChannelSource + Send + 'static>(source: S) -> Self {
+        EventStream::spawn(DEFAULT_CAPACITY, move || source.channels())
+    }
+
+    /// The rendered events of a subscription as they're published
+    pub fn subscribe<L, C>(source: L, channel: C, query: Option<String>, start: StartAt) -> Self
+    where
+        L: LiveSource + Send + 'static,
+        C: Into<String>,
+    {
+        let channel = channel.into();
+        EventStream::spawn(DEFAULT_CAPACITY, move || {
+            let events = source.subscribe(&channel, query.as_deref(), start)?;
+            let mut rend = source.renderer();
+            Ok(events.map(move |e| e.and_then(|e| rend.render(e))))
+        })
+    }
+}
+
+impl<T> Stream for EventStream<T> {
+    type Item = Result<T, WinEvtError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::ptr;
+
+use widestring::U16CString;
+use winapi::shared::winerror::ERROR_TIMEOUT;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::synchapi::{CreateEventW, ResetEvent, WaitForSingleObject};
+use winapi::um::winbase::{INFINITE, WAIT_OBJECT_0};
+use winapi::um::winevt::{self, EvtClose, EvtCreateBookmark, EvtNext, EvtSubscribe, EVT_HANDLE};
+use winapi::um::winnt::HANDLE;
+
+use crate::errors::{WinError, WinEvtError};
+use crate::query::escape_xml;
+use crate::renderer::Renderer;
+use crate::source::{EventSource, LiveSource, StartAt};
+use crate::utils;
+use crate::win_event::WinEvent;
+
+const EVENTS_BUFFER: usize = 10;
+
+/// A pull model `EvtSubscribe` subscription to a local channel.
+///
+/// Windows sets the signal event whenever new events are ready; `next` reads
+/// whatever is there and otherwise waits on it.
+pub struct WinSubscription {
+    handle: EVT_HANDLE,
+    signal: HANDLE,
+    /// Whether the signal has been reset since the last wait
+    reset: bool,
+    events: VecDeque<Result<WinEvent, WinEvtError>>,
+}
+
+impl WinSubscription {
+    pub fn subscribe(
+        channel: &str,
+        query: Option<&str>,
+        start: StartAt,
+    ) -> Result<WinSubscription, WinEvtError> {
+        let path = U16CString::from_str(channel).expect("Invalid channel");
+        let query = query.map(|q| U16CString::from_str(q).expect("Invalid query"));
+
+        let (flags, bookmark) = match start {
+            StartAt::Now => (winevt::EvtSubscribeToFutureEvents, ptr::null_mut()),
+            StartAt::Oldest => (winevt::EvtSubscribeStartAtOldestRecord, ptr::null_mut()),
+            StartAt::After(id) => {
+                let xml = format!(
+                    "<BookmarkList><Bookmark Channel=\"{}\" RecordId=\"{}\" IsCurrent=\"true\"/></BookmarkList>",
+                    escape_xml(channel),
+                    id
+                );
+                let xml = U16CString::from_str(xml).expect("Invalid bookmark");
+                (
+                    winevt::EvtSubscribeStartAfterBookmark,
+                    utils::not_null(unsafe { EvtCreateBookmark(xml.as_ptr()) })?,
+                )
+            }
+        };
+
+        let signal = unsafe { CreateEventW(ptr::null_mut(), 1, 1, ptr::null()) };
+        if signal.is_null() {
+            if !bookmark.is_null() {
+                unsafe { EvtClose(bookmark) };
+            }
+            return Err(WinEvtError::from_last_error());
+        }
+
+        let handle = unsafe {
+            EvtSubscribe(
+                ptr::null_mut(),
+                signal,
+                path.as_ptr(),
+                query.as_ref().map_or(ptr::null(), |q| q.as_ptr()),
+                bookmark,
+                ptr::null_mut(),
+                None,
+                flags,
+            )
+        };
+        let handle = utils::not_null(handle);
+
+        if !bookmark.is_null() {
+            unsafe { EvtClose(bookmark) };
+        }
+        let handle = match handle {
+            Ok(handle) => handle,
+            Err(e) => {
+                unsafe { CloseHandle(signal) };
+                return Err(e);
+            }
+        };
+
+        Ok(WinSubscription {
+            handle,
+            signal,
+            reset: false,
+            events: VecDeque::with_capacity(EVENTS_BUFFER),
+        })
+    }
+}
+
+impl Iterator for WinSubscription {
+    type Item = Result<WinEvent, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(e) = self.events.pop_front() {
+                return Some(e);
+            }
+
+            let mut returned = 0;
+            let mut next: Vec<EVT_HANDLE> = vec![ptr::null_mut(); EVENTS_BUFFER];
+
+            match utils::check_okay_check(unsafe {
+                EvtNext(
+                    self.handle,
+                    EVENTS_BUFFER as u32,
+                    next.as_mut_ptr(),
+                    0,
+                    0,
+                    &mut returned,
+                )
+            }) {
+                Ok(()) => self.events.extend(
+                    next.iter()
+                        .take(returned as usize)
+                        .map(|&h| Ok(WinEvent::new(h))),
+                ),
+                Err(WinError::NoMoreItems) => self.wait()?,
+                Err(WinError::Err(ref e)) if e.code() == ERROR_TIMEOUT => self.wait()?,
+                Err(e) => return Some(Err(e.into_err())),
+            }
+        }
+    }
+}
+
+impl WinSubscription {
+    /// Nothing was ready; reset the signal and check once more before
+    /// waiting, so events arriving in between aren't missed
+    fn wait(&mut self) -> Option<()> {
+        if !self.reset {
+            unsafe { ResetEvent(self.signal) };
+            self.reset = true;
+            return Some(());
+        }
+
+        self.reset = false;
+        match unsafe { WaitForSingleObject(self.signal, INFINITE) } {
+            WAIT_OBJECT_0 => Some(()),
+            _ => None,
+        }
+    }
+}
+
+impl EventSource for WinSubscription {
+    type Event = WinEvent;
+}
+
+impl Drop for WinSubscription {
+    fn drop(&mut self) {
+        crate::utils::check_okay(unsafe { EvtClose(self.handle) })
+            .expect("Couldn't close the windows event subscription handle");
+        unsafe { CloseHandle(self.signal) };
+    }
+}
+
+/// Subscriptions to the channels of the local machine's event log
+#[derive(Default)]
+pub struct LocalSubscriber;
+
+impl LiveSource for LocalSubscriber {
+    type Events = WinSubscription;
+    type Renderer = Renderer;
+
+    fn subscribe(
+        &self,
+        channel: &str,
+        query: Option<&str>,
+        start: StartAt,
+    ) -> Result<WinSubscription, WinEvtError> {
+        WinSubscription::subscribe(channel, query, start)
+    }
+
+    fn renderer(&self) -> Renderer {
+        Renderer::new()
+    }
+}
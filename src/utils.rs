@@ -24,7 +24,7 @@ pub fn check_okay(b: i32) -> Result<(), WinEvtError> {
 }
 
 #[inline(always)]
-pub fn check_okay_check(b: i32) -> Result<(), WinError> {
+pub(crate) fn check_okay_check(b: i32) -> Result<(), WinError> {
     if b == 0 {
         Err(match unsafe { GetLastError() } {
             winerror::ERROR_INSUFFICIENT_BUFFER => WinError::InsufficientBuffer,
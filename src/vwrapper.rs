@@ -64,6 +64,7 @@ impl WevWrapper {
                 if self.pointer == ptr::null_mut() {
                     panic!("Couldn't allocate a windows event variant object")
                 }
+                self.size = new_size;
             }
         };
 
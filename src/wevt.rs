@@ -0,0 +1,307 @@
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::binxml::{escape, format_guid};
+use crate::errors::{WinEvtError, ERROR_RESOURCE_TYPE_NOT_FOUND};
+use crate::evtx::{invalid, read_u16, read_u32, read_u64};
+use crate::pe::{self, ResourceType};
+use crate::pub_metadata::{Channel, EventMetadata, Keyword, Level, OpCode, PubMetadata, Task};
+
+const WEVT_TEMPLATE: &str = "WEVT_TEMPLATE";
+
+/// Message IDs are this when a provider doesn't give one
+const NO_MESSAGE_ID: u32 = 0xFFFF_FFFF;
+
+const TEMPLATE_XMLNS: &str = "http://schemas.microsoft.com/win/2004/08/events";
+
+/// `win:` input types, numbered as in the manifest
+const IN_TYPES: &[&str] = &[
+    "win:Null",
+    "win:UnicodeString",
+    "win:AnsiString",
+    "win:Int8",
+    "win:UInt8",
+    "win:Int16",
+    "win:UInt16",
+    "win:Int32",
+    "win:UInt32",
+    "win:Int64",
+    "win:UInt64",
+    "win:Float",
+    "win:Double",
+    "win:Boolean",
+    "win:Binary",
+    "win:GUID",
+    "win:Pointer",
+    "win:FILETIME",
+    "win:SYSTEMTIME",
+    "win:SID",
+    "win:HexInt32",
+    "win:HexInt64",
+];
+
+/// Output types, numbered as in the manifest; zero means the input type's default
+const OUT_TYPES: &[&str] = &[
+    "",
+    "xs:string",
+    "xs:dateTime",
+    "xs:byte",
+    "xs:unsignedByte",
+    "xs:short",
+    "xs:unsignedShort",
+    "xs:int",
+    "xs:unsignedInt",
+    "xs:long",
+    "xs:unsignedLong",
+    "xs:float",
+    "xs:double",
+    "xs:boolean",
+    "xs:GUID",
+    "xs:hexBinary",
+    "win:HexInt8",
+    "win:HexInt16",
+    "win:HexInt32",
+    "win:HexInt64",
+    "win:PID",
+    "win:TID",
+    "win:Port",
+    "win:IPv4",
+    "win:IPv6",
+    "win:SocketAddress",
+    "win:CIMDateTime",
+    "win:ETWTIME",
+    "win:Xml",
+    "win:ErrorCode",
+    "win:Win32Error",
+    "win:NTSTATUS",
+    "win:HResult",
+    "win:DateTimeCultureInsensitive",
+];
+
+/// Reads the providers from the `WEVT_TEMPLATE` resources of a PE image,
+/// where the message compiler leaves a provider's instrumentation manifest.
+pub fn from_pe(data: &[u8]) -> Result<Vec<PubMetadata>, WinEvtError> {
+    let manifests = pe::resources(data, ResourceType::Name(WEVT_TEMPLATE))?;
+    if manifests.is_empty() {
+        return Err(WinEvtError::new(
+            ERROR_RESOURCE_TYPE_NOT_FOUND,
+            "no WEVT_TEMPLATE resource in the image",
+        ));
+    }
+
+    let mut providers = Vec::new();
+    for (_, manifest) in manifests {
+        providers.extend(parse(manifest)?);
+    }
+    Ok(providers)
+}
+
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Vec<PubMetadata>, WinEvtError> {
+    from_pe(&fs::read(path)?)
+}
+
+/// Reads a compiled manifest, the `CRIM` block a `WEVT_TEMPLATE` resource
+/// holds, with each provider it defines.
+///
+/// A provider's channels, levels, tasks, opcodes, keywords and events come
+/// from its `CHAN`, `LEVL`, `TASK`, `OPCO`, `KEYW` and `EVNT` blocks, and
+/// each event's template from the `TTBL` entry it points at. The manifest
+/// doesn't name the provider or the files holding its messages.
+pub fn parse(data: &[u8]) -> Result<Vec<PubMetadata>, WinEvtError> {
+    let header = block(data, 0, 16)?;
+    if &header[..4] != b"CRIM" {
+        return Err(invalid("not a compiled manifest"));
+    }
+
+    (0..read_u32(header, 12) as usize)
+        .map(|i| {
+            let desc = block(data, 16 + i * 20, 20)?;
+            let guid = <&[u8; 16]>::try_from(&desc[..16]).unwrap();
+            provider(data, read_u32(desc, 16) as usize, format_guid(guid))
+        })
+        .collect()
+}
+
+/// Reads the `WEVT` block of one provider. Offsets all through the manifest
+/// are from the start of the `CRIM` block.
+fn provider(data: &[u8], at: usize, guid: String) -> Result<PubMetadata, WinEvtError> {
+    let header = block(data, at, 20)?;
+    if &header[..4] != b"WEVT" {
+        return Err(invalid(format!("no provider at {:#x}", at)));
+    }
+
+    let mut meta = PubMetadata {
+        guid: Some(guid),
+        message_id: message_id(read_u32(header, 8)),
+        ..PubMetadata::default()
+    };
+
+    for i in 0..read_u32(header, 12) as usize {
+        let offset = read_u32(block(data, at + 20 + i * 8, 8)?, 0) as usize;
+        let element = block(data, offset, 12)?;
+        let count = read_u32(element, 8) as usize;
+
+        match &element[..4] {
+            b"CHAN" => {
+                meta.channels = entries(data, offset + 12, count, 16, |e| {
+                    Ok(Channel {
+                        name: name(data, read_u32(e, 4))?,
+                        id: Some(read_u32(e, 0)),
+                        message_id: message_id(read_u32(e, 12)),
+                        ..Channel::default()
+                    })
+                })?;
+                for (index, channel) in meta.channels.iter_mut().enumerate() {
+                    channel.index = Some(index as u32);
+                }
+            }
+            b"LEVL" => {
+                meta.levels = entries(data, offset + 12, count, 12, |e| {
+                    Ok(Level {
+                        name: name(data, read_u32(e, 8))?,
+                        id: Some(read_u32(e, 0)),
+                        message_id: message_id(read_u32(e, 4)),
+                    })
+                })?
+            }
+            b"TASK" => {
+                meta.tasks = entries(data, offset + 12, count, 28, |e| {
+                    let guid = <&[u8; 16]>::try_from(&e[8..24]).unwrap();
+                    Ok(Task {
+                        name: name(data, read_u32(e, 24))?,
+                        guid: Some(format_guid(guid)).filter(|_| guid != &[0; 16]),
+                        value: Some(read_u32(e, 0)),
+                        message_id: message_id(read_u32(e, 4)),
+                    })
+                })?
+            }
+            b"OPCO" => {
+                // The task is in the low word and the opcode in the high
+                meta.opcodes = entries(data, offset + 12, count, 12, |e| {
+                    Ok(OpCode {
+                        name: name(data, read_u32(e, 8))?,
+                        opcode_value: Some(read_u16(e, 2)),
+                        task_id: Some(read_u16(e, 0)),
+                        message_id: message_id(read_u32(e, 4)),
+                    })
+                })?
+            }
+            b"KEYW" => {
+                meta.keywords = entries(data, offset + 12, count, 16, |e| {
+                    Ok(Keyword {
+                        name: name(data, read_u32(e, 12))?,
+                        mask: Some(read_u64(e, 0)),
+                        message_id: message_id(read_u32(e, 8)),
+                    })
+                })?
+            }
+            b"EVNT" => {
+                meta.events = entries(data, offset + 16, count, 48, |e| {
+                    let template = match read_u32(e, 20) {
+                        0 => None,
+                        at => Some(template(data, at as usize)?),
+                    };
+                    Ok(EventMetadata {
+                        id: read_u16(e, 0).into(),
+                        version: e[2],
+                        channel: e[3],
+                        level: e[4],
+                        opcode: e[5],
+                        task: read_u16(e, 6),
+                        keywords: read_u64(e, 8),
+                        message_id: message_id(read_u32(e, 16)),
+                        template,
+                    })
+                })?
+            }
+            // Templates are read through the events using them, and value
+            // maps (MAPS) and provider attributes (PRVA) aren't kept
+            _ => {}
+        }
+    }
+
+    Ok(meta)
+}
+
+/// Renders a `TEMP` block the way the event log gives an event's template
+fn template(data: &[u8], at: usize) -> Result<String, WinEvtError> {
+    let header = block(data, at, 40)?;
+    if &header[..4] != b"TEMP" {
+        return Err(invalid(format!("no template at {:#x}", at)));
+    }
+    let items = read_u32(header, 16) as usize;
+
+    let mut xml = format!("<template xmlns=\"{}\">", TEMPLATE_XMLNS);
+    for i in 0..read_u32(header, 8) as usize {
+        let item = block(data, items + i * 20, 20)?;
+
+        xml.push_str("<data name=\"");
+        escape(
+            &mut xml,
+            &name(data, read_u32(item, 16))?.unwrap_or_default(),
+            true,
+        );
+        write!(xml, "\" inType=\"{}\"", type_name(IN_TYPES, item[4])).unwrap();
+        if item[5] != 0 {
+            write!(xml, " outType=\"{}\"", type_name(OUT_TYPES, item[5])).unwrap();
+        }
+        xml.push_str("/>");
+    }
+    xml.push_str("</template>");
+
+    Ok(xml)
+}
+
+fn type_name(names: &[&str], n: u8) -> String {
+    match names.get(n as usize) {
+        Some(name) => name.to_string(),
+        None => n.to_string(),
+    }
+}
+
+/// Reads `count` fixed size entries starting at `at`
+fn entries<T, F>(
+    data: &[u8],
+    at: usize,
+    count: usize,
+    size: usize,
+    mut entry: F,
+) -> Result<Vec<T>, WinEvtError>
+where
+    F: FnMut(&[u8]) -> Result<T, WinEvtError>,
+{
+    (0..count)
+        .map(|i| entry(block(data, at + i * size, size)?))
+        .collect()
+}
+
+/// A name stored as its size in bytes, counting the size itself, then
+/// NUL terminated UTF-16; an offset of zero means there isn't one
+fn name(data: &[u8], at: u32) -> Result<Option<String>, WinEvtError> {
+    if at == 0 {
+        return Ok(None);
+    }
+
+    let at = at as usize;
+    let size = read_u32(block(data, at, 4)?, 0) as usize;
+    let units: Vec<u16> = block(data, at + 4, size.saturating_sub(4))?
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Ok(Some(
+        String::from_utf16_lossy(&units)
+            .trim_end_matches('\0')
+            .to_string(),
+    ))
+}
+
+fn message_id(id: u32) -> Option<u32> {
+    Some(id).filter(|&id| id != NO_MESSAGE_ID)
+}
+
+fn block(data: &[u8], at: usize, len: usize) -> Result<&[u8], WinEvtError> {
+    data.get(at..at.saturating_add(len))
+        .ok_or_else(|| invalid(format!("manifest truncated at {:#x}", at)))
+}
@@ -0,0 +1,80 @@
+/// The levels `winmeta.xml` defines, which like its opcodes, keywords and
+/// channels every provider can use without defining them itself. Each has
+/// its name, value and the en-US text the event log displays for it.
+pub const LEVELS: &[(&str, u8, &str)] = &[
+    // Event Viewer shows events logged at any level as informational
+    ("win:LogAlways", 0, "Information"),
+    ("win:Critical", 1, "Critical"),
+    ("win:Error", 2, "Error"),
+    ("win:Warning", 3, "Warning"),
+    ("win:Informational", 4, "Information"),
+    ("win:Verbose", 5, "Verbose"),
+];
+
+pub const OPCODES: &[(&str, u8, &str)] = &[
+    ("win:Info", 0, "Info"),
+    ("win:Start", 1, "Start"),
+    ("win:Stop", 2, "Stop"),
+    ("win:DC_Start", 3, "DCStart"),
+    ("win:DC_Stop", 4, "DCStop"),
+    ("win:Extension", 5, "Extension"),
+    ("win:Reply", 6, "Reply"),
+    ("win:Resume", 7, "Resume"),
+    ("win:Suspend", 8, "Suspend"),
+    ("win:Send", 9, "Send"),
+    ("win:Receive", 240, "Receive"),
+];
+
+/// The reserved keywords, in the high bits a provider's own keywords can't use
+pub const KEYWORDS: &[(&str, u64, &str)] = &[
+    ("win:AnyKeyword", 0, "Any Keyword"),
+    ("win:ResponseTime", 0x0001_0000_0000_0000, "Response Time"),
+    ("win:WDIContext", 0x0002_0000_0000_0000, "WDI Context"),
+    ("win:WDIDiag", 0x0004_0000_0000_0000, "WDI Diag"),
+    ("win:SQM", 0x0008_0000_0000_0000, "SQM"),
+    ("win:AuditFailure", 0x0010_0000_0000_0000, "Audit Failure"),
+    ("win:AuditSuccess", 0x0020_0000_0000_0000, "Audit Success"),
+    (
+        "win:CorrelationHint",
+        0x0040_0000_0000_0000,
+        "Correlation Hint",
+    ),
+    ("win:EventlogClassic", 0x0080_0000_0000_0000, "Classic"),
+];
+
+/// Channels the event log itself owns; providers' own start at
+/// [`FIRST_CHANNEL`]
+pub const CHANNELS: &[(&str, u8, &str)] = &[
+    ("TraceClassic", 0, "TraceClassic"),
+    ("System", 8, "System"),
+    ("Application", 9, "Application"),
+    ("Security", 10, "Security"),
+    ("TraceLogging", 11, "TraceLogging"),
+    ("ProviderMetadata", 12, "ProviderMetadata"),
+];
+
+pub const FIRST_CHANNEL: u8 = 16;
+
+/// The value of a standard name in one of the tables
+pub fn lookup<T: Copy>(table: &[(&str, T, &str)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|&(_, v, _)| v)
+}
+
+/// The display text of a standard name
+pub fn display_name<'a, T>(table: &[(&str, T, &'a str)], name: &str) -> Option<&'a str> {
+    table
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|&(_, _, d)| d)
+}
+
+/// The display text of a standard value
+pub fn display<'a, T: PartialEq>(table: &[(&str, T, &'a str)], value: T) -> Option<&'a str> {
+    table
+        .iter()
+        .find(|(_, v, _)| *v == value)
+        .map(|&(_, _, d)| d)
+}
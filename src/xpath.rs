@@ -0,0 +1,604 @@
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+use roxmltree::{Document, Node};
+
+use crate::errors::{
+    WinEvtError, ERROR_EVT_FILTER_PARSEERR, ERROR_EVT_FILTER_UNEXPECTEDTOKEN,
+    ERROR_EVT_FILTER_UNSUPPORTEDOP, ERROR_EVT_MALFORMED_XML_TEXT,
+};
+use crate::filetime;
+use crate::source::{EventRenderer, EventSource};
+
+const TICKS_PER_MILLI: u64 = 10_000;
+
+/// The subset of XPath 1.0 the event log's query engine understands, evaluated
+/// against rendered event XML so the same filter can be used with sources
+/// that have no query engine of their own.
+///
+/// Supported are element paths with predicates (`System[Level=2]`,
+/// `Data[@Name='LogonType']`), attributes, string and number literals,
+/// `=`, `!=`, `<`, `<=`, `>`, `>=`, `and`, `or`, `not()`, `band()` and
+/// `timediff()`.
+///
+/// As in XPath, comparing a path compares each of the values it selects and
+/// holds if any does. Values that are both numbers, decimal or `0x` hex, are
+/// compared as numbers and values that are both timestamps as times, anything
+/// else as strings.
+///
+/// ```
+/// use win_events::xpath::XPathFilter;
+///
+/// let filter = XPathFilter::parse(
+///     "*[System[(EventID=4624 or EventID=4625) and band(Keywords,0x8020000000000000)]]",
+/// )
+/// .unwrap();
+///
+/// let xml = "<Event><System><EventID>4624</EventID>\
+///            <Keywords>0x8020000000000000</Keywords></System></Event>";
+/// assert!(filter.matches(xml).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPathFilter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, Op, Operand),
+    Test(Operand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Path(Path),
+    Str(String),
+    Num(i128),
+    /// Milliseconds from each time selected by the path until now
+    TimeDiff(Path),
+    /// Each number selected by the path bitwise anded with the mask
+    Band(Path, i128),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Path {
+    steps: Vec<Step>,
+    attribute: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    /// `None` for `*`
+    name: Option<String>,
+    predicates: Vec<Expr>,
+}
+
+/// A value an operand evaluates to
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    Str(String),
+    Num(i128),
+}
+
+impl XPathFilter {
+    pub fn parse(xpath: &str) -> Result<Self, WinEvtError> {
+        let mut parser = Parser {
+            tokens: tokenize(xpath)?,
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        match parser.peek() {
+            None => Ok(XPathFilter { expr }),
+            Some(tok) => Err(WinEvtError::new(
+                ERROR_EVT_FILTER_UNEXPECTEDTOKEN,
+                format!("unexpected {} in {}", tok, xpath),
+            )),
+        }
+    }
+
+    /// Whether the rendered event `xml` matches
+    pub fn matches(&self, xml: &str) -> Result<bool, WinEvtError> {
+        let doc = Document::parse(xml)
+            .map_err(|e| WinEvtError::parse(ERROR_EVT_MALFORMED_XML_TEXT, e))?;
+        Ok(self.matches_doc(&doc))
+    }
+
+    /// Whether an already parsed event matches
+    pub fn matches_doc(&self, doc: &Document) -> bool {
+        let now = filetime::from_system_time(SystemTime::now());
+        self.expr.eval(doc.root(), now)
+    }
+}
+
+impl Expr {
+    fn eval(&self, node: Node, now: u64) -> bool {
+        match self {
+            Expr::Or(a, b) => a.eval(node, now) || b.eval(node, now),
+            Expr::And(a, b) => a.eval(node, now) && b.eval(node, now),
+            Expr::Not(a) => !a.eval(node, now),
+            Expr::Test(a) => a.eval(node, now).iter().any(|atom| match atom {
+                Atom::Str(_) => true,
+                Atom::Num(n) => *n != 0,
+            }),
+            Expr::Compare(a, op, b) => {
+                let b = b.eval(node, now);
+                a.eval(node, now)
+                    .iter()
+                    .any(|a| b.iter().any(|b| op.holds(compare(a, b))))
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn eval(&self, node: Node, now: u64) -> Vec<Atom> {
+        match self {
+            Operand::Path(path) => path.eval(node, now).into_iter().map(Atom::Str).collect(),
+            Operand::Str(s) => vec![Atom::Str(s.clone())],
+            Operand::Num(n) => vec![Atom::Num(*n)],
+            Operand::TimeDiff(path) => path
+                .eval(node, now)
+                .iter()
+                .filter_map(|s| filetime::parse_system_time(s))
+                .map(|t| (now as i128 - t as i128) / TICKS_PER_MILLI as i128)
+                .map(Atom::Num)
+                .collect(),
+            Operand::Band(path, mask) => path
+                .eval(node, now)
+                .iter()
+                .filter_map(|s| number(s))
+                .map(|n| Atom::Num(n & mask))
+                .collect(),
+        }
+    }
+}
+
+impl Path {
+    /// The string value of everything the path selects from `node`
+    fn eval(&self, node: Node, now: u64) -> Vec<String> {
+        let mut nodes = vec![node];
+
+        for step in &self.steps {
+            nodes = nodes
+                .iter()
+                .flat_map(|n| n.children())
+                .filter(|n| {
+                    n.is_element()
+                        && step
+                            .name
+                            .as_ref()
+                            .map_or(true, |name| n.tag_name().name() == name)
+                })
+                .filter(|n| step.predicates.iter().all(|p| p.eval(*n, now)))
+                .collect();
+        }
+
+        match &self.attribute {
+            Some(attr) => nodes
+                .iter()
+                .filter_map(|n| n.attribute(attr.as_str()))
+                .map(str::to_string)
+                .collect(),
+            None => nodes
+                .iter()
+                .map(|n| {
+                    n.descendants()
+                        .filter(Node::is_text)
+                        .filter_map(|t| t.text())
+                        .collect::<String>()
+                        .trim()
+                        .to_string()
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Op {
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            Op::Eq => ord == Ordering::Equal,
+            Op::Ne => ord != Ordering::Equal,
+            Op::Lt => ord == Ordering::Less,
+            Op::Le => ord != Ordering::Greater,
+            Op::Gt => ord == Ordering::Greater,
+            Op::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+fn number(s: &str) -> Option<i128> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(h) => i128::from_str_radix(h, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn compare(a: &Atom, b: &Atom) -> Ordering {
+    let as_number = |atom: &Atom| match atom {
+        Atom::Num(n) => Some(*n),
+        Atom::Str(s) => number(s),
+    };
+    if let (Some(a), Some(b)) = (as_number(a), as_number(b)) {
+        return a.cmp(&b);
+    }
+
+    match (a, b) {
+        (Atom::Str(a), Atom::Str(b)) => {
+            match (
+                filetime::parse_system_time(a),
+                filetime::parse_system_time(b),
+            ) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a.as_str().cmp(b.as_str()),
+            }
+        }
+        (Atom::Str(a), Atom::Num(b)) => a.as_str().cmp(b.to_string().as_str()),
+        (Atom::Num(a), Atom::Str(b)) => a.to_string().as_str().cmp(b.as_str()),
+        (Atom::Num(a), Atom::Num(b)) => a.cmp(b),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Str(String),
+    Num(i128),
+    Op(Op),
+    At,
+    Star,
+    Slash,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Name(n) => write!(f, "{}", n),
+            Token::Str(s) => write!(f, "'{}'", s),
+            Token::Num(n) => write!(f, "{}", n),
+            Token::Op(op) => write!(f, "{:?}", op),
+            Token::At => write!(f, "@"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn parse_error<S: Into<String>>(msg: S) -> WinEvtError {
+    WinEvtError::new(ERROR_EVT_FILTER_PARSEERR, msg)
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, WinEvtError> {
+    let chars = s.char_indices().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (at, c) = chars[i];
+        i += 1;
+        let next = chars.get(i).map(|&(_, c)| c);
+
+        let tok = match c {
+            c if c.is_whitespace() => continue,
+            '@' => Token::At,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '=' => Token::Op(Op::Eq),
+            '!' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Ne)
+            }
+            '<' | '>' => {
+                let or_equal = next == Some('=');
+                if or_equal {
+                    i += 1;
+                }
+                Token::Op(match (c, or_equal) {
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    _ => Op::Ge,
+                })
+            }
+            '\'' | '"' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|&(_, q)| q == c)
+                    .ok_or_else(|| parse_error(format!("unterminated string at {}", at)))?;
+                let lit = chars[i..i + end].iter().map(|&(_, c)| c).collect();
+                i += end + 1;
+                Token::Str(lit)
+            }
+            c if c.is_ascii_digit() => {
+                let start = i - 1;
+                while i < chars.len() && chars[i].1.is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let lit = chars[start..i].iter().map(|&(_, c)| c).collect::<String>();
+                Token::Num(number(&lit).ok_or_else(|| parse_error(format!("bad number {}", lit)))?)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i - 1;
+                while i < chars.len()
+                    && (chars[i].1.is_alphanumeric() || matches!(chars[i].1, '_' | '-' | '.'))
+                {
+                    i += 1;
+                }
+                Token::Name(chars[start..i].iter().map(|&(_, c)| c).collect())
+            }
+            c => return Err(parse_error(format!("unexpected {} at {}", c, at))),
+        };
+        tokens.push(tok);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn expect(&mut self, tok: Token) -> Result<(), WinEvtError> {
+        match self.next() {
+            Some(t) if t == tok => Ok(()),
+            Some(t) => Err(WinEvtError::new(
+                ERROR_EVT_FILTER_UNEXPECTEDTOKEN,
+                format!("expected {} but found {}", tok, t),
+            )),
+            None => Err(parse_error(format!("expected {} but the query ended", tok))),
+        }
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(n)) if n == name)
+    }
+
+    fn expr(&mut self) -> Result<Expr, WinEvtError> {
+        let mut expr = self.and()?;
+        while self.is_name("or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, WinEvtError> {
+        let mut expr = self.unary()?;
+        while self.is_name("and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, WinEvtError> {
+        if self.is_name("not") && self.tokens.get(self.pos + 1) == Some(&Token::LParen) {
+            self.pos += 2;
+            let expr = self.expr()?;
+            self.expect(Token::RParen)?;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.expr()?;
+            self.expect(Token::RParen)?;
+            return Ok(expr);
+        }
+
+        let lhs = self.operand()?;
+        match self.peek() {
+            Some(&Token::Op(op)) => {
+                self.pos += 1;
+                Ok(Expr::Compare(lhs, op, self.operand()?))
+            }
+            _ => Ok(Expr::Test(lhs)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, WinEvtError> {
+        match self.peek().cloned() {
+            Some(Token::Str(s)) => {
+                self.pos += 1;
+                Ok(Operand::Str(s))
+            }
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+                Ok(Operand::Num(n))
+            }
+            Some(Token::Name(name)) if self.tokens.get(self.pos + 1) == Some(&Token::LParen) => {
+                self.pos += 2;
+                let operand = match name.as_str() {
+                    "timediff" => Operand::TimeDiff(self.path()?),
+                    "band" => {
+                        let path = self.path()?;
+                        self.expect(Token::Comma)?;
+                        match self.next() {
+                            Some(Token::Num(mask)) => Operand::Band(path, mask),
+                            _ => return Err(parse_error("band takes a number as its mask")),
+                        }
+                    }
+                    _ => {
+                        return Err(WinEvtError::new(
+                            ERROR_EVT_FILTER_UNSUPPORTEDOP,
+                            format!("unsupported function {}", name),
+                        ))
+                    }
+                };
+                self.expect(Token::RParen)?;
+                Ok(operand)
+            }
+            _ => Ok(Operand::Path(self.path()?)),
+        }
+    }
+
+    fn path(&mut self) -> Result<Path, WinEvtError> {
+        let mut path = Path {
+            steps: Vec::new(),
+            attribute: None,
+        };
+
+        loop {
+            let name = match self.next() {
+                Some(Token::At) => match self.next() {
+                    Some(Token::Name(attr)) => {
+                        path.attribute = Some(attr);
+                        return Ok(path);
+                    }
+                    _ => return Err(parse_error("expected an attribute name after @")),
+                },
+                Some(Token::Star) => None,
+                Some(Token::Name(name)) => Some(name),
+                Some(tok) => {
+                    return Err(WinEvtError::new(
+                        ERROR_EVT_FILTER_UNEXPECTEDTOKEN,
+                        format!("unexpected {}", tok),
+                    ))
+                }
+                None => return Err(parse_error("the query ended early")),
+            };
+
+            let mut predicates = Vec::new();
+            while self.peek() == Some(&Token::LBracket) {
+                self.pos += 1;
+                predicates.push(self.expr()?);
+                self.expect(Token::RBracket)?;
+            }
+            path.steps.push(Step { name, predicates });
+
+            if self.peek() != Some(&Token::Slash) {
+                return Ok(path);
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+/// Filters the events of a source through an [`XPathFilter`], rendering each
+/// one to test it.
+///
+/// For sources without a query engine of their own; with no filter every
+/// event is passed through without being rendered. Events that were rendered
+/// come out as their XML, so [`FilteredRenderer`] doesn't render them again.
+pub struct FilteredEvents<I: EventSource, R> {
+    inner: I,
+    filter: Option<XPathFilter>,
+    renderer: R,
+}
+
+impl<I: EventSource, R: EventRenderer<I::Event>> FilteredEvents<I, R> {
+    /// Parses `query`, if any, so a bad one fails straight away
+    pub fn new(inner: I, query: Option<&str>, renderer: R) -> Result<Self, WinEvtError> {
+        Ok(FilteredEvents {
+            inner,
+            filter: query.map(XPathFilter::parse).transpose()?,
+            renderer,
+        })
+    }
+
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+}
+
+/// An event from [`FilteredEvents`], as the source gave it or as it was
+/// rendered to test the filter
+pub enum FilteredEvent<E> {
+    Raw(E),
+    Rendered(String),
+}
+
+impl<I, R> Iterator for FilteredEvents<I, R>
+where
+    I: EventSource,
+    R: EventRenderer<I::Event>,
+{
+    type Item = Result<FilteredEvent<I::Event>, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filter = match &self.filter {
+            None => return Some(self.inner.next()?.map(FilteredEvent::Raw)),
+            Some(filter) => filter,
+        };
+
+        loop {
+            let event = match self.inner.next()? {
+                Ok(event) => event,
+                Err(e) => return Some(Err(e)),
+            };
+
+            let xml = match self.renderer.render(event) {
+                Ok(xml) => xml,
+                Err(e) => return Some(Err(e)),
+            };
+            match filter.matches(&xml) {
+                Ok(true) => return Some(Ok(FilteredEvent::Rendered(xml))),
+                Ok(false) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl<I, R> EventSource for FilteredEvents<I, R>
+where
+    I: EventSource,
+    R: EventRenderer<I::Event>,
+{
+    type Event = FilteredEvent<I::Event>;
+}
+
+/// Renders the events of [`FilteredEvents`], handing back the XML of those
+/// already rendered
+#[derive(Default)]
+pub struct FilteredRenderer<R>(pub R);
+
+impl<E, R: EventRenderer<E>> EventRenderer<FilteredEvent<E>> for FilteredRenderer<R> {
+    fn render(&mut self, event: FilteredEvent<E>) -> Result<String, WinEvtError> {
+        match event {
+            FilteredEvent::Raw(event) => self.0.render(event),
+            FilteredEvent::Rendered(xml) => Ok(xml),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use std::io::Cursor;
+
+use win_events::binxml::BinXmlDecoder;
+use win_events::evtx::{EvtxRecord, EvtxRecords};
+use win_events::filetime;
+
+use common::*;
+
+const SECURITY_GUID: [u8; 16] = [
+    0x25, 0x96, 0x84, 0x54, 0x78, 0x54, 0x94, 0x49, 0xa5, 0xba, 0x3e, 0x3b, 0x03, 0x28, 0xc3, 0x0d,
+];
+const SYSTEM_SID: [u8; 12] = [1, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0];
+
+fn records(chunks: &[Vec<u8>]) -> Vec<EvtxRecord> {
+    EvtxRecords::new(Cursor::new(evtx(chunks)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn data(name: &str, value: X) -> X {
+    elem("Data", vec![("Name", vec![text(name)])], vec![value])
+}
+
+/// A logon event, roughly as the Security channel defines it
+fn logon_template() -> Vec<X> {
+    vec![elem(
+        "Event",
+        vec![(
+            "xmlns",
+            vec![text(
+                "http://schemas.microsoft.com/win/2004/08/events/event",
+            )],
+        )],
+        vec![
+            elem(
+                "System",
+                vec![],
+                vec![
+                    elem(
+                        "Provider",
+                        vec![("Name", vec![X::Sub(0)]), ("Guid", vec![X::Optional(1)])],
+                        vec![],
+                    ),
+                    elem("EventID", vec![], vec![X::Sub(2)]),
+                    elem("TimeCreated", vec![("SystemTime", vec![X::Sub(3)])], vec![]),
+                    elem("EventRecordID", vec![], vec![X::Sub(4)]),
+                    elem("Keywords", vec![], vec![X::Sub(5)]),
+                    elem("Security", vec![("UserID", vec![X::Optional(6)])], vec![]),
+                ],
+            ),
+            elem(
+                "EventData",
+                vec![],
+                vec![
+                    data("SubjectUserSid", X::Sub(6)),
+                    data("LogonGuid", X::Sub(7)),
+                    data("ProcessId", X::Sub(8)),
+                    data("Blob", X::Sub(9)),
+                    data("Ports", X::Sub(10)),
+                    data("Names", X::Sub(11)),
+                    data("Empty", X::Optional(12)),
+                ],
+            ),
+        ],
+    )]
+}
+
+fn logon_values(id: u64, guid: Option<[u8; 16]>) -> Vec<(u8, Vec<u8>)> {
+    let created = filetime::parse_system_time("2019-05-17T16:16:09.2851463Z").unwrap();
+    let ports: Vec<u8> = [80u16, 443].iter().flat_map(|p| p.to_le_bytes()).collect();
+
+    vec![
+        value(TYPE_STRING, utf16("Microsoft-Windows-Security-Auditing")),
+        match guid {
+            Some(g) => value(TYPE_GUID, g.to_vec()),
+            None => value(0, vec![]),
+        },
+        value(TYPE_UINT16, 4624u16.to_le_bytes().to_vec()),
+        value(TYPE_FILETIME, created.to_le_bytes().to_vec()),
+        value(TYPE_UINT64, id.to_le_bytes().to_vec()),
+        value(
+            TYPE_HEX_INT64,
+            0x8020_0000_0000_0000u64.to_le_bytes().to_vec(),
+        ),
+        value(TYPE_SID, SYSTEM_SID.to_vec()),
+        value(TYPE_GUID, SECURITY_GUID.to_vec()),
+        value(TYPE_HEX_INT32, 0x2c4u32.to_le_bytes().to_vec()),
+        value(TYPE_BINARY, vec![0xde, 0xad, 0xbe, 0xef]),
+        value(TYPE_ARRAY | TYPE_UINT16, ports),
+        value(TYPE_ARRAY | TYPE_STRING, utf16("a\0b\0")),
+        value(0, vec![]),
+    ]
+}
+
+fn logon_xml(id: u64, guid: bool) -> String {
+    format!(
+        "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System>\
+         <Provider Name='Microsoft-Windows-Security-Auditing'{}/>\
+         <EventID>4624</EventID><TimeCreated SystemTime='2019-05-17T16:16:09.2851463Z'/>\
+         <EventRecordID>{}</EventRecordID><Keywords>0x8020000000000000</Keywords>\
+         <Security UserID='S-1-5-18'/></System><EventData>\
+         <Data Name='SubjectUserSid'>S-1-5-18</Data>\
+         <Data Name='LogonGuid'>{{54849625-5478-4994-A5BA-3E3B0328C30D}}</Data>\
+         <Data Name='ProcessId'>0x2c4</Data><Data Name='Blob'>DEADBEEF</Data>\
+         <Data Name='Ports'>80,443</Data><Data Name='Names'>a,b</Data>\
+         <Data Name='Empty'></Data></EventData></Event>",
+        if guid {
+            " Guid='{54849625-5478-4994-A5BA-3E3B0328C30D}'"
+        } else {
+            ""
+        },
+        id
+    )
+}
+
+#[test]
+fn expands_templates() {
+    let mut chunk = ChunkBuilder::default();
+    chunk.record(
+        7,
+        0,
+        0x1000,
+        &logon_template(),
+        &logon_values(7, Some(SECURITY_GUID)),
+    );
+    // The definition and names are only referred back to from here on
+    chunk.record(8, 0, 0x1000, &logon_template(), &logon_values(8, None));
+    let recs = records(&[chunk.finish()]);
+
+    let mut decoder = BinXmlDecoder::new();
+    assert_eq!(decoder.render(&recs[0]).unwrap(), logon_xml(7, true));
+    assert_eq!(decoder.render(&recs[1]).unwrap(), logon_xml(8, false));
+
+    // Nothing needs to have been cached from the record defining them
+    assert_eq!(
+        BinXmlDecoder::new().render(&recs[1]).unwrap(),
+        logon_xml(8, false)
+    );
+}
+
+#[test]
+fn templates_are_cached_per_chunk() {
+    let chunk = |tag: &str, id: u64| {
+        let mut chunk = ChunkBuilder::default();
+        let def = vec![elem(tag, vec![], vec![X::Sub(0)])];
+        chunk.record(
+            id,
+            0,
+            1,
+            &def,
+            &[value(TYPE_UINT64, id.to_le_bytes().to_vec())],
+        );
+        chunk.finish()
+    };
+
+    // Both chunks define template 1 at the same offset, differently
+    let recs = records(&[chunk("A", 1), chunk("B", 2), chunk("A", 3)]);
+    let mut decoder = BinXmlDecoder::new();
+    let xml: Vec<_> = recs.iter().map(|r| decoder.render(r).unwrap()).collect();
+    assert_eq!(xml, vec!["<A>1</A>", "<B>2</B>", "<A>3</A>"]);
+}
+
+#[test]
+fn limits_nesting() {
+    let nested = |depth: usize| {
+        let mut node = X::Sub(0);
+        for _ in 0..depth {
+            node = elem("E", vec![], vec![node]);
+        }
+        let mut chunk = ChunkBuilder::default();
+        chunk.record(1, 0, 1, &[node], &[value(TYPE_UINT64, vec![0; 8])]);
+        records(&[chunk.finish()]).remove(0)
+    };
+
+    let xml = BinXmlDecoder::new().render(&nested(100)).unwrap();
+    assert!(xml.starts_with("<E><E>") && xml.contains(">0<"));
+
+    let err = BinXmlDecoder::new().render(&nested(2000)).unwrap_err();
+    assert!(err.to_string().contains("nested too deeply"), "{}", err);
+}
+
+#[test]
+fn drops_characters_xml_cannot_hold() {
+    let mut chunk = ChunkBuilder::default();
+    let def = vec![elem(
+        "Data",
+        vec![("Name", vec![X::Sub(0)])],
+        vec![X::Sub(0)],
+    )];
+    chunk.record(
+        1,
+        0,
+        1,
+        &def,
+        &[value(TYPE_STRING, utf16("a\u{1}\tb\u{1b}\r\nc\u{ffff}'"))],
+    );
+    let recs = records(&[chunk.finish()]);
+
+    let xml = BinXmlDecoder::new().render(&recs[0]).unwrap();
+    assert_eq!(xml, "<Data Name='a\tb\r\nc&apos;'>a\tb\r\nc'</Data>");
+    let doc = roxmltree::Document::parse(&xml).unwrap();
+    // Line endings are normalised as the XML is parsed
+    assert_eq!(doc.root_element().text(), Some("a\tb\nc'"));
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use win_events::bookmark::{self, Bookmarks};
+use win_events::dumper::ChannelDumper;
+use win_events::errors::WinEvtError;
+use win_events::fixture::FixtureSource;
+use win_events::output::EventWriter;
+
+use common::event;
+
+/// The state file exactly as documented
+const STATE: &str = r#"{
+  "version": 1,
+  "channels": {
+    "Microsoft-Windows-Sysmon/Operational": 42,
+    "Security": 1234567
+  }
+}
+"#;
+
+#[test]
+fn reads_and_writes_the_state_file() {
+    let bookmarks = Bookmarks::from_json(STATE).unwrap();
+    assert_eq!(bookmarks.get("Security"), Some(1234567));
+    assert_eq!(
+        bookmarks.get("Microsoft-Windows-Sysmon/Operational"),
+        Some(42)
+    );
+    assert_eq!(bookmarks.get("System"), None);
+    assert_eq!(bookmarks.to_json(), STATE);
+
+    let mut built = Bookmarks::new();
+    built.set("Security", 1234567);
+    built.set("Microsoft-Windows-Sysmon/Operational", 40);
+    built.advance("Microsoft-Windows-Sysmon/Operational", 42);
+    built.advance("Security", 5);
+    assert_eq!(built, bookmarks);
+
+    // Hand edits
+    let edited = Bookmarks::from_json(r#"{"version": 1}"#).unwrap();
+    assert_eq!(edited.iter().count(), 0);
+    for bad in [
+        "{}",
+        r#"{"version": 2, "channels": {}}"#,
+        r#"{"version": 1, "channels": []}"#,
+        r#"{"version": 1, "channels": {"Security": -1}}"#,
+        r#"{"version": 1, "channels": {"Security": "12"}}"#,
+        "version: 1",
+    ] {
+        assert!(Bookmarks::from_json(bad).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn saves_and_loads() {
+    let dir = env::temp_dir().join(format!("wevents-bookmark-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("state.json");
+
+    assert_eq!(Bookmarks::load(&path).unwrap(), Bookmarks::new());
+
+    let bookmarks = Bookmarks::from_json(STATE).unwrap();
+    bookmarks.save(&path).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), STATE);
+    assert_eq!(Bookmarks::load(&path).unwrap(), bookmarks);
+    assert!(!dir.join("state.json.tmp").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn record_ids_come_from_the_system_block() {
+    let xml = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'>\
+               <System><EventRecordID> 17 </EventRecordID></System></Event>";
+    assert_eq!(bookmark::record_id(xml), Some(17));
+
+    // Not fooled by the text of the event
+    let xml = "<Event><System/><EventData>\
+               <Data Name='Note'>&lt;EventRecordID&gt;99&lt;/EventRecordID&gt;</Data>\
+               </EventData></Event>";
+    assert_eq!(bookmark::record_id(xml), None);
+    assert_eq!(bookmark::record_id("<EventRecordID>5</EventRecordID"), None);
+}
+
+/// Records the state file as it was when each event was written
+struct Snapshots {
+    state: PathBuf,
+    seen: Vec<(String, Option<Bookmarks>)>,
+}
+
+impl EventWriter for Snapshots {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        let state = if self.state.exists() {
+            Some(Bookmarks::load(&self.state)?)
+        } else {
+            None
+        };
+        self.seen.push((xml.to_string(), state));
+        Ok(())
+    }
+}
+
+#[test]
+fn dumps_resume_from_the_state_file() {
+    let dir = env::temp_dir().join(format!("wevents-resume-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let state = dir.join("state.json");
+
+    let channels = vec!["A".to_string(), "B".to_string()];
+    let mut source = FixtureSource::new();
+    for id in 1..=3 {
+        source.add_event("A", event("A", id));
+        source.add_event("B", event("B", id));
+    }
+
+    let dump = |source: &FixtureSource| {
+        let mut bookmarks = Bookmarks::load(&state).unwrap();
+        let mut out = Snapshots {
+            state: state.clone(),
+            seen: Vec::new(),
+        };
+        ChannelDumper::new(source)
+            .jobs(1)
+            .state(Some(&state))
+            .dump(&channels, &mut out, Some(&mut bookmarks))
+            .unwrap();
+        out.seen
+    };
+
+    // Saved once A is done, before B's events are written
+    let seen = dump(&source);
+    assert_eq!(seen.len(), 6);
+    assert_eq!(seen[2], (event("A", 3), None));
+    let mut after_a = Bookmarks::new();
+    after_a.set("A", 3);
+    assert_eq!(seen[3], (event("B", 1), Some(after_a)));
+
+    let saved = Bookmarks::load(&state).unwrap();
+    assert_eq!((saved.get("A"), saved.get("B")), (Some(3), Some(3)));
+
+    // Only what's new the next time round
+    source.add_event("B", event("B", 4));
+    source.add_event("A", event("A", 4));
+    let seen: Vec<_> = dump(&source).into_iter().map(|(xml, _)| xml).collect();
+    assert_eq!(seen, vec![event("A", 4), event("B", 4)]);
+    let saved = Bookmarks::load(&state).unwrap();
+    assert_eq!((saved.get("A"), saved.get("B")), (Some(4), Some(4)));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::Cursor;
+
+use win_events::carver::{CarveSource, CarveStats, CarvedRecords};
+use win_events::evtx::{EvtxRecord, EvtxRenderer, CHUNK_HEADER_SIZE};
+use win_events::source::{ChannelSource, EventRenderer};
+
+use common::*;
+
+/// Bytes that aren't anything, with the odd false record signature
+fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut x = seed;
+    let mut bytes: Vec<u8> = (0..len)
+        .map(|_| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 16) as u8
+        })
+        .collect();
+    bytes[len / 2..len / 2 + 4].copy_from_slice(b"**\0\0");
+    bytes
+}
+
+fn record_size(chunk: &[u8], off: usize) -> usize {
+    u32::from_le_bytes([
+        chunk[off + 4],
+        chunk[off + 5],
+        chunk[off + 6],
+        chunk[off + 7],
+    ]) as usize
+}
+
+/// A chunk of records 1-3, a record on its own, a chunk whose records no
+/// longer match their checksum and a record that only looks like one, all
+/// surrounded by noise
+fn image() -> Vec<u8> {
+    let mut image = noise(10_000, 1);
+    image.extend(simple_chunk(1..=3, 0));
+    image.extend(noise(3_000, 2));
+
+    let lone = simple_chunk(10..=10, 0);
+    let size = record_size(&lone, CHUNK_HEADER_SIZE);
+    image.extend(&lone[CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + size]);
+    image.extend(noise(3_000, 3));
+
+    let mut damaged = simple_chunk(20..=21, 0);
+    damaged[CHUNK_HEADER_SIZE + 16] ^= 1;
+    image.extend(damaged);
+    image.extend(noise(500, 4));
+
+    let mut fake = b"**\0\0".to_vec();
+    fake.extend(100u32.to_le_bytes());
+    fake.extend([0; 16]);
+    fake.extend([0x0f, 0x01, 0x01, 0x00]);
+    fake.extend([0xff; 68]);
+    fake.extend(100u32.to_le_bytes());
+    image.extend(fake);
+    image.extend(noise(500, 5));
+    image
+}
+
+fn render(recs: Vec<EvtxRecord>) -> Vec<String> {
+    let mut renderer = EvtxRenderer::default();
+    recs.into_iter()
+        .map(|r| renderer.render(r).unwrap())
+        .collect()
+}
+
+#[test]
+fn carves_chunks_and_records() {
+    let mut carved = CarvedRecords::new(Cursor::new(image()));
+    let recs: Vec<_> = carved.by_ref().collect::<Result<_, _>>().unwrap();
+
+    assert_eq!(
+        recs.iter().map(|r| r.record_id).collect::<Vec<_>>(),
+        vec![1, 2, 3, 10, 20, 21]
+    );
+    assert_eq!(
+        render(recs),
+        [1, 2, 3, 10, 20, 21]
+            .iter()
+            .map(|&id| simple_xml(id))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        carved.stats(),
+        &CarveStats {
+            chunks: 1,
+            records: 3,
+            orphans: 4,
+            undecodable: 1,
+        }
+    );
+}
+
+#[test]
+fn source_totals_the_stats() {
+    let dir = env::temp_dir().join(format!("wevents-carve-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("disk.img");
+    fs::write(&path, image()).unwrap();
+
+    let mut source = CarveSource::new();
+    source.add_image(&path);
+    let name = source.channels().unwrap().next().unwrap().unwrap();
+
+    for _ in 0..2 {
+        let events = source.events_for(&name, None).unwrap();
+        assert_eq!(events.count(), 6);
+    }
+    let stats = source.stats();
+    assert_eq!((stats.chunks, stats.records, stats.orphans), (2, 6, 8));
+
+    let filtered = source
+        .events_for(&name, Some("*[System[EventRecordID>=10]]"))
+        .unwrap();
+    assert_eq!(filtered.count(), 3);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
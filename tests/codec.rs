@@ -0,0 +1,52 @@
+use std::io::{Read, Write};
+
+use win_events::codec::Codec;
+
+const XML: &str = "<Event><System><EventID>4624</EventID></System></Event>\n";
+
+fn compress(codec: Codec, level: u32) -> Vec<u8> {
+    let mut enc = codec.encoder(Vec::new(), level).unwrap();
+    for _ in 0..100 {
+        enc.write_all(XML.as_bytes()).unwrap();
+    }
+    enc.finish().unwrap()
+}
+
+#[test]
+fn round_trips() {
+    for codec in [Codec::None, Codec::Gzip, Codec::Zstd, Codec::Xz] {
+        let data = compress(codec, codec.default_level());
+        assert_eq!(Codec::detect(&data), codec);
+
+        let mut xml = String::new();
+        Codec::decoder(&data[..])
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        assert_eq!(xml, XML.repeat(100), "{}", codec);
+    }
+}
+
+#[test]
+fn concatenated_streams() {
+    for codec in [Codec::Gzip, Codec::Zstd, Codec::Xz] {
+        let mut data = compress(codec, 1);
+        data.extend(compress(codec, 1));
+
+        let mut xml = String::new();
+        Codec::decoder(&data[..])
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        assert_eq!(xml, XML.repeat(200), "{}", codec);
+    }
+}
+
+#[test]
+fn levels() {
+    assert!(Codec::Gzip.encoder(Vec::new(), 10).is_err());
+    assert!(Codec::Zstd.encoder(Vec::new(), 0).is_err());
+    assert!(Codec::Zstd.encoder(Vec::new(), 22).is_ok());
+    assert_eq!("zst".parse::<Codec>(), Ok(Codec::Zstd));
+    assert!("lz4".parse::<Codec>().is_err());
+}
@@ -0,0 +1,330 @@
+// Rendered events and a writer collecting them, and evtx files, chunks and
+// BinXML records built byte by byte
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use win_events::errors::WinEvtError;
+use win_events::evtx::{CHUNK_HEADER_SIZE, CHUNK_SIZE, FILE_HEADER_SIZE};
+use win_events::output::EventWriter;
+
+pub const TYPE_STRING: u8 = 0x01;
+pub const TYPE_UINT16: u8 = 0x06;
+pub const TYPE_UINT32: u8 = 0x08;
+pub const TYPE_UINT64: u8 = 0x0a;
+pub const TYPE_BINARY: u8 = 0x0e;
+pub const TYPE_GUID: u8 = 0x0f;
+pub const TYPE_FILETIME: u8 = 0x11;
+pub const TYPE_SID: u8 = 0x13;
+pub const TYPE_HEX_INT32: u8 = 0x14;
+pub const TYPE_HEX_INT64: u8 = 0x15;
+pub const TYPE_ARRAY: u8 = 0x80;
+
+/// A piece of a template definition
+pub enum X {
+    Element(String, Vec<(String, Vec<X>)>, Vec<X>),
+    Text(String),
+    Sub(u16),
+    Optional(u16),
+}
+
+pub fn elem(name: &str, attrs: Vec<(&str, Vec<X>)>, children: Vec<X>) -> X {
+    X::Element(
+        name.to_string(),
+        attrs.into_iter().map(|(n, v)| (n.to_string(), v)).collect(),
+        children,
+    )
+}
+
+pub fn text(s: &str) -> X {
+    X::Text(s.to_string())
+}
+
+pub fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+/// A typed substitution value
+pub fn value(ty: u8, bytes: Vec<u8>) -> (u8, Vec<u8>) {
+    (ty, bytes)
+}
+
+/// A rendered logon event from `chan`, see [`event_with`]
+pub fn event(chan: &str, id: u64) -> String {
+    event_with(chan, id, 4624, 0)
+}
+
+/// A rendered event with the record ID, event ID and level given, as the
+/// offline sources and the mock publisher hand them out
+pub fn event_with(chan: &str, id: u64, event_id: u32, level: u8) -> String {
+    format!(
+        "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System>\
+         <EventID>{}</EventID><Level>{}</Level><EventRecordID>{}</EventRecordID>\
+         <Channel>{}</Channel></System></Event>",
+        event_id, level, id, chan
+    )
+}
+
+/// Keeps every event written to it, also when borrowed by another writer
+#[derive(Default)]
+pub struct Collect(pub Vec<String>);
+
+impl EventWriter for Collect {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        self.0.push(xml.to_string());
+        Ok(())
+    }
+}
+
+impl EventWriter for &mut Collect {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        (**self).write_event(xml)
+    }
+}
+
+/// `<Event><System><EventRecordID>` filled in from a template, the simplest
+/// record the decoder takes
+pub fn simple_template() -> Vec<X> {
+    vec![elem(
+        "Event",
+        vec![],
+        vec![elem(
+            "System",
+            vec![],
+            vec![elem("EventRecordID", vec![], vec![X::Sub(0)])],
+        )],
+    )]
+}
+
+pub fn simple_xml(id: u64) -> String {
+    format!(
+        "<Event><System><EventRecordID>{}</EventRecordID></System></Event>",
+        id
+    )
+}
+
+/// Writes the records of a chunk, keeping track of the names and templates
+/// already defined in it as the event log does
+pub struct ChunkBuilder {
+    data: Vec<u8>,
+    names: HashMap<String, u32>,
+    templates: HashMap<u32, u32>,
+    ids: Vec<u64>,
+    last_record: usize,
+}
+
+impl Default for ChunkBuilder {
+    fn default() -> Self {
+        ChunkBuilder {
+            data: vec![0; CHUNK_HEADER_SIZE],
+            names: HashMap::new(),
+            templates: HashMap::new(),
+            ids: Vec::new(),
+            last_record: 0,
+        }
+    }
+}
+
+impl ChunkBuilder {
+    fn u16(&mut self, v: u16) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn pos(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn name(&mut self, name: &str) {
+        if let Some(&off) = self.names.get(name) {
+            return self.u32(off);
+        }
+
+        let off = self.pos() + 4;
+        self.names.insert(name.to_string(), off);
+        self.u32(off);
+        self.u32(0);
+        self.u16(0);
+        self.u16(name.encode_utf16().count() as u16);
+        self.data.extend(utf16(name));
+        self.u16(0);
+    }
+
+    fn node(&mut self, node: &X) {
+        match node {
+            X::Element(name, attrs, children) => {
+                self.data.push(if attrs.is_empty() { 0x01 } else { 0x41 });
+                self.u16(0xffff);
+                self.u32(0);
+                self.name(name);
+
+                if !attrs.is_empty() {
+                    self.u32(0);
+                    for (i, (name, value)) in attrs.iter().enumerate() {
+                        self.data
+                            .push(if i + 1 < attrs.len() { 0x46 } else { 0x06 });
+                        self.name(name);
+                        value.iter().for_each(|v| self.node(v));
+                    }
+                }
+
+                if children.is_empty() {
+                    self.data.push(0x03);
+                } else {
+                    self.data.push(0x02);
+                    children.iter().for_each(|c| self.node(c));
+                    self.data.push(0x04);
+                }
+            }
+            X::Text(s) => {
+                self.data.extend([0x05, TYPE_STRING]);
+                self.u16(s.encode_utf16().count() as u16);
+                self.data.extend(utf16(s));
+            }
+            X::Sub(index) | X::Optional(index) => {
+                let token = if matches!(node, X::Sub(_)) {
+                    0x0d
+                } else {
+                    0x0e
+                };
+                self.data.push(token);
+                self.u16(*index);
+                self.data.push(TYPE_STRING);
+            }
+        }
+    }
+
+    /// Adds a record made of a template instance, defining the template inline
+    /// the first time it's used in the chunk. Returns the record's offset.
+    pub fn record(
+        &mut self,
+        id: u64,
+        written: u64,
+        template: u32,
+        def: &[X],
+        values: &[(u8, Vec<u8>)],
+    ) -> usize {
+        let start = self.data.len();
+        self.data.extend(b"**\0\0");
+        self.u32(0);
+        self.data.extend(id.to_le_bytes());
+        self.data.extend(written.to_le_bytes());
+
+        self.data.extend([0x0f, 0x01, 0x01, 0x00, 0x0c, 0x01]);
+        self.u32(template);
+        match self.templates.get(&template) {
+            Some(&off) => self.u32(off),
+            None => {
+                let off = self.pos() + 4;
+                self.templates.insert(template, off);
+                self.u32(off);
+                self.u32(0);
+                self.data.extend(template.to_le_bytes().repeat(4));
+                let size_at = self.data.len();
+                self.u32(0);
+
+                self.data.extend([0x0f, 0x01, 0x01, 0x00]);
+                def.iter().for_each(|n| self.node(n));
+                self.data.push(0x00);
+                let size = (self.data.len() - size_at - 4) as u32;
+                self.data[size_at..size_at + 4].copy_from_slice(&size.to_le_bytes());
+            }
+        }
+
+        self.u32(values.len() as u32);
+        for (ty, bytes) in values {
+            self.u16(bytes.len() as u16);
+            self.data.extend([*ty, 0]);
+        }
+        for (_, bytes) in values {
+            self.data.extend(bytes);
+        }
+        self.data.push(0x00);
+
+        let size = (self.data.len() + 4 - start) as u32;
+        self.u32(size);
+        self.data[start + 4..start + 8].copy_from_slice(&size.to_le_bytes());
+
+        self.ids.push(id);
+        self.last_record = start;
+        start
+    }
+
+    /// A record of the [`simple_template`]
+    pub fn simple(&mut self, id: u64, written: u64) -> usize {
+        let value = value(TYPE_UINT64, id.to_le_bytes().to_vec());
+        self.record(id, written, 1, &simple_template(), &[value])
+    }
+
+    /// The whole 64KiB chunk with its header and checksums filled in
+    pub fn finish(self) -> Vec<u8> {
+        let mut data = self.data;
+        let free = data.len();
+        assert!(free <= CHUNK_SIZE);
+        data.resize(CHUNK_SIZE, 0);
+
+        let first = self.ids.iter().copied().min().unwrap_or(0);
+        let last = self.ids.iter().copied().max().unwrap_or(0);
+
+        data[..8].copy_from_slice(b"ElfChnk\0");
+        data[8..16].copy_from_slice(&first.to_le_bytes());
+        data[16..24].copy_from_slice(&last.to_le_bytes());
+        data[24..32].copy_from_slice(&first.to_le_bytes());
+        data[32..40].copy_from_slice(&last.to_le_bytes());
+        data[40..44].copy_from_slice(&128u32.to_le_bytes());
+        data[44..48].copy_from_slice(&(self.last_record as u32).to_le_bytes());
+        data[48..52].copy_from_slice(&(free as u32).to_le_bytes());
+        fix_chunk_checksums(&mut data);
+        data
+    }
+}
+
+/// Recomputes a chunk's records and header checksums, in that order as the
+/// header's covers the records'
+pub fn fix_chunk_checksums(chunk: &mut [u8]) {
+    let free = u32::from_le_bytes(chunk[48..52].try_into().unwrap()) as usize;
+    let records = crc32fast::hash(&chunk[CHUNK_HEADER_SIZE..free]);
+    chunk[52..56].copy_from_slice(&records.to_le_bytes());
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&chunk[..120]);
+    hasher.update(&chunk[128..CHUNK_HEADER_SIZE]);
+    chunk[124..128].copy_from_slice(&hasher.finalize().to_le_bytes());
+}
+
+/// A file header of `block` bytes for `chunks` chunks
+pub fn file_header(block: usize, chunks: usize, next_record_id: u64, flags: u32) -> Vec<u8> {
+    let mut header = vec![0; block.max(128)];
+    header[..8].copy_from_slice(b"ElfFile\0");
+    header[16..24].copy_from_slice(&(chunks.saturating_sub(1) as u64).to_le_bytes());
+    header[24..32].copy_from_slice(&next_record_id.to_le_bytes());
+    header[32..36].copy_from_slice(&128u32.to_le_bytes());
+    header[36..38].copy_from_slice(&1u16.to_le_bytes());
+    header[38..40].copy_from_slice(&3u16.to_le_bytes());
+    header[40..42].copy_from_slice(&(block as u16).to_le_bytes());
+    header[42..44].copy_from_slice(&(chunks as u16).to_le_bytes());
+    header[120..124].copy_from_slice(&flags.to_le_bytes());
+    let crc = crc32fast::hash(&header[..120]);
+    header[124..128].copy_from_slice(&crc.to_le_bytes());
+    header
+}
+
+/// A clean evtx file holding the chunks
+pub fn evtx(chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut file = file_header(FILE_HEADER_SIZE, chunks.len(), 0, 0);
+    chunks.iter().for_each(|c| file.extend(c));
+    file
+}
+
+/// A chunk of `simple` records with consecutive IDs, written a second apart
+pub fn simple_chunk(ids: std::ops::RangeInclusive<u64>, written: u64) -> Vec<u8> {
+    let mut chunk = ChunkBuilder::default();
+    for id in ids {
+        chunk.simple(id, written + id * 10_000_000);
+    }
+    chunk.finish()
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use std::io;
+
+use win_events::bookmark::{self, Bookmarks};
+use win_events::dumper::ChannelDumper;
+use win_events::errors::{WinEvtError, ERROR_EVT_MALFORMED_XML_TEXT};
+use win_events::fixture::FixtureSource;
+use win_events::output::EventWriter;
+
+use common::{event, Collect};
+
+fn source(channels: &[String], events: u64) -> FixtureSource {
+    let mut source = FixtureSource::new();
+    for chan in channels {
+        for id in 1..=events {
+            source.add_event(chan.as_str(), event(chan, id));
+        }
+    }
+    source
+}
+
+#[test]
+fn dumps_every_channel_in_order() {
+    let channels: Vec<_> = (0..20).map(|n| format!("Channel{}", n)).collect();
+    let source = source(&channels, 1000);
+
+    let mut out = Collect::default();
+    let reports = ChannelDumper::new(&source)
+        .jobs(4)
+        .dump(&channels, &mut out, None)
+        .unwrap();
+
+    assert_eq!(out.0.len(), 20 * 1000);
+    for (report, chan) in reports.iter().zip(&channels) {
+        assert_eq!(&report.channel, chan);
+        assert_eq!(report.events, 1000);
+        assert!(report.error.is_none());
+
+        let ids: Vec<_> = out
+            .0
+            .iter()
+            .filter(|xml| xml.contains(&format!("<Channel>{}<", chan)))
+            .map(|xml| bookmark::record_id(xml).unwrap())
+            .collect();
+        assert_eq!(ids, (1..=1000).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn errors_are_per_channel() {
+    let channels = vec!["A".to_string(), "B".to_string()];
+    let source = source(&channels, 3);
+    let wanted = vec!["A".to_string(), "Missing".to_string(), "B".to_string()];
+
+    let mut out = Collect::default();
+    let reports = ChannelDumper::new(&source)
+        .dump(&wanted, &mut out, None)
+        .unwrap();
+
+    assert_eq!(out.0.len(), 6);
+    assert!(reports[0].error.is_none());
+    assert!(reports[1].error.is_some());
+    assert!(reports[2].error.is_none());
+}
+
+#[test]
+fn resumes_from_bookmarks() {
+    let channels = vec!["A".to_string(), "B".to_string()];
+    let source = source(&channels, 5);
+    let mut bookmarks = Bookmarks::default();
+    bookmarks.set("A", 3);
+
+    let mut out = Collect::default();
+    let reports = ChannelDumper::new(&source)
+        .jobs(2)
+        .dump(&channels, &mut out, Some(&mut bookmarks))
+        .unwrap();
+
+    assert_eq!(reports[0].events, 2);
+    assert_eq!(reports[1].events, 5);
+    assert_eq!(bookmarks.get("A"), Some(5));
+    assert_eq!(bookmarks.get("B"), Some(5));
+}
+
+/// Can't parse the second event of each channel, and has its output closed
+/// on reaching channel `Closed`
+#[derive(Default)]
+struct Picky(Vec<String>);
+
+impl EventWriter for Picky {
+    fn write_event(&mut self, xml: &str) -> Result<(), WinEvtError> {
+        if xml.contains("<Channel>Closed<") {
+            return Err(WinEvtError::output(io::ErrorKind::BrokenPipe.into()));
+        }
+        if bookmark::record_id(xml) == Some(2) {
+            return Err(WinEvtError::new(ERROR_EVT_MALFORMED_XML_TEXT, "bad event"));
+        }
+        self.0.push(xml.to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn skips_events_the_output_cannot_take() {
+    let channels = vec!["A".to_string(), "B".to_string()];
+    let mut bookmarks = Bookmarks::default();
+
+    let mut out = Picky::default();
+    let reports = ChannelDumper::new(&source(&channels, 3))
+        .jobs(1)
+        .dump(&channels, &mut out, Some(&mut bookmarks))
+        .unwrap();
+
+    assert_eq!(
+        out.0,
+        vec![event("A", 1), event("A", 3), event("B", 1), event("B", 3)]
+    );
+    for report in &reports {
+        assert_eq!((report.events, report.skipped), (2, 1));
+        assert!(matches!(report.error, Some(WinEvtError::Parse { .. })));
+    }
+    assert_eq!(bookmarks.get("A"), Some(3));
+
+    // Unless the output itself has failed
+    let channels = vec!["A".to_string(), "Closed".to_string()];
+    let err = ChannelDumper::new(&source(&channels, 3))
+        .jobs(1)
+        .dump(&channels, &mut Picky::default(), None)
+        .unwrap_err();
+    assert!(matches!(err, WinEvtError::Output { .. }));
+}
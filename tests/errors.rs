@@ -0,0 +1,89 @@
+use std::error::Error;
+use std::io;
+
+use win_events::errors::*;
+use win_events::event::Event;
+use win_events::metadata_cache::MetadataCache;
+use win_events::output::{EventWriter, XmlWriter};
+use win_events::xpath::XPathFilter;
+
+#[test]
+fn codes_pick_the_variant() {
+    let kind = |code| match WinEvtError::new(code, "") {
+        WinEvtError::QuerySyntax { .. } => "query",
+        WinEvtError::ChannelNotFound { .. } => "channel",
+        WinEvtError::AccessDenied { .. } => "access",
+        WinEvtError::MetadataMissing { .. } => "metadata",
+        WinEvtError::Parse { .. } => "parse",
+        WinEvtError::Output { .. } => "output",
+        WinEvtError::Api { .. } => "api",
+        WinEvtError::Io(_) => "io",
+    };
+
+    assert_eq!(kind(ERROR_EVT_INVALID_QUERY), "query");
+    assert_eq!(kind(ERROR_EVT_FILTER_INVTYPE), "query");
+    assert_eq!(kind(ERROR_EVT_CHANNEL_NOT_FOUND), "channel");
+    assert_eq!(kind(ERROR_ACCESS_DENIED), "access");
+    assert_eq!(kind(ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND), "metadata");
+    assert_eq!(kind(ERROR_EVT_MALFORMED_XML_TEXT), "parse");
+    assert_eq!(kind(ERROR_CANCELLED), "output");
+    assert_eq!(kind(ERROR_EVT_QUERY_RESULT_STALE), "api");
+
+    let e = WinEvtError::new(ERROR_EVT_CHANNEL_NOT_FOUND, "no such channel");
+    assert_eq!(e.code(), 15007);
+    assert_eq!(e.to_string(), "no such channel");
+    assert!(e.source().is_none());
+}
+
+#[test]
+fn sources_are_kept() {
+    let e = Event::from_xml("<Event>").unwrap_err();
+    assert_eq!(e.code(), ERROR_EVT_MALFORMED_XML_TEXT);
+    assert!(e.source().unwrap().is::<roxmltree::Error>());
+
+    let e = MetadataCache::from_slice(b"{ nope").unwrap_err();
+    assert_eq!(e.code(), ERROR_INVALID_DATA);
+    assert!(e.source().is_some());
+
+    let e = XPathFilter::parse("*[System[EventID=]]").unwrap_err();
+    assert!(matches!(e, WinEvtError::QuerySyntax { .. }));
+
+    let e = WinEvtError::from(io::Error::from_raw_os_error(2));
+    assert_eq!(e.code(), 2);
+    assert!(e.source().unwrap().is::<io::Error>());
+}
+
+struct Closed;
+
+impl io::Write for Closed {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_failures_are_output_errors() {
+    let e = XmlWriter::new(Closed).write_event("<Event/>").unwrap_err();
+    assert!(matches!(e, WinEvtError::Output { .. }));
+    assert_eq!(
+        e.source()
+            .and_then(|e| e.downcast_ref::<io::Error>())
+            .map(io::Error::kind),
+        Some(io::ErrorKind::BrokenPipe)
+    );
+}
+
+#[test]
+fn describes_event_log_codes() {
+    assert_eq!(
+        describe(ERROR_EVT_CHANNEL_NOT_FOUND),
+        Some("channel not found")
+    );
+    assert_eq!(describe(15006), None);
+    assert_eq!(describe(ERROR_INVALID_DATA), None);
+    assert_eq!(EVT_ERRORS.len(), 38);
+}
@@ -0,0 +1,200 @@
+use win_events::event::{Data, Element, Event};
+
+/// A logon as the Security channel renders it, message included
+const LOGON: &str = r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event">
+  <System>
+    <Provider Name="Microsoft-Windows-Security-Auditing" Guid="{54849625-5478-4994-A5BA-3E3B0328C30D}" />
+    <EventID>4624</EventID>
+    <Version>2</Version>
+    <Level>0</Level>
+    <Task>12544</Task>
+    <Opcode>0</Opcode>
+    <Keywords>0x8020000000000000</Keywords>
+    <TimeCreated SystemTime="2019-05-17T16:16:09.2851463Z" />
+    <EventRecordID>57622</EventRecordID>
+    <Correlation ActivityID="{F7E6DA3A-0C4B-0001-4BDB-E6F74B0CD501}" />
+    <Execution ProcessID="708" ThreadID="4412" />
+    <Channel>Security</Channel>
+    <Computer>DESKTOP-1</Computer>
+    <Security />
+  </System>
+  <EventData>
+    <Data Name="SubjectUserSid">S-1-5-18</Data>
+    <Data Name="SubjectUserName">DESKTOP-1$</Data>
+    <Data Name="TargetUserName">SYSTEM</Data>
+    <Data Name="LogonType">5</Data>
+    <Data Name="LogonGuid">{00000000-0000-0000-0000-000000000000}</Data>
+    <Data Name="IpAddress">-</Data>
+  </EventData>
+  <RenderingInfo Culture="en-US">
+    <Message>An account was successfully logged on.</Message>
+    <Level>Information</Level>
+    <Task>Logon</Task>
+    <Opcode>Info</Opcode>
+    <Channel>Security</Channel>
+    <Provider>Microsoft Windows security auditing.</Provider>
+    <Keywords>
+      <Keyword>Audit Success</Keyword>
+    </Keywords>
+  </RenderingInfo>
+</Event>"#;
+
+/// A process creation as Sysmon renders it
+const SYSMON: &str = r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event"><System><Provider Name="Microsoft-Windows-Sysmon" Guid="{5770385F-C22A-43E0-BF4C-06F5698FFBD9}"/><EventID>1</EventID><Version>5</Version><Level>4</Level><Task>1</Task><Opcode>0</Opcode><Keywords>0x8000000000000000</Keywords><TimeCreated SystemTime="2021-03-02T09:41:27.9260000Z"/><EventRecordID>18293</EventRecordID><Correlation/><Execution ProcessID="3164" ThreadID="4292"/><Channel>Microsoft-Windows-Sysmon/Operational</Channel><Computer>ws01.corp.local</Computer><Security UserID="S-1-5-18"/></System><EventData><Data Name="RuleName">-</Data><Data Name="UtcTime">2021-03-02 09:41:27.925</Data><Data Name="ProcessGuid">{8BA8A1B6-0867-603E-2E01-000000001500}</Data><Data Name="ProcessId">7424</Data><Data Name="Image">C:\Windows\System32\cmd.exe</Data><Data Name="CommandLine">"C:\Windows\system32\cmd.exe" /c "echo &lt;hi&gt;"</Data><Data Name="Hashes">SHA256=B99D114B267FFD068C3289199B6DF95C9A1D1A9B9C1F8E4B9D2E8A0C6F2E6D1A</Data><Data Name="ParentImage"></Data></EventData></Event>"#;
+
+#[test]
+fn parses_a_security_event() {
+    let event: Event = LOGON.parse().unwrap();
+    let sys = &event.system;
+
+    assert_eq!(
+        sys.provider.name.as_deref(),
+        Some("Microsoft-Windows-Security-Auditing")
+    );
+    assert_eq!(
+        sys.provider.guid.as_deref(),
+        Some("{54849625-5478-4994-A5BA-3E3B0328C30D}")
+    );
+    assert_eq!(sys.provider.event_source_name, None);
+    assert_eq!(sys.event_id, Some(4624));
+    assert_eq!(sys.qualifiers, None);
+    assert_eq!(
+        (sys.version, sys.level, sys.task, sys.opcode),
+        (Some(2), Some(0), Some(12544), Some(0))
+    );
+    assert_eq!(sys.keywords, Some(0x8020_0000_0000_0000));
+    assert_eq!(
+        sys.time_created.as_deref(),
+        Some("2019-05-17T16:16:09.2851463Z")
+    );
+    assert_eq!(sys.event_record_id, Some(57622));
+    assert_eq!(
+        sys.correlation.activity_id.as_deref(),
+        Some("{F7E6DA3A-0C4B-0001-4BDB-E6F74B0CD501}")
+    );
+    assert_eq!(sys.correlation.related_activity_id, None);
+    assert_eq!(
+        (sys.execution.process_id, sys.execution.thread_id),
+        (Some(708), Some(4412))
+    );
+    assert_eq!(sys.channel.as_deref(), Some("Security"));
+    assert_eq!(sys.user_id, None);
+
+    assert_eq!(event.event_data.len(), 6);
+    assert_eq!(event.data("TargetUserName"), Some("SYSTEM"));
+    assert_eq!(event.data("IpAddress"), Some("-"));
+    assert_eq!(event.data("Missing"), None);
+    assert_eq!(event.binary, None);
+    assert!(event.user_data.is_empty());
+
+    let ri = event.rendering_info.unwrap();
+    assert_eq!(ri.culture.as_deref(), Some("en-US"));
+    assert_eq!(
+        ri.message.as_deref(),
+        Some("An account was successfully logged on.")
+    );
+    assert_eq!(ri.task.as_deref(), Some("Logon"));
+    assert_eq!(ri.keywords, vec!["Audit Success"]);
+}
+
+#[test]
+fn parses_a_sysmon_event() {
+    let event = Event::from_xml(SYSMON).unwrap();
+
+    assert_eq!(event.system.event_id, Some(1));
+    assert_eq!(event.system.user_id.as_deref(), Some("S-1-5-18"));
+    assert_eq!(
+        event.system.channel.as_deref(),
+        Some("Microsoft-Windows-Sysmon/Operational")
+    );
+    // An empty <Correlation/> has neither ID
+    assert_eq!(event.system.correlation, Default::default());
+    assert_eq!(event.rendering_info, None);
+
+    assert_eq!(event.event_data.len(), 8);
+    assert_eq!(
+        event.data("CommandLine"),
+        Some(r#""C:\Windows\system32\cmd.exe" /c "echo <hi>""#)
+    );
+    assert_eq!(event.data("Image"), Some(r"C:\Windows\System32\cmd.exe"));
+    assert_eq!(event.data("ParentImage"), Some(""));
+}
+
+#[test]
+fn missing_fields_are_none() {
+    let event = Event::from_xml(
+        "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'>\
+         <System><Provider Name='Application Error'/>\
+         <EventID Qualifiers='0'>1000</EventID><Level>2</Level>\
+         <Keywords>not hex</Keywords><EventRecordID>x</EventRecordID></System>\
+         <EventData><Data>first</Data><Data>second</Data>\
+         <Binary>DEADBEEF</Binary></EventData></Event>",
+    )
+    .unwrap();
+    let sys = &event.system;
+
+    assert_eq!((sys.event_id, sys.qualifiers), (Some(1000), Some(0)));
+    assert_eq!(sys.level, Some(2));
+    assert_eq!((sys.version, sys.task, sys.opcode), (None, None, None));
+    // Unparseable values are left out rather than failing the event
+    assert_eq!((sys.keywords, sys.event_record_id), (None, None));
+    assert_eq!((&sys.time_created, &sys.channel), (&None, &None));
+
+    assert_eq!(
+        event.event_data,
+        vec![
+            Data {
+                name: None,
+                value: "first".to_string()
+            },
+            Data {
+                name: None,
+                value: "second".to_string()
+            },
+        ]
+    );
+    assert_eq!(event.binary.as_deref(), Some("DEADBEEF"));
+
+    // Nothing but the root is required
+    assert_eq!(Event::from_xml("<Event/>").unwrap(), Event::default());
+    assert!(Event::from_xml("<Events/>").is_err());
+    assert!(Event::from_xml("<Event>").is_err());
+}
+
+#[test]
+fn keeps_all_of_user_data() {
+    let event = Event::from_xml(
+        "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'>\
+         <System><EventID>1102</EventID></System><UserData>\
+         <LogFileCleared xmlns='http://manifests.microsoft.com/win/2004/08/windows/eventlog'>\
+         <SubjectUserSid>S-1-5-21-1</SubjectUserSid><SubjectUserName>admin</SubjectUserName>\
+         </LogFileCleared>\
+         <Extra Kind='note'>more</Extra></UserData></Event>",
+    )
+    .unwrap();
+
+    let leaf = |name: &str, text: &str| Element {
+        name: name.to_string(),
+        text: text.to_string(),
+        ..Element::default()
+    };
+    assert_eq!(
+        event.user_data,
+        vec![
+            Element {
+                name: "LogFileCleared".to_string(),
+                children: vec![
+                    leaf("SubjectUserSid", "S-1-5-21-1"),
+                    leaf("SubjectUserName", "admin"),
+                ],
+                ..Element::default()
+            },
+            Element {
+                name: "Extra".to_string(),
+                attrs: vec![("Kind".to_string(), "note".to_string())],
+                text: "more".to_string(),
+                children: vec![],
+            },
+        ]
+    );
+}
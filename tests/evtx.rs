@@ -0,0 +1,221 @@
+mod common;
+
+use std::io::Cursor;
+
+use win_events::evtx::{
+    CorruptChunks, EvtxRecords, ValidationReport, CHUNK_HEADER_SIZE, CHUNK_SIZE, FILE_HEADER_SIZE,
+};
+use win_events::filetime;
+
+use common::*;
+
+fn records(file: Vec<u8>) -> Vec<(u64, String)> {
+    EvtxRecords::new(Cursor::new(file))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .map(|r| (r.record_id, filetime::to_system_time(r.written)))
+        .collect()
+}
+
+#[test]
+fn reads_records_in_order() {
+    let written = filetime::parse_system_time("2019-05-17T16:16:00.0000000Z").unwrap();
+    let file = evtx(&[simple_chunk(1..=3, written), simple_chunk(4..=5, written)]);
+
+    let reader = EvtxRecords::new(Cursor::new(file.clone())).unwrap();
+    assert_eq!(reader.header.chunk_count, 2);
+    assert_eq!(reader.header.major_version, 3);
+    assert!(!reader.header.is_dirty());
+
+    assert_eq!(
+        records(file),
+        vec![
+            (1, "2019-05-17T16:16:01.0000000Z".to_string()),
+            (2, "2019-05-17T16:16:02.0000000Z".to_string()),
+            (3, "2019-05-17T16:16:03.0000000Z".to_string()),
+            (4, "2019-05-17T16:16:04.0000000Z".to_string()),
+            (5, "2019-05-17T16:16:05.0000000Z".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn follows_the_header() {
+    let chunks = [simple_chunk(1..=2, 0), simple_chunk(3..=4, 0)];
+    let ids = |file: Vec<u8>| {
+        records(file)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>()
+    };
+
+    // A header block other than the usual 4KiB
+    let mut file = file_header(1024, 2, 5, 0);
+    chunks.iter().for_each(|c| file.extend(c));
+    assert_eq!(ids(file), vec![1, 2, 3, 4]);
+
+    // Chunks past the count are left alone, unless the file wasn't closed
+    // cleanly and the count may be out of date
+    let mut file = file_header(4096, 1, 3, 0);
+    chunks.iter().for_each(|c| file.extend(c));
+    assert_eq!(ids(file.clone()), vec![1, 2]);
+
+    let mut dirty = file_header(4096, 1, 3, 0x1);
+    dirty.extend(&file[4096..]);
+    assert_eq!(ids(dirty), vec![1, 2, 3, 4]);
+
+    // Preallocated chunks are skipped
+    let mut file = evtx(&chunks);
+    file.extend(vec![0; CHUNK_SIZE]);
+    file[42] = 3;
+    assert_eq!(ids(file), vec![1, 2, 3, 4]);
+
+    assert!(EvtxRecords::new(Cursor::new(file_header(64, 0, 0, 0))).is_err());
+    assert!(EvtxRecords::new(Cursor::new(b"ElfChnk\0".to_vec())).is_err());
+}
+
+const CHUNK_1: usize = FILE_HEADER_SIZE;
+const CHUNK_2: usize = FILE_HEADER_SIZE + CHUNK_SIZE;
+const CHUNK_3: usize = FILE_HEADER_SIZE + 2 * CHUNK_SIZE;
+
+/// Four chunks of two records each
+fn four_chunks() -> Vec<u8> {
+    evtx(&[
+        simple_chunk(1..=2, 0),
+        simple_chunk(3..=4, 0),
+        simple_chunk(5..=6, 0),
+        simple_chunk(7..=8, 0),
+    ])
+}
+
+fn validate(file: Vec<u8>) -> ValidationReport {
+    EvtxRecords::new(Cursor::new(file))
+        .unwrap()
+        .validate()
+        .unwrap()
+}
+
+#[test]
+fn checks_each_checksum() {
+    let report = validate(four_chunks());
+    assert!(report.is_ok());
+    assert_eq!(report.chunks.len(), 4);
+
+    // Unused bytes within the file header
+    let mut file = four_chunks();
+    file[100] ^= 1;
+    let report = validate(file);
+    assert!(!report.file_header_ok);
+    assert!(!report.is_ok());
+    assert_eq!(report.corrupt_chunks().count(), 0);
+
+    // Unused bytes within a chunk header
+    let mut file = four_chunks();
+    file[CHUNK_2 + 100] ^= 1;
+    let report = validate(file);
+    let bad: Vec<_> = report.corrupt_chunks().collect();
+    assert_eq!(bad.len(), 1);
+    assert_eq!((bad[0].index, bad[0].offset), (1, CHUNK_2 as u64));
+    assert!(!bad[0].header_ok && bad[0].records_ok);
+
+    // The timestamp of the chunk's first record
+    let mut file = four_chunks();
+    file[CHUNK_3 + CHUNK_HEADER_SIZE + 16] ^= 1;
+    let report = validate(file);
+    let bad: Vec<_> = report.corrupt_chunks().collect();
+    assert_eq!(bad.len(), 1);
+    assert_eq!((bad[0].index, bad[0].first_record_id), (2, 5));
+    assert!(bad[0].header_ok && !bad[0].records_ok);
+}
+
+#[test]
+fn unreadable_chunks_are_reported() {
+    let mut file = four_chunks();
+    file[CHUNK_2..CHUNK_2 + 8].copy_from_slice(b"Trashed!");
+    let report = validate(file);
+    assert!(report.file_header_ok);
+    assert!(!report.is_ok());
+    let bad: Vec<_> = report.corrupt_chunks().collect();
+    assert_eq!(bad.len(), 1);
+    assert_eq!((bad[0].index, bad[0].offset), (1, CHUNK_2 as u64));
+    assert!(bad[0].error.as_deref().unwrap().contains("signature"));
+
+    // A chunk cut short at the end of the file
+    let mut file = four_chunks();
+    file.truncate(file.len() - 100);
+    let report = validate(file);
+    assert!(!report.is_ok());
+    assert_eq!(report.corrupt_chunks().next().unwrap().index, 3);
+}
+
+/// The records read with each way of handling corruption, `0` standing in
+/// for an error
+fn read(file: &[u8], validation: CorruptChunks) -> (Vec<u64>, ValidationReport) {
+    let mut records = EvtxRecords::new(Cursor::new(file.to_vec()))
+        .unwrap()
+        .with_validation(validation)
+        .unwrap();
+    let ids = records
+        .by_ref()
+        .map(|r| r.map_or(0, |r| r.record_id))
+        .collect();
+    (ids, records.report().clone())
+}
+
+#[test]
+fn handles_corrupt_chunks() {
+    // Bad records in the second chunk, a trashed signature on the third
+    let mut file = four_chunks();
+    file[CHUNK_1 + CHUNK_SIZE + CHUNK_HEADER_SIZE + 16] ^= 1;
+    file[CHUNK_3..CHUNK_3 + 8].copy_from_slice(b"Trashed!");
+
+    let (ids, report) = read(&file, CorruptChunks::Ignore);
+    assert_eq!(ids, vec![1, 2, 3, 4, 0, 7, 8]);
+    assert!(report.chunks.is_empty());
+
+    let (ids, report) = read(&file, CorruptChunks::Skip);
+    assert_eq!(ids, vec![1, 2, 7, 8]);
+    assert_eq!(
+        report.corrupt_chunks().map(|c| c.index).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    let (ids, report) = read(&file, CorruptChunks::Warn);
+    assert_eq!(ids, vec![1, 2, 3, 4, 7, 8]);
+    assert_eq!(report.corrupt_chunks().count(), 2);
+
+    let (ids, report) = read(&file, CorruptChunks::Abort);
+    assert_eq!(ids, vec![1, 2, 0]);
+    assert_eq!(report.chunks.len(), 2);
+
+    // Only the unreadable chunk
+    let mut file = four_chunks();
+    file[CHUNK_2..CHUNK_2 + 8].copy_from_slice(b"Trashed!");
+    assert_eq!(read(&file, CorruptChunks::Skip).0, vec![1, 2, 5, 6, 7, 8]);
+    assert_eq!(read(&file, CorruptChunks::Abort).0, vec![1, 2, 0]);
+
+    // A bad file header
+    let mut file = four_chunks();
+    file[100] ^= 1;
+    assert_eq!(
+        read(&file, CorruptChunks::Skip).0,
+        (1..=8).collect::<Vec<_>>()
+    );
+    assert!(EvtxRecords::new(Cursor::new(file))
+        .unwrap()
+        .with_validation(CorruptChunks::Abort)
+        .is_err());
+}
+
+#[test]
+fn skips_runs_of_corrupt_chunks() {
+    let mut chunks = vec![simple_chunk(1..=1, 0)];
+    let mut bad = simple_chunk(2..=2, 0);
+    bad[CHUNK_HEADER_SIZE + 16] ^= 1;
+    chunks.extend(std::iter::repeat_n(bad, 500));
+    chunks.push(simple_chunk(3..=3, 0));
+
+    let (ids, report) = read(&evtx(&chunks), CorruptChunks::Skip);
+    assert_eq!(ids, vec![1, 3]);
+    assert_eq!(report.corrupt_chunks().count(), 500);
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+
+use win_events::codec::Codec;
+use win_events::dumper::ChannelDumper;
+use win_events::fixture::FixtureSource;
+use win_events::output::{EventWriter, XmlWriter};
+use win_events::source::{ChannelSource, EventRenderer};
+
+use common::event;
+
+fn channels<S: ChannelSource>(source: &S) -> Vec<String> {
+    source
+        .channels()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+fn dump<S: ChannelSource + Sync>(source: &S, query: Option<&str>) -> Vec<String> {
+    let mut xml = Vec::new();
+    let mut out = XmlWriter::new(&mut xml);
+    ChannelDumper::new(source)
+        .jobs(2)
+        .query(query)
+        .dump(&channels(source), &mut out, None)
+        .unwrap();
+    out.finish().unwrap();
+
+    String::from_utf8(xml)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn dumps_a_fixture_dir() {
+    let dir = env::temp_dir().join(format!("wevents-fixture-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    // One event per line, as the dumper writes them
+    let security = (1..=3)
+        .map(|id| event("Security", id) + "\n")
+        .collect::<String>();
+    fs::write(dir.join("Security.xml"), security).unwrap();
+
+    // Wrapped in <Events>, as wevtutil exports them, and compressed
+    let sysmon = "Microsoft-Windows-Sysmon/Operational";
+    let mut enc = Codec::Zstd
+        .encoder(
+            fs::File::create(dir.join("Microsoft-Windows-Sysmon%4Operational.xml.zst")).unwrap(),
+            3,
+        )
+        .unwrap();
+    write!(
+        enc,
+        "<Events>{}{}</Events>",
+        event(sysmon, 7),
+        event(sysmon, 8)
+    )
+    .unwrap();
+    enc.finish().unwrap();
+
+    fs::write(dir.join("notes.txt"), "not a fixture").unwrap();
+
+    let source = FixtureSource::from_dir(&dir).unwrap();
+    assert_eq!(channels(&source), vec![sysmon, "Security"]);
+
+    let events = dump(&source, None);
+    assert_eq!(events.len(), 5);
+    assert!(events.contains(&event("Security", 2)));
+    assert!(events.contains(&event(sysmon, 8)));
+
+    let filtered = dump(&source, Some("*[System[EventRecordID>=3]]"));
+    assert_eq!(filtered.len(), 3);
+    assert!(!filtered.contains(&event("Security", 2)));
+
+    // A dump loads back into the same channels
+    let path = dir.join("events.xml");
+    fs::write(&path, events.join("\n")).unwrap();
+    let reloaded = FixtureSource::from_dump(&path).unwrap();
+    assert_eq!(channels(&reloaded), channels(&source));
+
+    let mut again = dump(&reloaded, None);
+    let mut events = events;
+    again.sort();
+    events.sort();
+    assert_eq!(again, events);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unknown_channels_are_errors() {
+    let mut source = FixtureSource::new();
+    source.add_channel("Empty");
+
+    assert_eq!(source.events_for("Empty", None).unwrap().count(), 0);
+    assert!(source.events_for("Missing", None).is_err());
+}
+
+#[test]
+fn self_closing_events_stand_alone() {
+    let mut source = FixtureSource::new();
+    source.add_xml(
+        "Security",
+        &format!(
+            "<Events><Event/><Event xmlns='a>b'/>{}</Events>",
+            event("Security", 1)
+        ),
+    );
+
+    let mut rend = source.renderer();
+    let events: Vec<_> = source
+        .events_for("Security", None)
+        .unwrap()
+        .map(|e| rend.render(e.unwrap()).unwrap())
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "<Event/>".to_string(),
+            "<Event xmlns='a>b'/>".to_string(),
+            event("Security", 1)
+        ]
+    );
+}
@@ -0,0 +1,234 @@
+use win_events::manifest::Manifest;
+use win_events::pub_metadata::{Channel, EventMetadata, OpCode};
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<instrumentationManifest xmlns="http://schemas.microsoft.com/win/2004/08/events"
+    xmlns:win="http://manifests.microsoft.com/win/2004/08/windows/events"
+    xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <instrumentation>
+    <events>
+      <provider name="Contoso-Backup" guid="{A1B2C3D4-0000-4000-8000-000000000001}"
+          symbol="CONTOSO_BACKUP" resourceFileName="%ProgramFiles%\Contoso\backup.exe"
+          messageFileName="%ProgramFiles%\Contoso\backup.exe" message="$(string.Provider)">
+        <channels>
+          <importChannel name="Application" chid="app"/>
+          <unknownChannelKind name="Ignored"/>
+          <channel name="Contoso-Backup/Operational" chid="op" type="Operational" enabled="true"/>
+          <channel name="Contoso-Backup/Debug" type="Debug" value="20"/>
+        </channels>
+        <levels>
+          <level name="Chatty" value="16" message="$(string.Level.Chatty)"/>
+        </levels>
+        <tasks>
+          <task name="Backup" value="1" eventGUID="{A1B2C3D4-0000-4000-8000-0000000000F1}" message="$(string.Task.Backup)">
+            <opcodes>
+              <opcode name="Resume" value="10"/>
+            </opcodes>
+          </task>
+        </tasks>
+        <opcodes>
+          <opcode name="Verify" value="11"/>
+        </opcodes>
+        <keywords>
+          <keyword name="Disk" mask="0x1"/>
+          <keyword name="Network" mask="0x2"/>
+        </keywords>
+        <templates>
+          <template tid="T_Backup">
+            <data name="Path" inType="win:UnicodeString" outType="xs:string"/>
+            <data name="Files" inType="win:UInt32"/>
+          </template>
+        </templates>
+        <events>
+          <event value="100" version="1" channel="op" level="win:Informational" task="Backup"
+              opcode="win:Start" keywords="Disk Network" template="T_Backup" message="$(string.Event.100)"/>
+          <event value="101" channel="Contoso-Backup/Debug" level="Chatty" task="Backup" opcode="Resume"/>
+          <event value="102" channel="app" level="win:Error" opcode="Verify" keywords="win:AuditFailure"/>
+        </events>
+      </provider>
+    </events>
+  </instrumentation>
+  <localization>
+    <resources culture="en-US">
+      <stringTable>
+        <string id="Provider" value="Contoso Backup"/>
+        <string id="Level.Chatty" value="Chatty"/>
+        <string id="Task.Backup" value="Backup"/>
+        <string id="Event.100" value="Backed up %2 files from %1.%n"/>
+      </stringTable>
+    </resources>
+    <resources culture="de-DE">
+      <stringTable>
+        <string id="Event.100" value="%2 Dateien aus %1 gesichert.%n"/>
+      </stringTable>
+    </resources>
+  </localization>
+</instrumentationManifest>
+"#;
+
+#[test]
+fn reads_a_provider() {
+    let manifest = Manifest::parse(MANIFEST).unwrap();
+    let provider = manifest.provider("contoso-backup").unwrap();
+    assert_eq!(
+        manifest.provider("a1b2c3d4-0000-4000-8000-000000000001"),
+        Some(provider)
+    );
+    let meta = &provider.metadata;
+
+    assert_eq!(
+        manifest.message(provider, meta.message_id.unwrap(), "en-US"),
+        Some("Contoso Backup")
+    );
+    assert_eq!(
+        meta.channels
+            .iter()
+            .map(|c| (c.name.clone().unwrap(), c.id, c.imported))
+            .collect::<Vec<_>>(),
+        vec![
+            ("Application".to_string(), Some(9), true),
+            ("Contoso-Backup/Operational".to_string(), Some(16), false),
+            ("Contoso-Backup/Debug".to_string(), Some(20), false),
+        ]
+    );
+    assert_eq!(
+        meta.channels[1],
+        Channel {
+            name: Some("Contoso-Backup/Operational".into()),
+            index: Some(1),
+            id: Some(16),
+            imported: false,
+            message_id: None,
+        }
+    );
+    assert_eq!(meta.levels[0].id, Some(16));
+    assert_eq!(meta.tasks[0].value, Some(1));
+    assert_eq!(
+        meta.opcodes,
+        vec![
+            OpCode {
+                name: Some("Resume".into()),
+                opcode_value: Some(10),
+                task_id: Some(1),
+                message_id: None,
+            },
+            OpCode {
+                name: Some("Verify".into()),
+                opcode_value: Some(11),
+                task_id: Some(0),
+                message_id: None,
+            },
+        ]
+    );
+    assert_eq!(meta.keywords.len(), 2);
+}
+
+#[test]
+fn resolves_event_references() {
+    let manifest = Manifest::parse(MANIFEST).unwrap();
+    let events = &manifest.providers[0].metadata.events;
+
+    assert_eq!(
+        events[0],
+        EventMetadata {
+            id: 100,
+            version: 1,
+            channel: 16,
+            level: 4,
+            opcode: 1,
+            task: 1,
+            keywords: 0x3,
+            message_id: events[0].message_id,
+            template: Some(
+                "<template xmlns=\"http://schemas.microsoft.com/win/2004/08/events\">\
+                 <data name=\"Path\" inType=\"win:UnicodeString\" outType=\"xs:string\"/>\
+                 <data name=\"Files\" inType=\"win:UInt32\"/>\
+                 </template>"
+                    .into()
+            ),
+        }
+    );
+    assert!(events[0].message_id.is_some());
+
+    assert_eq!(
+        (events[1].channel, events[1].level, events[1].opcode),
+        (20, 16, 10)
+    );
+    assert_eq!(
+        (
+            events[2].channel,
+            events[2].level,
+            events[2].opcode,
+            events[2].keywords
+        ),
+        (9, 2, 11, 0x0010_0000_0000_0000)
+    );
+}
+
+#[test]
+fn formats_event_messages() {
+    let manifest = Manifest::parse(MANIFEST).unwrap();
+    let event = |id| {
+        format!(
+            r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event">
+                <System><Provider Name="Contoso-Backup"/><EventID>{}</EventID><Version>1</Version></System>
+                <EventData><Data Name="Path">D:\</Data><Data Name="Files">42</Data></EventData>
+            </Event>"#,
+            id
+        )
+    };
+
+    assert_eq!(
+        manifest
+            .format_event(&event(100), "en-US")
+            .unwrap()
+            .as_deref(),
+        Some("Backed up 42 files from D:\\.\r\n")
+    );
+    assert_eq!(
+        manifest
+            .format_event(&event(100), "de-DE")
+            .unwrap()
+            .as_deref(),
+        Some("42 Dateien aus D:\\ gesichert.\r\n")
+    );
+    assert_eq!(manifest.format_event(&event(101), "en-US").unwrap(), None);
+    assert_eq!(manifest.format_event(&event(999), "en-US").unwrap(), None);
+}
+
+#[test]
+fn rejects_undefined_references() {
+    for (from, to) in [
+        (r#"level="Chatty""#, r#"level="Loud""#),
+        (r#"opcode="Verify""#, r#"opcode="Resume""#),
+        (r#"keywords="Disk Network""#, r#"keywords="Disk Tape""#),
+        (r#"channel="op""#, r#"channel="admin""#),
+        (r#"template="T_Backup""#, r#"template="T_Restore""#),
+        (
+            r#"message="$(string.Event.100)""#,
+            r#"message="$(string.Event.999)""#,
+        ),
+        (r#"value="20""#, r#"value="0x100""#),
+    ] {
+        assert!(MANIFEST.contains(from), "{}", from);
+        let err = Manifest::parse(&MANIFEST.replace(from, to)).unwrap_err();
+        assert!(err.to_string().contains("line "), "{}", err);
+    }
+
+    // More unnumbered channels than there are values left for; the
+    // Operational channel already takes the first
+    let debug = r#"<channel name="Contoso-Backup/Debug" type="Debug" value="20"/>"#;
+    assert!(MANIFEST.contains(debug));
+    let with_channels = |n: usize| {
+        let extra: String = (0..n)
+            .map(|i| format!(r#"<channel name="Contoso-Backup/C{}"/>"#, i))
+            .collect();
+        MANIFEST.replace(debug, &(extra + debug))
+    };
+    assert!(Manifest::parse(&with_channels(239)).is_ok());
+    let err = Manifest::parse(&with_channels(240)).unwrap_err();
+    assert!(err.to_string().contains("too many channels"), "{}", err);
+
+    assert!(Manifest::parse("<QueryList/>").is_err());
+    assert!(Manifest::parse("<instrumentationManifest>").is_err());
+}
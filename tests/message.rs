@@ -0,0 +1,102 @@
+use win_events::message::{event_data, format_message, format_message_with_params};
+
+#[test]
+fn inserts() {
+    assert_eq!(
+        format_message("Process %1 started by %2.", &["cmd.exe", "SYSTEM"]),
+        "Process cmd.exe started by SYSTEM."
+    );
+    assert_eq!(format_message("%2%1", &["a", "b"]), "ba");
+    assert_eq!(
+        format_message(
+            "%10 %1",
+            &(1..=10).map(|n| n.to_string()).collect::<Vec<_>>()
+        ),
+        "10 1"
+    );
+    assert_eq!(format_message("%3 missing", &["a"]), "%3 missing");
+}
+
+#[test]
+fn escapes() {
+    assert_eq!(
+        format_message("a%nb%tc%rd%%e%.%!% f", &[] as &[&str]),
+        "a\r\nb\tc\rd%e.! f"
+    );
+    assert_eq!(format_message("kept%0 dropped", &[] as &[&str]), "kept");
+    assert_eq!(format_message("trailing %", &[] as &[&str]), "trailing %");
+}
+
+#[test]
+fn printf_specs() {
+    let fmt = |spec: &str, value: &str| format_message(&format!("%1!{}!", spec), &[value]);
+
+    assert_eq!(fmt("s", "text"), "text");
+    assert_eq!(fmt("-6s", "ab"), "ab    ");
+    assert_eq!(fmt("6s", "ab"), "    ab");
+    assert_eq!(fmt(".2s", "abc"), "ab");
+    assert_eq!(fmt("d", "-42"), "-42");
+    assert_eq!(fmt("+d", "42"), "+42");
+    assert_eq!(fmt("05d", "-42"), "-0042");
+    assert_eq!(fmt("u", "4294967295"), "4294967295");
+    assert_eq!(fmt("x", "255"), "ff");
+    assert_eq!(fmt("08X", "0xbeef"), "0000BEEF");
+    assert_eq!(fmt("#x", "255"), "0xff");
+    assert_eq!(fmt("x", "-1"), "ffffffff");
+    assert_eq!(fmt("I64x", "-1"), "ffffffffffffffff");
+    assert_eq!(fmt("lu", "7"), "7");
+    assert_eq!(fmt("c", "65"), "A");
+    assert_eq!(fmt("d", "not a number"), "not a number");
+    assert_eq!(fmt("d", "--5"), "--5");
+    assert_eq!(fmt("x", "0x-5"), "0x-5");
+    assert_eq!(fmt("d", "-+5"), "-+5");
+    assert_eq!(fmt("d", "+5"), "5");
+
+    // Widths and precisions too big to honour insert the value as is
+    assert_eq!(fmt("99999999999999999999s", "x"), "x");
+    assert_eq!(fmt("4000000000s", "x"), "x");
+    assert_eq!(fmt(".4000000000d", "7"), "7");
+    assert_eq!(fmt("1024s", "x").len(), 1024);
+
+    // An unterminated spec is just text
+    assert_eq!(format_message("%1!s", &["a"]), "a!s");
+    assert_eq!(format_message("%2!s!", &["a"]), "%2!s!");
+
+    // Text that isn't ASCII, in the spec or after it
+    assert_eq!(format_message("%1!s! für %2", &["a", "b"]), "a für b");
+    assert_eq!(format_message("%1!ü! after", &["a"]), "a after");
+    assert_eq!(format_message("%1!s — ü", &["a"]), "a!s — ü");
+}
+
+#[test]
+fn parameter_references() {
+    let params = |id| match id {
+        1842 => Some("Yes\r\n".to_string()),
+        1843 => Some("No".to_string()),
+        _ => None,
+    };
+
+    assert_eq!(
+        format_message_with_params("Elevated: %1, Virtual: %2", &["%%1842", "%%1843"], params),
+        "Elevated: Yes, Virtual: No"
+    );
+    assert_eq!(
+        format_message_with_params("%1", &["%%9999 and %%1843"], params),
+        "%%9999 and No"
+    );
+    assert_eq!(format_message("%1", &["%%1842"]), "%%1842");
+}
+
+#[test]
+fn event_data_values() {
+    let xml = r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event">
+        <System><EventID>4688</EventID></System>
+        <EventData><Data Name="SubjectUserName">SYSTEM</Data><Data Name="NewProcessName">cmd.exe</Data><Data Name="Empty"/></EventData>
+    </Event>"#;
+    assert_eq!(event_data(xml).unwrap(), vec!["SYSTEM", "cmd.exe", ""]);
+
+    let xml = r#"<Event><System/><UserData><LogFileCleared xmlns="x"><SubjectUserName>bob</SubjectUserName><SubjectDomainName>CORP</SubjectDomainName></LogFileCleared></UserData></Event>"#;
+    assert_eq!(event_data(xml).unwrap(), vec!["bob", "CORP"]);
+
+    assert!(event_data("<Event>").is_err());
+}
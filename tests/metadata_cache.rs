@@ -0,0 +1,101 @@
+use std::env;
+use std::fs;
+
+use win_events::metadata_cache::{CacheFormat, MetadataCache, ProviderKey};
+use win_events::pub_metadata::{EventMetadata, Keyword, Level, PubMetadata};
+
+fn sysmon(guid: &str) -> PubMetadata {
+    PubMetadata {
+        guid: Some(guid.to_string()),
+        message_file_path: Some(r"C:\Windows\Sysmon64.exe".into()),
+        levels: vec![Level {
+            name: Some("win:Informational".into()),
+            id: Some(4),
+            message_id: Some(0x5000_0004),
+        }],
+        keywords: vec![Keyword {
+            name: Some("ProcessCreate".into()),
+            mask: Some(0x8000_0000_0000_0000),
+            message_id: None,
+        }],
+        events: vec![EventMetadata {
+            id: 1,
+            version: 5,
+            channel: 16,
+            level: 4,
+            task: 1,
+            keywords: 0x8000_0000_0000_0000,
+            message_id: Some(0x1),
+            template: Some("<template tid=\"t1\"/>".into()),
+            ..EventMetadata::default()
+        }],
+        ..PubMetadata::default()
+    }
+}
+
+fn cache() -> MetadataCache {
+    let mut cache = MetadataCache::new();
+    cache.insert(
+        ProviderKey::new("Microsoft-Windows-Sysmon", Some("15.0".into()), "en-US"),
+        sysmon("{15}"),
+    );
+    cache.insert(
+        ProviderKey::new("Microsoft-Windows-Sysmon", Some("14.0".into()), "en-US"),
+        sysmon("{14}"),
+    );
+    cache.insert(
+        ProviderKey::new("Microsoft-Windows-Sysmon", None, "de-DE"),
+        sysmon("{de}"),
+    );
+    cache
+}
+
+#[test]
+fn round_trips() {
+    let cache = cache();
+    for format in [CacheFormat::Json, CacheFormat::Cbor] {
+        let data = cache.to_vec(format).unwrap();
+        assert_eq!(MetadataCache::from_slice(&data).unwrap(), cache);
+    }
+}
+
+#[test]
+fn finds_the_closest_match() {
+    let cache = cache();
+    let guid = |version, locale| {
+        cache
+            .find("microsoft-windows-sysmon", version, locale)
+            .and_then(|(_, meta)| meta.guid.clone())
+    };
+
+    assert_eq!(guid(Some("14.0"), "en-US").as_deref(), Some("{14}"));
+    assert_eq!(guid(Some("13.0"), "de-DE").as_deref(), Some("{de}"));
+    assert!(guid(None, "fr-FR").is_some());
+    assert!(cache.find("Security-Auditing", None, "en-US").is_none());
+}
+
+#[test]
+fn files() {
+    let dir = env::temp_dir().join(format!("wevents-cache-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    assert!(MetadataCache::load(dir.join("missing.json"))
+        .unwrap()
+        .is_empty());
+
+    for name in ["meta.json", "meta.cbor"] {
+        let path = dir.join(name);
+        cache().save(&path).unwrap();
+        assert_eq!(MetadataCache::load(&path).unwrap(), cache());
+    }
+    assert!(fs::read(dir.join("meta.json")).unwrap().starts_with(b"{"));
+    assert!(!fs::read(dir.join("meta.cbor")).unwrap().starts_with(b"{"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rejects_other_versions() {
+    assert!(MetadataCache::from_slice(br#"{"version": 2, "providers": []}"#).is_err());
+    assert!(MetadataCache::from_slice(b"not a cache").is_err());
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use win_events::event::Event;
+use win_events::metadata_cache::{MetadataCache, ProviderKey};
+use win_events::names::{EventNames, NamingWriter};
+use win_events::output::{to_json, EventWriter};
+use win_events::pub_metadata::{Keyword, Level, OpCode, PubMetadata, Task};
+
+use common::Collect;
+
+fn meta() -> PubMetadata {
+    PubMetadata {
+        levels: vec![
+            Level {
+                name: Some("win:Error".into()),
+                id: Some(2),
+                message_id: Some(0x5000_0002),
+            },
+            Level {
+                name: Some("Chatty".into()),
+                id: Some(16),
+                message_id: None,
+            },
+        ],
+        tasks: vec![Task {
+            name: Some("Logon".into()),
+            value: Some(12544),
+            message_id: Some(0x7000_3100),
+            ..Task::default()
+        }],
+        opcodes: vec![
+            OpCode {
+                name: Some("Resume".into()),
+                opcode_value: Some(10),
+                task_id: Some(12544),
+                message_id: None,
+            },
+            OpCode {
+                name: Some("Verify".into()),
+                opcode_value: Some(11),
+                task_id: Some(0),
+                message_id: None,
+            },
+        ],
+        keywords: vec![
+            Keyword {
+                name: Some("Disk".into()),
+                mask: Some(0x1),
+                message_id: None,
+            },
+            Keyword {
+                name: Some("Storage".into()),
+                mask: Some(0x6),
+                message_id: None,
+            },
+            Keyword {
+                name: Some("win:AuditSuccess".into()),
+                mask: Some(0x0020_0000_0000_0000),
+                message_id: None,
+            },
+        ],
+        ..PubMetadata::default()
+    }
+}
+
+fn event(provider: &str, level: u8, task: u16, opcode: u8, keywords: u64) -> String {
+    format!(
+        "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System>\
+         <Provider Name='{}'/><EventID>4624</EventID><Level>{}</Level><Task>{}</Task>\
+         <Opcode>{}</Opcode><Keywords>0x{:x}</Keywords></System></Event>",
+        provider, level, task, opcode, keywords
+    )
+}
+
+#[test]
+fn names_from_metadata() {
+    let meta = meta();
+    let names = EventNames::new(&meta);
+
+    assert_eq!(names.level(2).as_deref(), Some("Error"));
+    assert_eq!(names.level(16).as_deref(), Some("Chatty"));
+    assert_eq!(names.task(12544).as_deref(), Some("Logon"));
+    assert_eq!(names.task(7), None);
+    assert_eq!(names.opcode(12544, 10).as_deref(), Some("Resume"));
+    assert_eq!(names.opcode(12544, 11).as_deref(), Some("Verify"));
+    assert_eq!(names.opcode(1, 10), None);
+    assert_eq!(
+        names.keywords(0x8020_0000_0000_0007),
+        vec!["Disk", "Storage", "Audit Success"]
+    );
+    assert_eq!(names.keywords(0x2), Vec::<String>::new());
+}
+
+#[test]
+fn standard_fallbacks() {
+    let none = PubMetadata::default();
+    let names = EventNames::new(&none);
+
+    assert_eq!(names.level(0).as_deref(), Some("Information"));
+    assert_eq!(names.level(4).as_deref(), Some("Information"));
+    assert_eq!(names.level(16), None);
+    assert_eq!(names.opcode(0, 2).as_deref(), Some("Stop"));
+    assert_eq!(names.opcode(0, 240).as_deref(), Some("Receive"));
+    assert_eq!(
+        names.keywords(0x0090_0000_0000_0001),
+        vec!["Audit Failure", "Classic"]
+    );
+}
+
+#[test]
+fn message_text_wins() {
+    let meta = meta();
+    let names = EventNames::new(&meta).messages(|id| match id {
+        0x5000_0002 => Some("Fehler\r\n".to_string()),
+        0x7000_3100 => Some("Anmelden".to_string()),
+        _ => None,
+    });
+
+    assert_eq!(names.level(2).as_deref(), Some("Fehler"));
+    assert_eq!(names.task(12544).as_deref(), Some("Anmelden"));
+    assert_eq!(names.level(16).as_deref(), Some("Chatty"));
+}
+
+#[test]
+fn enriches_rendered_events() {
+    let meta = meta();
+    let names = EventNames::new(&meta);
+
+    let xml = names
+        .enrich(&event("Security", 0, 12544, 0, 0x8020_0000_0000_0000))
+        .unwrap();
+    assert!(xml.ends_with(
+        "</System><RenderingInfo><Level>Information</Level><Task>Logon</Task>\
+         <Opcode>Info</Opcode><Keywords><Keyword>Audit Success</Keyword></Keywords>\
+         </RenderingInfo></Event>"
+    ));
+
+    let info = Event::from_xml(&xml).unwrap().rendering_info.unwrap();
+    assert_eq!(info.task.as_deref(), Some("Logon"));
+    assert_eq!(info.keywords, vec!["Audit Success"]);
+
+    let json = to_json(&Event::from_xml(&xml).unwrap());
+    assert_eq!(json["TaskName"], "Logon");
+    assert_eq!(json["KeywordNames"][0], "Audit Success");
+
+    // Already enriched, or nothing to add
+    assert_eq!(names.enrich(&xml).unwrap(), xml);
+    let bare = "<Event><System><EventID>1</EventID></System></Event>";
+    assert_eq!(names.enrich(bare).unwrap(), bare);
+}
+
+#[test]
+fn naming_writer_uses_the_cache() {
+    let mut cache = MetadataCache::new();
+    cache.insert(ProviderKey::new("Contoso", None, "en-US"), meta());
+
+    let mut out = Collect::default();
+    let mut writer = NamingWriter::new(&mut out, cache);
+    writer.write_event(&event("contoso", 16, 0, 0, 0)).unwrap();
+    writer.write_event(&event("Unknown", 16, 0, 2, 0)).unwrap();
+    writer.finish().unwrap();
+
+    let info = |xml: &str| Event::from_xml(xml).unwrap().rendering_info.unwrap();
+    assert_eq!(info(&out.0[0]).level.as_deref(), Some("Chatty"));
+    assert_eq!(info(&out.0[1]).level, None);
+    assert_eq!(info(&out.0[1]).opcode.as_deref(), Some("Stop"));
+}
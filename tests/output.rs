@@ -0,0 +1,90 @@
+use win_events::errors::WinEvtError;
+use win_events::event::Event;
+use win_events::output::{to_json, EventWriter, Format, JsonWriter};
+
+const LOGON: &str = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'>\
+    <System><Provider Name='Microsoft-Windows-Security-Auditing' \
+    Guid='{54849625-5478-4994-A5BA-3E3B0328C30D}'/><EventID>4624</EventID>\
+    <Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode>\
+    <Keywords>0x8020000000000000</Keywords>\
+    <TimeCreated SystemTime='2019-05-17T16:16:09.2851463Z'/>\
+    <EventRecordID>57622</EventRecordID>\
+    <Correlation ActivityID='{F7E6DA3A-0C4B-0001-4BDB-E6F74B0CD501}'/>\
+    <Execution ProcessID='708' ThreadID='4412'/><Channel>Security</Channel>\
+    <Computer>DESKTOP-1</Computer><Security/></System><EventData>\
+    <Data Name='SubjectUserSid'>S-1-5-18</Data><Data Name='LogonType'>5</Data>\
+    <Data Name='Binary'>named</Data><Data Name='Ip'>-</Data><Data Name='Ip'>::1</Data>\
+    <Data>unnamed</Data><Binary>DEADBEEF</Binary></EventData>\
+    <RenderingInfo Culture='en-US'><Message>An account was successfully logged on.</Message>\
+    <Level>Information</Level><Task>Logon</Task><Opcode>Info</Opcode>\
+    <Keywords><Keyword>Audit Success</Keyword></Keywords></RenderingInfo></Event>";
+
+const LOGON_JSON: &str = r##"{"ProviderName":"Microsoft-Windows-Security-Auditing","ProviderGuid":"{54849625-5478-4994-A5BA-3E3B0328C30D}","EventID":4624,"Version":2,"Level":0,"Task":12544,"Opcode":0,"Keywords":"0x8020000000000000","TimeCreated":"2019-05-17T16:16:09.2851463Z","EventRecordID":57622,"ActivityID":"{F7E6DA3A-0C4B-0001-4BDB-E6F74B0CD501}","ProcessID":708,"ThreadID":4412,"Channel":"Security","Computer":"DESKTOP-1","Message":"An account was successfully logged on.","LevelName":"Information","TaskName":"Logon","OpcodeName":"Info","KeywordNames":["Audit Success"],"EventData":{"SubjectUserSid":"S-1-5-18","LogonType":"5","Binary":"named","Ip":["-","::1"],"param6":"unnamed","#Binary":"DEADBEEF"}}"##;
+
+const CLEARED: &str = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'>\
+    <System><Provider Name='Microsoft-Windows-Eventlog'/>\
+    <EventID Qualifiers='0'>1102</EventID><EventRecordID>9</EventRecordID>\
+    <Security UserID='S-1-5-18'/></System><UserData>\
+    <LogFileCleared xmlns='http://manifests.microsoft.com/win/2004/08/windows/eventlog'>\
+    <SubjectUserSid>S-1-5-21-1</SubjectUserSid><Sub Kind='a'>x</Sub><Sub Kind='b'/>\
+    </LogFileCleared><Extra>more</Extra></UserData></Event>";
+
+const CLEARED_JSON: &str = r##"{"ProviderName":"Microsoft-Windows-Eventlog","EventID":1102,"Qualifiers":0,"EventRecordID":9,"UserID":"S-1-5-18","UserData":{"LogFileCleared":{"SubjectUserSid":"S-1-5-21-1","Sub":[{"@Kind":"a","#text":"x"},{"@Kind":"b"}]},"Extra":"more"}}"##;
+
+fn json(xml: &str) -> String {
+    to_json(&Event::from_xml(xml).unwrap()).to_string()
+}
+
+#[test]
+fn json_layout_is_stable() {
+    assert_eq!(json(LOGON), LOGON_JSON);
+    assert_eq!(json(CLEARED), CLEARED_JSON);
+    assert_eq!(json("<Event/>"), "{}");
+}
+
+fn write(format: Format, events: &[&str]) -> String {
+    let mut out = Vec::new();
+    let mut writer = format.writer(&mut out);
+    for xml in events {
+        writer.write_event(xml).unwrap();
+    }
+    writer.finish().unwrap();
+    drop(writer);
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn writes_ndjson() {
+    assert_eq!(
+        write(Format::NdJson, &[LOGON, CLEARED]),
+        format!("{}\n{}\n", LOGON_JSON, CLEARED_JSON)
+    );
+    assert_eq!(write(Format::NdJson, &[]), "");
+}
+
+#[test]
+fn writes_a_json_array() {
+    assert_eq!(
+        write(Format::Json, &[LOGON, CLEARED]),
+        format!("[\n{},\n{}\n]\n", LOGON_JSON, CLEARED_JSON)
+    );
+    assert_eq!(write(Format::Json, &[]), "[]\n");
+
+    // Nothing is written for an event that doesn't parse, and the writer
+    // carries on with the next
+    let mut out = Vec::new();
+    let mut writer = JsonWriter::array(&mut out);
+    let err = writer.write_event("<Event>").unwrap_err();
+    assert!(matches!(err, WinEvtError::Parse { .. }), "{:?}", err);
+    writer.write_event(CLEARED).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(out, format!("[\n{}\n]\n", CLEARED_JSON).as_bytes());
+}
+
+#[test]
+fn writes_xml_as_rendered() {
+    assert_eq!(
+        write(Format::Xml, &[LOGON, CLEARED]),
+        format!("{}\n{}\n", LOGON, CLEARED)
+    );
+}
@@ -0,0 +1,200 @@
+use std::env;
+use std::fs;
+
+use win_events::pe::{mui_path, MessageTable};
+
+const SECTION_RVA: u32 = 0x1000;
+const SECTION_OFFSET: usize = 0x200;
+
+fn put_u16(buf: &mut [u8], at: usize, v: u16) {
+    buf[at..at + 2].copy_from_slice(&v.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], at: usize, v: u32) {
+    buf[at..at + 4].copy_from_slice(&v.to_le_bytes());
+}
+
+/// A `MESSAGE_RESOURCE_DATA` with one block of consecutive IDs
+fn message_data(first: u32, messages: &[(&str, bool)]) -> Vec<u8> {
+    let mut entries = Vec::new();
+    for (text, unicode) in messages {
+        let mut bytes: Vec<u8> = if *unicode {
+            text.encode_utf16()
+                .chain([0])
+                .flat_map(u16::to_le_bytes)
+                .collect()
+        } else {
+            text.bytes().chain([0]).collect()
+        };
+        bytes.resize((bytes.len() + 3) & !3, 0);
+        entries.extend(((bytes.len() + 4) as u16).to_le_bytes());
+        entries.extend((*unicode as u16).to_le_bytes());
+        entries.extend(bytes);
+    }
+
+    let mut data = Vec::new();
+    data.extend(1u32.to_le_bytes());
+    data.extend(first.to_le_bytes());
+    data.extend((first + messages.len() as u32 - 1).to_le_bytes());
+    data.extend(16u32.to_le_bytes());
+    data.extend(entries);
+    data
+}
+
+/// A PE32+ image with a `.rsrc` section holding message table 1 in each
+/// of the languages given
+fn pe(tables: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    pe_with_types(tables, &[])
+}
+
+/// Offset of the language directory within the `.rsrc` section of a
+/// [`pe_with_types`] image with `types` other types
+fn lang_dir(types: usize) -> u32 {
+    16 + 8 * (types as u32 + 1) + 0x18
+}
+
+/// Like [`pe`], with other resource type entries before the message table's
+fn pe_with_types(tables: &[(u16, Vec<u8>)], types: &[(u32, u32)]) -> Vec<u8> {
+    let langs = lang_dir(types.len()) as usize;
+    let names = langs - 0x18;
+    let mut entries = types.to_vec();
+    entries.push((11, 0x8000_0000 | names as u32));
+
+    // Type, name and language directories, then the data entries and data
+    let mut rsrc = vec![0; langs + 16 + tables.len() * 8];
+    put_u16(&mut rsrc, 14, entries.len() as u16);
+    for (i, (name, target)) in entries.iter().enumerate() {
+        put_u32(&mut rsrc, 16 + i * 8, *name);
+        put_u32(&mut rsrc, 20 + i * 8, *target);
+    }
+    rsrc[names + 14] = 1;
+    put_u32(&mut rsrc, names + 16, 1);
+    put_u32(&mut rsrc, names + 20, 0x8000_0000 | langs as u32);
+    put_u16(&mut rsrc, langs + 14, tables.len() as u16);
+
+    for (i, (lang, data)) in tables.iter().enumerate() {
+        let entry = rsrc.len();
+        rsrc.extend([0; 16]);
+        let blob = rsrc.len();
+        rsrc.extend(data);
+
+        put_u32(&mut rsrc, langs + 16 + i * 8, *lang as u32);
+        put_u32(&mut rsrc, langs + 20 + i * 8, entry as u32);
+        put_u32(&mut rsrc, entry, SECTION_RVA + blob as u32);
+        put_u32(&mut rsrc, entry + 4, data.len() as u32);
+    }
+
+    let mut image = vec![0; SECTION_OFFSET];
+    image[..2].copy_from_slice(b"MZ");
+    put_u32(&mut image, 0x3c, 0x40);
+    image[0x40..0x44].copy_from_slice(b"PE\0\0");
+    let coff = 0x44;
+    put_u16(&mut image, coff, 0x8664);
+    put_u16(&mut image, coff + 2, 1);
+    put_u16(&mut image, coff + 16, 240);
+    let optional = coff + 20;
+    put_u16(&mut image, optional, 0x20b);
+    put_u32(&mut image, optional + 108, 16);
+    put_u32(&mut image, optional + 112 + 16, SECTION_RVA);
+    put_u32(&mut image, optional + 112 + 20, rsrc.len() as u32);
+
+    let section = optional + 240;
+    image[section..section + 5].copy_from_slice(b".rsrc");
+    put_u32(&mut image, section + 8, rsrc.len() as u32);
+    put_u32(&mut image, section + 12, SECTION_RVA);
+    put_u32(&mut image, section + 16, rsrc.len() as u32);
+    put_u32(&mut image, section + 20, SECTION_OFFSET as u32);
+
+    image.extend(rsrc);
+    image
+}
+
+#[test]
+fn reads_each_language() {
+    let image = pe(&[
+        (
+            0x409,
+            message_data(
+                0x1000,
+                &[("Process %1 started.\r\n", true), ("Plain ANSI\r\n", false)],
+            ),
+        ),
+        (
+            0x407,
+            message_data(0x1000, &[("Prozess %1 gestartet.\r\n", true)]),
+        ),
+    ]);
+    let table = MessageTable::from_pe(&image).unwrap();
+
+    assert_eq!(table.languages().collect::<Vec<_>>(), vec![0x407, 0x409]);
+    assert_eq!(table.get(0x1000, 0x409), Some("Process %1 started.\r\n"));
+    assert_eq!(table.get(0x1000, 0x407), Some("Prozess %1 gestartet.\r\n"));
+    assert_eq!(table.get(0x1001, 0x409), Some("Plain ANSI\r\n"));
+
+    // Missing from German, so it falls back to English
+    assert_eq!(table.get(0x1001, 0x407), Some("Plain ANSI\r\n"));
+    assert_eq!(table.get(0x1000, 0x40c), Some("Process %1 started.\r\n"));
+    assert_eq!(table.get(0x2000, 0x409), None);
+    assert_eq!(table.messages(0x409).count(), 2);
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(MessageTable::from_pe(b"not an image").is_err());
+    assert!(MessageTable::from_pe(&pe(&[])).is_err());
+
+    let mut truncated = pe(&[(0x409, message_data(1, &[("x", true)]))]);
+    truncated.truncate(truncated.len() - 8);
+    assert!(MessageTable::from_pe(&truncated).is_err());
+
+    // A section whose file offset runs off the end of the address space
+    let mut image = pe(&[(0x409, message_data(1, &[("x", true)]))]);
+    let optional = 0x44 + 20;
+    put_u32(&mut image, optional + 112 + 16, SECTION_RVA + 0x20);
+    put_u32(&mut image, optional + 240 + 20, 0xffff_fff0);
+    assert!(MessageTable::from_pe(&image).is_err());
+}
+
+#[test]
+fn skips_other_resource_types() {
+    let tables = [(0x409, message_data(1, &[("x\r\n", true)]))];
+    // A leaf where a name directory should be, and a directory of leaves
+    // where a directory of directories should be
+    let image = pe_with_types(&tables, &[(24, 0x10), (16, 0x8000_0000 | lang_dir(2))]);
+
+    let table = MessageTable::from_pe(&image).unwrap();
+    assert_eq!(table.get(1, 0x409), Some("x\r\n"));
+}
+
+#[test]
+fn walks_each_directory_once() {
+    let tables = [(0x409, message_data(1, &[("x\r\n", true)]))];
+    // Thousands of entries looping back to the root, of other types and of
+    // the message table's
+    let mut types = vec![(24, 0x8000_0000); 2000];
+    types.extend(vec![(11, 0x8000_0000); 2000]);
+    let image = pe_with_types(&tables, &types);
+
+    let table = MessageTable::from_pe(&image).unwrap();
+    assert_eq!(table.get(1, 0x409), Some("x\r\n"));
+}
+
+#[test]
+fn mui_satellites() {
+    let dir = env::temp_dir().join(format!("wevents-pe-{}", std::process::id()));
+    fs::create_dir_all(dir.join("en-US")).unwrap();
+
+    // A MUI-aware file has no messages itself
+    let dll = dir.join("provider.dll");
+    fs::write(&dll, pe(&[])).unwrap();
+    assert!(MessageTable::open_localized(&dll, "en-US").is_err());
+
+    let mui = mui_path(&dll, "en-US").unwrap();
+    assert_eq!(mui, dir.join("en-US").join("provider.dll.mui"));
+    fs::write(&mui, pe(&[(0x409, message_data(7, &[("From MUI", true)]))])).unwrap();
+
+    let table = MessageTable::open_localized(&dll, "en-US").unwrap();
+    assert_eq!(table.get(7, 0x409), Some("From MUI"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
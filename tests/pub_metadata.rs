@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use win_events::errors::WinEvtError;
+use win_events::pub_metadata::{
+    Channel, Keyword, Level, OpCode, PropValue, PubMetaProp, PubMetadata, PubMetadataProps, Task,
+};
+
+use PubMetaProp::*;
+
+/// Properties held in maps; array elements are keyed by array, index and property
+#[derive(Default)]
+struct FakeProps {
+    props: HashMap<PubMetaProp, PropValue>,
+    arrays: HashMap<PubMetaProp, u32>,
+    elements: HashMap<(PubMetaProp, u32, PubMetaProp), PropValue>,
+    fail: Option<PubMetaProp>,
+}
+
+impl FakeProps {
+    fn set(&mut self, prop: PubMetaProp, value: PropValue) {
+        self.props.insert(prop, value);
+    }
+
+    fn push(&mut self, array: PubMetaProp, element: Vec<(PubMetaProp, PropValue)>) {
+        let index = self.arrays.entry(array).or_insert(0);
+        for (prop, value) in element {
+            self.elements.insert((array, *index, prop), value);
+        }
+        *index += 1;
+    }
+}
+
+impl PubMetadataProps for FakeProps {
+    fn prop(&mut self, prop: PubMetaProp) -> Result<PropValue, WinEvtError> {
+        if self.fail == Some(prop) {
+            return Err(WinEvtError::new(5, "access denied"));
+        }
+        Ok(self.props.get(&prop).cloned().unwrap_or(PropValue::Null))
+    }
+
+    fn array_len(&mut self, array: PubMetaProp) -> Result<u32, WinEvtError> {
+        Ok(self.arrays.get(&array).copied().unwrap_or(0))
+    }
+
+    fn array_prop(
+        &mut self,
+        array: PubMetaProp,
+        index: u32,
+        prop: PubMetaProp,
+    ) -> Result<PropValue, WinEvtError> {
+        assert!(index < self.arrays[&array]);
+        if self.fail == Some(prop) {
+            return Err(WinEvtError::new(5, "access denied"));
+        }
+        Ok(self
+            .elements
+            .get(&(array, index, prop))
+            .cloned()
+            .unwrap_or(PropValue::Null))
+    }
+}
+
+fn string(s: &str) -> PropValue {
+    PropValue::String(s.to_string())
+}
+
+fn sysmon() -> FakeProps {
+    let mut props = FakeProps::default();
+    props.set(
+        PublisherGuid,
+        PropValue::Guid("{5770385F-C22A-43E0-BF4C-06F5698FFBD9}".into()),
+    );
+    props.set(MessageFilePath, string(r"C:\Windows\Sysmon64.exe"));
+    props.set(PublisherMessageId, PropValue::UInt32(0xFFFF_FFFF));
+
+    props.push(
+        ChannelReferences,
+        vec![
+            (
+                ChannelReferencePath,
+                string("Microsoft-Windows-Sysmon/Operational"),
+            ),
+            (ChannelReferenceIndex, PropValue::UInt32(0)),
+            (ChannelReferenceId, PropValue::UInt32(16)),
+            (ChannelReferenceFlags, PropValue::UInt32(0)),
+            (ChannelReferenceMessageId, PropValue::UInt32(0x9000_0001)),
+        ],
+    );
+    props.push(
+        ChannelReferences,
+        vec![
+            (ChannelReferencePath, string("Application")),
+            (ChannelReferenceIndex, PropValue::UInt32(1)),
+            (ChannelReferenceId, PropValue::UInt32(9)),
+            (ChannelReferenceFlags, PropValue::UInt32(1)),
+            (ChannelReferenceMessageId, PropValue::UInt32(0xFFFF_FFFF)),
+        ],
+    );
+    props.push(
+        Levels,
+        vec![
+            (LevelName, string("win:Informational")),
+            (LevelValue, PropValue::UInt32(4)),
+            (LevelMessageId, PropValue::UInt32(0x5000_0004)),
+        ],
+    );
+    props.push(
+        Tasks,
+        vec![
+            (TaskName, string("ProcessCreate")),
+            (TaskValue, PropValue::UInt32(1)),
+            (TaskMessageId, PropValue::UInt32(0x7000_0001)),
+        ],
+    );
+    props.push(
+        Opcodes,
+        vec![
+            (OpcodeName, string("win:Info")),
+            (OpcodeValue, PropValue::UInt32(0x0000_0001)),
+        ],
+    );
+    props.push(
+        Opcodes,
+        vec![
+            (OpcodeName, string("Stop")),
+            (OpcodeValue, PropValue::UInt32(0x0002_0003)),
+        ],
+    );
+    props.push(
+        Keywords,
+        vec![
+            (KeywordName, string("ProcessCreate")),
+            (KeywordValue, PropValue::UInt64(0x8000_0000_0000_0000)),
+        ],
+    );
+    props
+}
+
+#[test]
+fn assembles_everything() {
+    let meta = PubMetadata::fetch(&mut sysmon()).unwrap();
+
+    assert_eq!(
+        meta.guid.as_deref(),
+        Some("{5770385F-C22A-43E0-BF4C-06F5698FFBD9}")
+    );
+    assert_eq!(
+        meta.message_file_path.as_deref(),
+        Some(r"C:\Windows\Sysmon64.exe")
+    );
+    assert_eq!(meta.resource_file_path, None);
+    assert_eq!(meta.message_id, None);
+
+    assert_eq!(
+        meta.channels,
+        vec![
+            Channel {
+                name: Some("Microsoft-Windows-Sysmon/Operational".into()),
+                index: Some(0),
+                id: Some(16),
+                imported: false,
+                message_id: Some(0x9000_0001),
+            },
+            Channel {
+                name: Some("Application".into()),
+                index: Some(1),
+                id: Some(9),
+                imported: true,
+                message_id: None,
+            },
+        ]
+    );
+    assert_eq!(
+        meta.levels,
+        vec![Level {
+            name: Some("win:Informational".into()),
+            id: Some(4),
+            message_id: Some(0x5000_0004),
+        }]
+    );
+    assert_eq!(
+        meta.tasks,
+        vec![Task {
+            name: Some("ProcessCreate".into()),
+            guid: None,
+            value: Some(1),
+            message_id: Some(0x7000_0001),
+        }]
+    );
+    assert_eq!(
+        meta.opcodes,
+        vec![
+            OpCode {
+                name: Some("win:Info".into()),
+                opcode_value: Some(0),
+                task_id: Some(1),
+                message_id: None,
+            },
+            OpCode {
+                name: Some("Stop".into()),
+                opcode_value: Some(2),
+                task_id: Some(3),
+                message_id: None,
+            },
+        ]
+    );
+    assert_eq!(
+        meta.keywords,
+        vec![Keyword {
+            name: Some("ProcessCreate".into()),
+            mask: Some(0x8000_0000_0000_0000),
+            message_id: None,
+        }]
+    );
+}
+
+#[test]
+fn missing_arrays_are_empty() {
+    let mut props = FakeProps::default();
+    props.set(HelpLink, string("https://example.com"));
+
+    let meta = PubMetadata::fetch(&mut props).unwrap();
+    assert_eq!(meta.help_link.as_deref(), Some("https://example.com"));
+    assert!(meta.channels.is_empty());
+    assert!(meta.keywords.is_empty());
+}
+
+#[test]
+fn errors_are_returned() {
+    let mut props = sysmon();
+    props.fail = Some(TaskValue);
+    assert!(PubMetadata::fetch(&mut props).is_err());
+}
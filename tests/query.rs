@@ -0,0 +1,113 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use win_events::query::QueryBuilder;
+
+#[test]
+fn empty_matches_everything() {
+    assert_eq!(QueryBuilder::new().build().unwrap(), "*");
+}
+
+#[test]
+fn event_ids_are_alternatives() {
+    let xpath = QueryBuilder::new()
+        .event_ids(vec![4624, 4625])
+        .event_id_range(4720..=4726)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        xpath,
+        "*[System[(EventID=4624 or EventID=4625 or (EventID>=4720 and EventID<=4726))]]"
+    );
+}
+
+#[test]
+fn system_conditions_are_combined() {
+    let xpath = QueryBuilder::new()
+        .level(1)
+        .level(2)
+        .provider("Microsoft-Windows-Security-Auditing")
+        .any_keywords(0x0010_0000_0000_0000)
+        .any_keywords(0x0020_0000_0000_0000)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        xpath,
+        "*[System[(Level=1 or Level=2) and \
+         Provider[@Name='Microsoft-Windows-Security-Auditing'] and \
+         band(Keywords,13510798882111488)]]"
+    );
+}
+
+#[test]
+fn time_ranges() {
+    let since = UNIX_EPOCH + Duration::from_secs(1_558_109_769);
+    let until = since + Duration::from_millis(1_500);
+
+    let xpath = QueryBuilder::new()
+        .since(since)
+        .until(until)
+        .build()
+        .unwrap();
+    assert_eq!(
+        xpath,
+        "*[System[TimeCreated[@SystemTime>='2019-05-17T16:16:09.0000000Z' and \
+         @SystemTime<='2019-05-17T16:16:10.5000000Z']]]"
+    );
+
+    let xpath = QueryBuilder::new()
+        .within_last(Duration::from_secs(86_400))
+        .build()
+        .unwrap();
+    assert_eq!(
+        xpath,
+        "*[System[TimeCreated[timediff(@SystemTime)<=86400000]]]"
+    );
+}
+
+#[test]
+fn event_data_matches_must_all_hold() {
+    let xpath = QueryBuilder::new()
+        .data("TargetUserName", "bob")
+        .data("LogonType", "3")
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        xpath,
+        "*[EventData[Data[@Name='TargetUserName']='bob'] and \
+         EventData[Data[@Name='LogonType']='3']]"
+    );
+}
+
+#[test]
+fn quotes() {
+    let xpath = QueryBuilder::new()
+        .data("Path", "C:\\it's")
+        .build()
+        .unwrap();
+    assert_eq!(xpath, "*[EventData[Data[@Name='Path']=\"C:\\it's\"]]");
+
+    assert!(QueryBuilder::new()
+        .provider("both ' and \"")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn query_list_escapes_xml() {
+    let list = QueryBuilder::new()
+        .event_id(1)
+        .data("CommandLine", "a && b")
+        .query_list("Microsoft-Windows-Sysmon/Operational")
+        .unwrap();
+
+    assert_eq!(
+        list,
+        "<QueryList><Query Id=\"0\" Path=\"Microsoft-Windows-Sysmon/Operational\">\
+         <Select Path=\"Microsoft-Windows-Sysmon/Operational\">\
+         *[System[EventID=1] and EventData[Data[@Name='CommandLine']='a &amp;&amp; b']]\
+         </Select></Query></QueryList>"
+    );
+}
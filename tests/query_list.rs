@@ -0,0 +1,99 @@
+mod common;
+
+use win_events::fixture::FixtureSource;
+use win_events::query_list::{Query, QueryList};
+
+use common::event_with;
+
+/// Security records 1-4 are logons at levels 0-3, System records 1-3 are
+/// service events at levels 1-3
+fn source() -> FixtureSource {
+    let mut source = FixtureSource::new();
+    for id in 1..=4 {
+        source.add_event("Security", event_with("Security", id, 4624, id as u8 - 1));
+    }
+    for id in 1..=3 {
+        source.add_event("System", event_with("System", id, 7036, id as u8));
+    }
+    source
+}
+
+/// The channel and record ID of each event returned
+fn run(list: &QueryList) -> Vec<(String, u64)> {
+    list.events(&source())
+        .unwrap()
+        .map(|xml| {
+            let xml = xml.unwrap();
+            let field = |tag: &str| {
+                let start = xml.find(&format!("<{}>", tag)).unwrap() + tag.len() + 2;
+                let end = xml.find(&format!("</{}>", tag)).unwrap();
+                xml[start..end].to_string()
+            };
+            (field("Channel"), field("EventRecordID").parse().unwrap())
+        })
+        .collect()
+}
+
+fn ids(chan: &str, ids: &[u64]) -> Vec<(String, u64)> {
+    ids.iter().map(|&id| (chan.to_string(), id)).collect()
+}
+
+#[test]
+fn suppress_drops_matching_events() {
+    let list = QueryList::new().query(
+        Query::new("Security")
+            .select("*[System[Level<=2]]")
+            .suppress("*[System[Level=1]]"),
+    );
+    assert_eq!(run(&list), ids("Security", &[1, 3]));
+
+    // Only within the query and channel it's given for
+    let list = QueryList::new()
+        .query(
+            Query::new("Security")
+                .select("*")
+                .suppress_from("System", "*"),
+        )
+        .query(Query::new("System").select("*[System[Level=3]]"));
+    let mut expected = ids("Security", &[1, 2, 3, 4]);
+    expected.extend(ids("System", &[3]));
+    assert_eq!(run(&list), expected);
+
+    // A suppress that doesn't parse fails the whole list up front
+    let list = QueryList::new().query(Query::new("Security").select("*").suppress("*[System["));
+    assert!(list.events(&source()).is_err());
+}
+
+#[test]
+fn overlapping_selects_return_events_once() {
+    // Within one query
+    let list = QueryList::new().query(
+        Query::new("Security")
+            .select("*[System[Level<=1]]")
+            .select("*[System[Level>=1]]"),
+    );
+    assert_eq!(run(&list), ids("Security", &[1, 2, 3, 4]));
+
+    // Across queries, including what an earlier query suppressed
+    let list = QueryList::new()
+        .query(
+            Query::new("Security")
+                .select("*[System[Level<=2]]")
+                .suppress("*[System[Level=0]]"),
+        )
+        .query(Query::new("System").select("*"))
+        .query(Query::new("Security").select("*"));
+    let mut expected = ids("Security", &[2, 3]);
+    expected.extend(ids("System", &[1, 2, 3]));
+    expected.extend(ids("Security", &[1, 4]));
+    assert_eq!(run(&list), expected);
+
+    // The same record ID in different channels is a different event
+    let list = QueryList::new()
+        .query(Query::new("Security").select("*[System[EventRecordID=1]]"))
+        .query(Query::new("System").select("*[System[EventRecordID=1]]"))
+        .query(Query::new("Security").select("*[System[EventRecordID<=1]]"));
+    let mut expected = ids("Security", &[1]);
+    expected.extend(ids("System", &[1]));
+    assert_eq!(run(&list), expected);
+}
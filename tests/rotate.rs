@@ -0,0 +1,190 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use win_events::codec::Codec;
+use win_events::output::{EventWriter, Format};
+use win_events::rotate::{RotatingWriter, Rotation};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("wevents-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// An event rendering to exactly 250 bytes with its newline
+fn event(id: u64) -> String {
+    let xml = common::event("Security", id);
+    format!("{:<241}</Event>", xml.strip_suffix("</Event>").unwrap())
+}
+
+fn names(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+fn read(path: &Path) -> String {
+    let mut out = String::new();
+    std::io::Read::read_to_string(&mut Codec::open(path).unwrap(), &mut out).unwrap();
+    out
+}
+
+#[test]
+fn names_segments() {
+    let rotating = Rotation {
+        max_size: Some(1),
+        max_age: None,
+    };
+    let writer =
+        |path: &str, rotation| RotatingWriter::new(path, Format::Xml, Codec::Gzip, 3, rotation);
+
+    let w = writer("out/events.xml.gz", rotating);
+    assert_eq!(w.segment_path(0), Path::new("out/events-00000.xml.gz"));
+    assert_eq!(w.segment_path(12), Path::new("out/events-00012.xml.gz"));
+    assert_eq!(
+        writer("events", rotating).segment_path(3),
+        Path::new("events-00003")
+    );
+    assert_eq!(
+        writer("out/events.xml.gz", Rotation::default()).segment_path(3),
+        Path::new("out/events.xml.gz")
+    );
+}
+
+#[test]
+fn rotates_by_compressed_size() {
+    let dir = temp_dir("rotate-size");
+
+    // Uncompressed, the size written is the size of the events
+    let rotation = Rotation {
+        max_size: Some(600),
+        max_age: None,
+    };
+    let mut w = RotatingWriter::new(
+        dir.join("events.xml"),
+        Format::Xml,
+        Codec::None,
+        0,
+        rotation,
+    );
+    for id in 1..=7 {
+        w.write_event(&event(id)).unwrap();
+    }
+    w.finish().unwrap();
+
+    assert_eq!(
+        names(w.segments()),
+        vec!["events-00000.xml", "events-00001.xml", "events-00002.xml"]
+    );
+    let sizes: Vec<_> = w
+        .segments()
+        .iter()
+        .map(|p| fs::metadata(p).unwrap().len())
+        .collect();
+    assert_eq!(sizes, vec![750, 750, 250]);
+
+    // Compressed, far more fits before the limit
+    let rotation = Rotation {
+        max_size: Some(1000),
+        max_age: None,
+    };
+    let mut w = RotatingWriter::new(
+        dir.join("events.xml.zst"),
+        Format::Xml,
+        Codec::Zstd,
+        3,
+        rotation,
+    );
+    for id in 1..=100 {
+        w.write_event(&event(id)).unwrap();
+    }
+    w.finish().unwrap();
+    assert_eq!(w.segments().len(), 1);
+    assert!(fs::metadata(&w.segments()[0]).unwrap().len() < 1000);
+    assert_eq!(read(&w.segments()[0]).lines().count(), 100);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rotates_by_age() {
+    let dir = temp_dir("rotate-age");
+    let rotation = Rotation {
+        max_size: None,
+        max_age: Some(Duration::from_millis(200)),
+    };
+    let mut w = RotatingWriter::new(
+        dir.join("events.json.gz"),
+        Format::Json,
+        Codec::Gzip,
+        3,
+        rotation,
+    );
+
+    w.write_event(&event(1)).unwrap();
+    w.write_event(&event(2)).unwrap();
+    thread::sleep(Duration::from_millis(300));
+    // Only checked as the next event arrives
+    assert!(w.segments().is_empty());
+    w.write_event(&event(3)).unwrap();
+    assert_eq!(names(w.segments()), vec!["events-00000.json.gz"]);
+    w.finish().unwrap();
+
+    // Every file is a complete JSON array
+    let counts: Vec<_> = w
+        .segments()
+        .iter()
+        .map(|p| {
+            let json: serde_json::Value = serde_json::from_str(&read(p)).unwrap();
+            json.as_array().unwrap().len()
+        })
+        .collect();
+    assert_eq!(counts, vec![2, 1]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn never_overwrites_segments() {
+    let dir = temp_dir("rotate-existing");
+    fs::write(dir.join("events-00000.xml"), "earlier").unwrap();
+    fs::write(dir.join("events-00002.xml"), "earlier").unwrap();
+
+    let rotation = Rotation {
+        max_size: Some(100),
+        max_age: None,
+    };
+    let mut w = RotatingWriter::new(
+        dir.join("events.xml"),
+        Format::Xml,
+        Codec::None,
+        0,
+        rotation,
+    );
+    for id in 1..=3 {
+        w.write_event(&event(id)).unwrap();
+    }
+    w.finish().unwrap();
+
+    assert_eq!(
+        names(w.segments()),
+        vec!["events-00001.xml", "events-00003.xml", "events-00004.xml"]
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("events-00000.xml")).unwrap(),
+        "earlier"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("events-00002.xml")).unwrap(),
+        "earlier"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
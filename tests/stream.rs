@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use futures::executor::block_on;
+use futures::StreamExt;
+
+use win_events::fixture::FixtureSource;
+use win_events::mock_publisher::MockPublisher;
+use win_events::source::StartAt;
+use win_events::stream::EventStream;
+
+fn fixtures() -> FixtureSource {
+    let mut source = FixtureSource::new();
+    for id in 1..=3 {
+        source.add_event(
+            "Security",
+            format!("<Event><System><EventID>{}</EventID></System></Event>", id),
+        );
+    }
+    source.add_channel("System");
+    source
+}
+
+#[test]
+fn channel_events() {
+    let events = EventStream::events(fixtures(), "Security", Some("*[System[EventID>1]]".into()));
+    let events: Vec<_> = block_on(events.collect::<Vec<_>>());
+
+    assert_eq!(events.len(), 2);
+    assert!(events[0].as_ref().unwrap().contains("<EventID>2<"));
+}
+
+#[test]
+fn channel_names() {
+    let names = block_on(EventStream::channels(fixtures()).collect::<Vec<_>>());
+    let names: Vec<_> = names.into_iter().map(Result::unwrap).collect();
+    assert_eq!(names, vec!["Security", "System"]);
+}
+
+#[test]
+fn open_errors() {
+    let events = block_on(EventStream::events(fixtures(), "Missing", None).collect::<Vec<_>>());
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_err());
+}
+
+#[test]
+fn subscriptions() {
+    let publisher = MockPublisher::new();
+    let mut events = EventStream::subscribe(publisher.clone(), "Security", None, StartAt::Oldest);
+
+    publisher.publish(
+        "Security",
+        "<Event><System><EventID>1</EventID></System></Event>",
+    );
+    let first = block_on(events.next()).unwrap().unwrap();
+    assert!(first.contains("<EventID>1<"));
+
+    publisher.close();
+    assert!(block_on(events.next()).is_none());
+}
+
+#[test]
+fn reads_ahead_only_so_far() {
+    let read = Arc::new(AtomicUsize::new(0));
+    let counter = read.clone();
+    let mut events = EventStream::spawn(4, move || {
+        Ok((0..1000).map(move |n| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(n)
+        }))
+    });
+
+    thread::sleep(Duration::from_millis(100));
+    assert!(read.load(Ordering::SeqCst) <= 6);
+
+    assert_eq!(block_on(events.next()).unwrap().unwrap(), 0);
+    drop(events);
+}
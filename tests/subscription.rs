@@ -0,0 +1,63 @@
+mod common;
+
+use std::thread;
+
+use win_events::bookmark;
+use win_events::mock_publisher::MockPublisher;
+use win_events::source::{LiveSource, StartAt};
+
+use common::event_with;
+
+fn ids<I: Iterator<Item = Result<String, win_events::errors::WinEvtError>>>(events: I) -> Vec<u64> {
+    events
+        .map(|e| bookmark::record_id(&e.unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn start_positions() {
+    let publisher = MockPublisher::new();
+    for id in 1..=3 {
+        publisher.publish("Security", event_with("Security", id, id as u32 % 2, 0));
+    }
+
+    let now = publisher.subscribe("Security", None, StartAt::Now).unwrap();
+    let oldest = publisher
+        .subscribe("Security", None, StartAt::Oldest)
+        .unwrap();
+    let after = publisher
+        .subscribe("Security", None, StartAt::After(2))
+        .unwrap();
+
+    publisher.publish("Security", event_with("Security", 4, 0, 0));
+    publisher.publish("System", event_with("System", 5, 1, 0));
+    publisher.close();
+
+    assert_eq!(ids(now), vec![4]);
+    assert_eq!(ids(oldest), vec![1, 2, 3, 4]);
+    assert_eq!(ids(after), vec![3, 4]);
+}
+
+#[test]
+fn follows_events_as_published() {
+    let publisher = MockPublisher::new();
+    let events = publisher
+        .subscribe("Security", Some("*[System[EventID=1]]"), StartAt::Now)
+        .unwrap();
+
+    let reader = thread::spawn(move || ids(events.take(3)));
+
+    for id in 1..=6 {
+        publisher.publish("Security", event_with("Security", id, id as u32 % 2, 0));
+    }
+
+    assert_eq!(reader.join().unwrap(), vec![1, 3, 5]);
+}
+
+#[test]
+fn bad_query() {
+    let publisher = MockPublisher::new();
+    assert!(publisher
+        .subscribe("Security", Some("*[System[EventID=]]"), StartAt::Now)
+        .is_err());
+}
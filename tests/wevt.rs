@@ -0,0 +1,230 @@
+use win_events::pub_metadata::{Channel, EventMetadata, Keyword, Level, OpCode, Task};
+use win_events::wevt;
+
+const GUID: [u8; 16] = [
+    0x90, 0x38, 0x5c, 0x5f, 0x6e, 0xa4, 0x44, 0x4e, 0x91, 0xf9, 0xe2, 0xfa, 0x2d, 0xcb, 0x97, 0x53,
+];
+const GUID_STR: &str = "{5F5C3890-A46E-4E44-91F9-E2FA2DCB9753}";
+
+struct Manifest(Vec<u8>);
+
+impl Manifest {
+    fn at(&self) -> u32 {
+        self.0.len() as u32
+    }
+
+    fn u8s(&mut self, values: &[u8]) {
+        self.0.extend(values);
+    }
+
+    fn u16s(&mut self, values: &[u16]) {
+        values.iter().for_each(|v| self.0.extend(v.to_le_bytes()));
+    }
+
+    fn u32s(&mut self, values: &[u32]) {
+        values.iter().for_each(|v| self.0.extend(v.to_le_bytes()));
+    }
+
+    fn patch(&mut self, at: usize, v: u32) {
+        self.0[at..at + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        let at = self.at();
+        let units: Vec<u16> = name.encode_utf16().chain([0]).collect();
+        self.u32s(&[4 + units.len() as u32 * 2]);
+        self.u16s(&units);
+        at
+    }
+
+    /// A block header, with the size left to patch
+    fn element(&mut self, sig: &[u8], count: u32) -> u32 {
+        let at = self.at();
+        self.u8s(sig);
+        self.u32s(&[0, count]);
+        at
+    }
+}
+
+/// A manifest for one provider, with one of each of the things it defines
+fn manifest() -> Vec<u8> {
+    const ELEMENTS: usize = 8;
+    let mut m = Manifest(Vec::new());
+
+    m.u8s(b"CRIM");
+    m.u32s(&[0]);
+    m.u16s(&[3, 1]);
+    m.u32s(&[1]);
+    m.u8s(&GUID);
+    m.u32s(&[36]);
+
+    m.u8s(b"WEVT");
+    m.u32s(&[0, 0x9000_0001, ELEMENTS as u32, 0]);
+    let descriptors = m.0.len();
+    m.u8s(&[0; ELEMENTS * 8]);
+
+    let chan = m.name("Microsoft-Windows-Sysmon/Operational");
+    let levl = m.name("win:Informational");
+    let task = m.name("Process Create");
+    let opco = m.name("win:Info");
+    let keyw = m.name("ProcessCreate");
+    let image = m.name("Image");
+    let pid = m.name("ProcessId");
+
+    let mut offsets = Vec::new();
+
+    offsets.push(m.element(b"CHAN", 1));
+    m.u32s(&[16, chan, 0, 0xFFFF_FFFF]);
+
+    offsets.push(m.element(b"LEVL", 1));
+    m.u32s(&[4, 0x5000_0004, levl]);
+
+    offsets.push(m.element(b"TASK", 1));
+    m.u32s(&[1, 0x7000_0001]);
+    m.u8s(&GUID);
+    m.u32s(&[task]);
+
+    offsets.push(m.element(b"OPCO", 1));
+    m.u16s(&[1, 0]);
+    m.u32s(&[0x3000_0000, opco]);
+
+    offsets.push(m.element(b"KEYW", 1));
+    m.0.extend(0x8000_0000_0000_0000u64.to_le_bytes());
+    m.u32s(&[0xFFFF_FFFF, keyw]);
+
+    offsets.push(m.element(b"TTBL", 1));
+    let temp = m.at();
+    m.u8s(b"TEMP");
+    m.u32s(&[0, 2, 2, temp + 40, 1]);
+    m.u8s(&[0; 16]);
+    m.u32s(&[0]);
+    m.u8s(&[1, 1]);
+    m.u16s(&[0]);
+    m.u32s(&[0]);
+    m.u16s(&[1, 0]);
+    m.u32s(&[image, 0]);
+    m.u8s(&[8, 0]);
+    m.u16s(&[0]);
+    m.u32s(&[0]);
+    m.u16s(&[1, 0]);
+    m.u32s(&[pid]);
+
+    offsets.push(m.element(b"EVNT", 2));
+    m.u32s(&[0]);
+    m.u16s(&[1]);
+    m.u8s(&[5, 16, 4, 0]);
+    m.u16s(&[1]);
+    m.0.extend(0x8000_0000_0000_0000u64.to_le_bytes());
+    m.u32s(&[0xB000_0001, temp, 0, 0, 0, 0, 0, 0]);
+    m.u16s(&[255]);
+    m.u8s(&[0, 16, 2, 0]);
+    m.u16s(&[0]);
+    m.0.extend(0u64.to_le_bytes());
+    m.u32s(&[0xFFFF_FFFF, 0, 0, 0, 0, 0, 0, 0]);
+
+    // Provider attributes, which aren't kept
+    offsets.push(m.element(b"PRVA", 0));
+
+    for (i, offset) in offsets.into_iter().enumerate() {
+        m.patch(descriptors + i * 8, offset);
+    }
+    let size = m.at();
+    m.patch(4, size);
+    m.0
+}
+
+#[test]
+fn reads_a_provider() {
+    let providers = wevt::parse(&manifest()).unwrap();
+    assert_eq!(providers.len(), 1);
+    let meta = &providers[0];
+
+    assert_eq!(meta.guid.as_deref(), Some(GUID_STR));
+    assert_eq!(meta.message_id, Some(0x9000_0001));
+    assert_eq!(
+        meta.channels,
+        vec![Channel {
+            name: Some("Microsoft-Windows-Sysmon/Operational".into()),
+            index: Some(0),
+            id: Some(16),
+            imported: false,
+            message_id: None,
+        }]
+    );
+    assert_eq!(
+        meta.levels,
+        vec![Level {
+            name: Some("win:Informational".into()),
+            id: Some(4),
+            message_id: Some(0x5000_0004),
+        }]
+    );
+    assert_eq!(
+        meta.tasks,
+        vec![Task {
+            name: Some("Process Create".into()),
+            guid: Some(GUID_STR.into()),
+            value: Some(1),
+            message_id: Some(0x7000_0001),
+        }]
+    );
+    assert_eq!(
+        meta.opcodes,
+        vec![OpCode {
+            name: Some("win:Info".into()),
+            opcode_value: Some(0),
+            task_id: Some(1),
+            message_id: Some(0x3000_0000),
+        }]
+    );
+    assert_eq!(
+        meta.keywords,
+        vec![Keyword {
+            name: Some("ProcessCreate".into()),
+            mask: Some(0x8000_0000_0000_0000),
+            message_id: None,
+        }]
+    );
+}
+
+#[test]
+fn reads_events_and_templates() {
+    let events = wevt::parse(&manifest()).unwrap().remove(0).events;
+
+    assert_eq!(
+        events,
+        vec![
+            EventMetadata {
+                id: 1,
+                version: 5,
+                channel: 16,
+                level: 4,
+                opcode: 0,
+                task: 1,
+                keywords: 0x8000_0000_0000_0000,
+                message_id: Some(0xB000_0001),
+                template: Some(
+                    "<template xmlns=\"http://schemas.microsoft.com/win/2004/08/events\">\
+                     <data name=\"Image\" inType=\"win:UnicodeString\" outType=\"xs:string\"/>\
+                     <data name=\"ProcessId\" inType=\"win:UInt32\"/>\
+                     </template>"
+                        .into()
+                ),
+            },
+            EventMetadata {
+                id: 255,
+                channel: 16,
+                level: 2,
+                ..EventMetadata::default()
+            },
+        ]
+    );
+}
+
+#[test]
+fn rejects_other_data() {
+    let data = manifest();
+    assert!(wevt::parse(&data[..data.len() - 40]).is_err());
+    assert!(wevt::parse(b"MZ not a manifest").is_err());
+    assert!(wevt::from_pe(&data).is_err());
+}
@@ -0,0 +1,201 @@
+use std::time::{Duration, SystemTime};
+
+use win_events::errors::WinEvtError;
+use win_events::filetime;
+use win_events::fixture::FixtureSource;
+use win_events::query::QueryBuilder;
+use win_events::query_list::{Query, QueryList};
+use win_events::source::{ChannelSource, EventRenderer, EventSource};
+use win_events::xpath::{FilteredEvents, FilteredRenderer, XPathFilter};
+
+const LOGON: &str = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'>\
+    <System><Provider Name='Microsoft-Windows-Security-Auditing'/>\
+    <EventID>4624</EventID><Level>0</Level><Keywords>0x8020000000000000</Keywords>\
+    <TimeCreated SystemTime='2019-05-17T16:16:09.2851463Z'/>\
+    <EventRecordID>7</EventRecordID><Channel>Security</Channel></System>\
+    <EventData><Data Name='TargetUserName'>bob</Data><Data Name='LogonType'>3</Data></EventData>\
+    </Event>";
+
+fn matches(xpath: &str, xml: &str) -> bool {
+    XPathFilter::parse(xpath).unwrap().matches(xml).unwrap()
+}
+
+#[test]
+fn system_tests() {
+    assert!(matches("*", LOGON));
+    assert!(matches("*[System[EventID=4624]]", LOGON));
+    assert!(matches("Event/System[EventID!=4625]", LOGON));
+    assert!(!matches("*[System[EventID=4625]]", LOGON));
+    assert!(matches(
+        "*[System[(EventID>=4624 and EventID<=4634)]]",
+        LOGON
+    ));
+    assert!(matches(
+        "*[System[Provider[@Name='Microsoft-Windows-Security-Auditing']]]",
+        LOGON
+    ));
+    assert!(matches("*[System[Level=0 or Level=4]]", LOGON));
+}
+
+#[test]
+fn event_data() {
+    assert!(matches("*[EventData[Data[@Name='LogonType']=3]]", LOGON));
+    assert!(matches(
+        "*[EventData[Data[@Name='TargetUserName']='bob']]",
+        LOGON
+    ));
+    assert!(!matches(
+        "*[EventData[Data[@Name='TargetUserName']='3']]",
+        LOGON
+    ));
+    assert!(matches("*[EventData[Data='bob']]", LOGON));
+}
+
+#[test]
+fn functions_and_not() {
+    assert!(matches(
+        "*[System[band(Keywords,0x0020000000000000)]]",
+        LOGON
+    ));
+    assert!(matches(
+        "*[System[band(Keywords,9007199254740992)=9007199254740992]]",
+        LOGON
+    ));
+    assert!(!matches("*[System[band(Keywords,0x10)]]", LOGON));
+    assert!(matches("not(*[System[band(Keywords,0x10)]])", LOGON));
+
+    assert!(!matches(
+        "*[System[TimeCreated[timediff(@SystemTime) <= 86400000]]]",
+        LOGON
+    ));
+
+    let now = filetime::to_system_time(filetime::from_system_time(
+        SystemTime::now() - Duration::from_secs(60),
+    ));
+    let recent = LOGON.replace("2019-05-17T16:16:09.2851463Z", &now);
+    assert!(matches(
+        "*[System[TimeCreated[timediff(@SystemTime) <= 86400000]]]",
+        &recent
+    ));
+}
+
+#[test]
+fn times_compare_as_times() {
+    assert!(matches(
+        "*[System[TimeCreated[@SystemTime>='2019-05-17T16:16:09.285Z']]]",
+        LOGON
+    ));
+    assert!(!matches(
+        "*[System[TimeCreated[@SystemTime>'2019-05-17T16:16:09.2851463Z']]]",
+        LOGON
+    ));
+
+    // Years a FILETIME can't hold are just text
+    let far = "<Event><EventData><Data>99999-01-01T00:00:00Z</Data></EventData></Event>";
+    assert!(!matches("*[EventData[Data='a']]", far));
+    assert!(matches("*[EventData[Data='99999-01-01T00:00:00Z']]", far));
+    assert_eq!(filetime::parse_system_time("1600-12-31T23:59:59Z"), None);
+    assert_eq!(filetime::parse_system_time("30828-01-01T00:00:00Z"), None);
+    assert!(filetime::parse_system_time("30827-12-31T23:59:59.9999999Z").is_some());
+}
+
+#[test]
+fn bad_queries() {
+    assert!(XPathFilter::parse("*[System[EventID=]]").is_err());
+    assert!(XPathFilter::parse("*[System[EventID=1]").is_err());
+    assert!(XPathFilter::parse("*[System[count(EventID)=1]]").is_err());
+    assert!(XPathFilter::parse("*[EventData[Data='bob]]").is_err());
+}
+
+#[test]
+fn builder_output_evaluates() {
+    let xpath = QueryBuilder::new()
+        .event_ids(vec![4624, 4625])
+        .provider("Microsoft-Windows-Security-Auditing")
+        .any_keywords(0x0020_0000_0000_0000)
+        .data("LogonType", "3")
+        .build()
+        .unwrap();
+
+    assert!(matches(&xpath, LOGON));
+}
+
+#[test]
+fn offline_sources_filter() {
+    let mut source = FixtureSource::new();
+    source.add_event("Security", LOGON);
+    source.add_event(
+        "Security",
+        LOGON
+            .replace("4624", "4625")
+            .replace("<EventRecordID>7", "<EventRecordID>8"),
+    );
+
+    let count = |query| source.events_for("Security", Some(query)).unwrap().count();
+    assert_eq!(count("*"), 2);
+    assert_eq!(count("*[System[EventID=4625]]"), 1);
+
+    let list = QueryList::new().query(
+        Query::new("Security")
+            .select("*")
+            .suppress("*[System[EventID=4624]]"),
+    );
+    let events = list
+        .events(&source)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].contains("4625"));
+}
+
+struct Events(std::vec::IntoIter<String>);
+
+impl Iterator for Events {
+    type Item = Result<String, WinEvtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Ok)
+    }
+}
+
+impl EventSource for Events {
+    type Event = String;
+}
+
+/// Counts the events it renders
+#[derive(Default)]
+struct Counting {
+    rendered: usize,
+}
+
+impl EventRenderer<String> for Counting {
+    fn render(&mut self, event: String) -> Result<String, WinEvtError> {
+        self.rendered += 1;
+        Ok(event)
+    }
+}
+
+#[test]
+fn filtered_events_are_rendered_once() {
+    let other = LOGON.replace("4624", "4625");
+    let events = || Events(vec![LOGON.to_string(), other.clone()].into_iter());
+    let render = |filtered: FilteredEvents<Events, Counting>| {
+        let mut rend = FilteredRenderer(Counting::default());
+        let xml: Vec<_> = filtered.map(|e| rend.render(e.unwrap()).unwrap()).collect();
+        (xml, rend.0.rendered)
+    };
+
+    // The filter renders every event, and those it keeps aren't rendered again
+    let filtered = FilteredEvents::new(
+        events(),
+        Some("*[System[EventID=4625]]"),
+        Counting::default(),
+    )
+    .unwrap();
+    assert_eq!(render(filtered), (vec![other.clone()], 0));
+
+    // Without a filter, only as they're consumed
+    let unfiltered = FilteredEvents::new(events(), None, Counting::default()).unwrap();
+    assert_eq!(render(unfiltered), (vec![LOGON.to_string(), other], 2));
+}